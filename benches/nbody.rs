@@ -0,0 +1,177 @@
+// 受力计算与积分器的性能基准, 用 `cargo bench` 运行.
+// 初始条件是固定种子的 Plummer 球, 每次运行的输入完全相同
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use nbody_simulation::diagnostics::total_energy;
+use nbody_simulation::force::{pair_softening_sq, softened_acceleration};
+use nbody_simulation::generators::generate_plummer;
+use nbody_simulation::integrators::{drift, kick, update_forces};
+use nbody_simulation::{
+    integrate_step, update_accelerations, Bodies, Body, BoundaryCondition, ForceMethod, ForceParams, Integrator, Real,
+    SofteningKernel, Vec3, G,
+};
+use rayon::prelude::*;
+use std::hint::black_box;
+
+const SEED: u64 = 42;
+const TOTAL_MASS: Real = 1.0e30;
+const SCALE_RADIUS: Real = 1.0e11;
+
+fn params(method: ForceMethod) -> ForceParams {
+    ForceParams {
+        g: G,
+        softening: 0.01 * SCALE_RADIUS,
+        kernel: SofteningKernel::Plummer,
+        method,
+        boundary: BoundaryCondition::Open,
+        external: None,
+        friction: None,
+        planar: false,
+        force_exponent: 2.0,
+    }
+}
+
+// 生成初始条件并算好初始受力, 积分器要求进入时加速度 (与 jerk) 已是当前值
+fn initial_bodies(n: usize, integrator: Integrator, params: &ForceParams) -> Vec<Body> {
+    let mut bodies = generate_plummer(n, TOTAL_MASS, SCALE_RADIUS, SEED, G);
+    update_forces(&mut bodies, integrator, params);
+    bodies
+}
+
+fn bench_forces(c: &mut Criterion) {
+    let methods = [
+        ("direct", ForceMethod::Direct),
+        ("pairwise", ForceMethod::Pairwise),
+        ("barnes_hut", ForceMethod::BarnesHut { theta: 0.5 }),
+    ];
+    let mut group = c.benchmark_group("update_accelerations");
+    // N = 10000 的直接求和单次就要数秒, 减少采样数
+    group.sample_size(10);
+    for n in [100, 1000, 10000] {
+        for (name, method) in methods {
+            let params = params(method);
+            let mut bodies = initial_bodies(n, Integrator::Leapfrog, &params);
+            group.bench_with_input(BenchmarkId::new(name, n), &n, |b, _| {
+                b.iter(|| update_accelerations(black_box(&mut bodies), &params))
+            });
+        }
+    }
+    group.finish();
+}
+
+// 单个粒子对的核函数, 与直接按定义 G m d / (|d|^2 + eps^2)^1.5 计算的参考公式对比
+fn bench_kernel(c: &mut Criterion) {
+    let bodies = generate_plummer(1000, TOTAL_MASS, SCALE_RADIUS, SEED, G);
+    let softening_sq = (0.01 * SCALE_RADIUS) * (0.01 * SCALE_RADIUS);
+    let pairs: Vec<(Vec3, Real)> = bodies.windows(2).map(|w| (w[1].position - w[0].position, w[1].mass)).collect();
+    let mut group = c.benchmark_group("softened_acceleration");
+    group.bench_function("reference", |b| {
+        b.iter(|| {
+            pairs.iter().fold(Vec3::ZERO, |sum, &(d, m)| {
+                let (d, m) = black_box((d, m));
+                sum + d * (G * m / (d.length_squared() + softening_sq).powf(1.5))
+            })
+        })
+    });
+    group.bench_function("kernel", |b| {
+        b.iter(|| {
+            pairs.iter().fold(Vec3::ZERO, |sum, &(d, m)| {
+                let (d, m) = black_box((d, m));
+                sum + softened_acceleration(d, G * m, softening_sq)
+            })
+        })
+    });
+    group.finish();
+}
+
+// 分块的直接求和与每个目标粒子都读一遍全部场源的朴素并行循环 (同样的结构体数组与核函数) 对比;
+// N = 50000 时场源数组约 2 MiB, 超出 L2 缓存
+fn bench_tiling(c: &mut Criterion) {
+    let n = 50000;
+    let params = params(ForceMethod::Direct);
+    let mut bodies = initial_bodies(n, Integrator::Leapfrog, &params);
+    let mut soa = Bodies::from_bodies(&bodies, params.softening);
+    let mut group = c.benchmark_group("direct_tiling");
+    group.sample_size(10);
+    group.bench_with_input(BenchmarkId::new("naive", n), &n, |b, _| {
+        b.iter(|| {
+            let Bodies { mass, position, acceleration, softening_sq, .. } = &mut soa;
+            acceleration.par_iter_mut().enumerate().for_each(|(i, acceleration_i)| {
+                let (pos_i, softening_sq_i) = (position[i], softening_sq[i]);
+                let mut total = Vec3::ZERO;
+                for ((pos_j, mass_j), softening_sq_j) in position.iter().zip(mass.iter()).zip(softening_sq.iter()) {
+                    if pos_i != *pos_j {
+                        let softening_sq = pair_softening_sq(softening_sq_i, *softening_sq_j);
+                        total += params.kernel.acceleration(*pos_j - pos_i, params.g * *mass_j, softening_sq);
+                    }
+                }
+                *acceleration_i = total;
+            })
+        })
+    });
+    group.bench_with_input(BenchmarkId::new("tiled", n), &n, |b, _| {
+        b.iter(|| update_accelerations(black_box(&mut bodies), &params))
+    });
+    group.finish();
+}
+
+fn bench_integrators(c: &mut Criterion) {
+    let integrators = [
+        ("leapfrog", Integrator::Leapfrog),
+        ("verlet", Integrator::VelocityVerlet),
+        ("yoshida4", Integrator::Yoshida4),
+        ("rk4", Integrator::Rk4),
+        ("hermite", Integrator::Hermite),
+        ("dopri5", Integrator::Dopri5),
+    ];
+    let params = params(ForceMethod::Direct);
+    let n = 1000;
+    let dt = 1.0e3;
+    let mut group = c.benchmark_group("integrate_step");
+    group.sample_size(20);
+    for (name, integrator) in integrators {
+        let bodies = initial_bodies(n, integrator, &params);
+        // 每次迭代从同一初始状态出发, 复制不计入耗时
+        group.bench_with_input(BenchmarkId::new(name, n), &n, |b, _| {
+            b.iter_batched_ref(
+                || bodies.clone(),
+                |bodies| integrate_step(bodies, integrator, dt, &params),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+// 并行的 kick 与 drift 与串行循环对比, 不计算受力 (N = 100000 的受力单次就要数分钟).
+// 每个粒子只有几次浮点运算, 受内存带宽限制; 加速度取自生成器的初始值 (零) 不影响耗时
+fn bench_kick_drift(c: &mut Criterion) {
+    let n = 100_000;
+    let dt = 1.0e3;
+    let mut bodies = generate_plummer(n, TOTAL_MASS, SCALE_RADIUS, SEED, G);
+    let mut group = c.benchmark_group("kick_drift");
+    group.bench_with_input(BenchmarkId::new("kick_serial", n), &n, |b, _| {
+        b.iter(|| black_box(&mut bodies).iter_mut().for_each(|body| body.velocity += body.acceleration * dt))
+    });
+    group.bench_with_input(BenchmarkId::new("kick_parallel", n), &n, |b, _| b.iter(|| kick(black_box(&mut bodies), dt)));
+    group.bench_with_input(BenchmarkId::new("drift_serial", n), &n, |b, _| {
+        b.iter(|| black_box(&mut bodies).iter_mut().for_each(|body| body.position += body.velocity * dt))
+    });
+    group.bench_with_input(BenchmarkId::new("drift_parallel", n), &n, |b, _| b.iter(|| drift(black_box(&mut bodies), dt)));
+    group.finish();
+}
+
+fn bench_energy(c: &mut Criterion) {
+    let params = params(ForceMethod::Direct);
+    let mut group = c.benchmark_group("total_energy");
+    group.sample_size(10);
+    for n in [100, 1000, 10000] {
+        let bodies = initial_bodies(n, Integrator::Leapfrog, &params);
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            b.iter(|| total_energy(black_box(&bodies), &params))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_forces, bench_kernel, bench_tiling, bench_integrators, bench_kick_drift, bench_energy);
+criterion_main!(benches);
@@ -0,0 +1,206 @@
+// Barnes-Hut 八叉树, 用单极近似 (质心 + 总质量) 把引力计算降到 O(N log N)
+use crate::force::{minimum_image, pair_softening_sq, SofteningKernel};
+use crate::{Body, Real, Vec3};
+
+// 细分的最大深度, 超过后把剩余粒子合并在同一叶节点中 (处理重合粒子)
+const MAX_DEPTH: usize = 64;
+
+// 空子节点的标记
+const EMPTY: usize = usize::MAX;
+
+struct Node {
+    center: Vec3,
+    half_size: Real,
+    mass: Real,
+    // 插入时累加 m * x, 建树完成后归一化为质心
+    com: Vec3,
+    children: [usize; 8],
+    // 叶节点中的粒子个数
+    count: usize,
+    // 单粒子叶节点中粒子的精确位置
+    position: Vec3,
+    // 插入时累加 m * eps^2, 建树完成后归一化为质量加权的软化长度平方
+    softening_sq: Real,
+}
+
+impl Node {
+    fn new(center: Vec3, half_size: Real) -> Self {
+        Self { center, half_size, mass: 0.0, com: Vec3::ZERO, children: [EMPTY; 8], count: 0, position: Vec3::ZERO, softening_sq: 0.0 }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.iter().all(|&c| c == EMPTY)
+    }
+
+    fn octant(&self, pos: Vec3) -> usize {
+        (pos.x >= self.center.x) as usize
+            | ((pos.y >= self.center.y) as usize) << 1
+            | ((pos.z >= self.center.z) as usize) << 2
+    }
+}
+
+pub struct Octree {
+    nodes: Vec<Node>,
+}
+
+impl Octree {
+    // 根据当前粒子位置建树, 每一步都需要重建; softening 为没有自己软化长度的粒子使用的全局值
+    pub fn new(bodies: &[Body], softening: Real) -> Self {
+        let (mut min, mut max) = (Vec3::splat(Real::MAX), Vec3::splat(Real::MIN));
+        for body in bodies {
+            min = min.min(body.position);
+            max = max.max(body.position);
+        }
+        let (center, half_size) = if bodies.is_empty() {
+            (Vec3::ZERO, 1.0)
+        } else {
+            // 稍微放大, 保证边界上的粒子落在根节点内部
+            ((min + max) / 2.0, ((max - min).max_element() / 2.0).max(Real::MIN_POSITIVE) * 1.0001)
+        };
+
+        let mut tree = Self { nodes: vec![Node::new(center, half_size)] };
+        for body in bodies {
+            tree.insert(0, body.position, body.mass, body.softening_sq(softening), 0);
+        }
+        for node in tree.nodes.iter_mut() {
+            // 单粒子叶节点直接用精确位置, 保证自身能被识别并跳过
+            if node.count == 1 {
+                node.com = node.position;
+            } else if node.mass > 0.0 {
+                node.com /= node.mass;
+            }
+            if node.mass > 0.0 {
+                node.softening_sq /= node.mass;
+            }
+        }
+        tree
+    }
+
+    fn insert(&mut self, index: usize, pos: Vec3, mass: Real, softening_sq: Real, depth: usize) {
+        // 空叶节点直接存放
+        if self.nodes[index].count == 0 && self.nodes[index].is_leaf() {
+            let node = &mut self.nodes[index];
+            node.mass = mass;
+            node.com = pos * mass;
+            node.softening_sq = softening_sq * mass;
+            node.count = 1;
+            node.position = pos;
+            return;
+        }
+
+        if self.nodes[index].is_leaf() {
+            if depth >= MAX_DEPTH {
+                let node = &mut self.nodes[index];
+                node.mass += mass;
+                node.com += pos * mass;
+                node.softening_sq += softening_sq * mass;
+                node.count += 1;
+                return;
+            }
+            // 细分: 把原有的单个粒子下放到子节点
+            let old_mass = self.nodes[index].mass;
+            let old_pos = self.nodes[index].position;
+            let old_softening_sq = if old_mass > 0.0 { self.nodes[index].softening_sq / old_mass } else { 0.0 };
+            self.nodes[index].mass = 0.0;
+            self.nodes[index].com = Vec3::ZERO;
+            self.nodes[index].softening_sq = 0.0;
+            self.nodes[index].count = 0;
+            self.insert_into_child(index, old_pos, old_mass, old_softening_sq, depth);
+        }
+
+        self.insert_into_child(index, pos, mass, softening_sq, depth);
+    }
+
+    fn insert_into_child(&mut self, index: usize, pos: Vec3, mass: Real, softening_sq: Real, depth: usize) {
+        let node = &mut self.nodes[index];
+        node.mass += mass;
+        node.com += pos * mass;
+        node.softening_sq += softening_sq * mass;
+
+        let octant = node.octant(pos);
+        let mut child = node.children[octant];
+        if child == EMPTY {
+            let quarter = node.half_size / 2.0;
+            let offset = Vec3::new(
+                if octant & 1 != 0 { quarter } else { -quarter },
+                if octant & 2 != 0 { quarter } else { -quarter },
+                if octant & 4 != 0 { quarter } else { -quarter },
+            );
+            let new_node = Node::new(node.center + offset, quarter);
+            child = self.nodes.len();
+            self.nodes[index].children[octant] = child;
+            self.nodes.push(new_node);
+        }
+        self.insert(child, pos, mass, softening_sq, depth + 1);
+    }
+
+    // 遍历树计算 body 受到的加速度, 节点尺寸与距离之比小于 theta 时使用单极近似,
+    // 此时节点的软化取其中粒子质量加权的 eps^2.
+    // 周期性边界下对节点质心使用最小镜像位移, 对接近盒长的大节点只是粗略近似
+    pub fn compute_acceleration(
+        &self,
+        body: &Body,
+        theta: Real,
+        g: Real,
+        softening: Real,
+        box_size: Option<Real>,
+        kernel: SofteningKernel,
+    ) -> Vec3 {
+        let softening_sq_i = body.softening_sq(softening);
+        let theta_sq = theta * theta;
+        let mut acceleration = Vec3::ZERO;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.mass == 0.0 {
+                continue;
+            }
+            let direction = minimum_image(node.com - body.position, box_size);
+            let distance_sq = direction.length_squared();
+
+            if node.is_leaf() {
+                // 与直接求和一致, 跳过与自身位置重合的粒子
+                if direction != Vec3::ZERO {
+                    acceleration += kernel.acceleration(direction, g * node.mass, pair_softening_sq(softening_sq_i, node.softening_sq));
+                }
+            } else {
+                let size = 2.0 * node.half_size;
+                if size * size < theta_sq * distance_sq {
+                    acceleration += kernel.acceleration(direction, g * node.mass, pair_softening_sq(softening_sq_i, node.softening_sq));
+                } else {
+                    stack.extend(node.children.iter().copied().filter(|&c| c != EMPTY));
+                }
+            }
+        }
+
+        acceleration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::force::{test_params, update_accelerations};
+    use crate::generators::generate_plummer;
+    use crate::precision::to_f64;
+    use crate::{ForceMethod, ForceParams};
+
+    #[test]
+    fn tree_matches_direct_summation_at_theta_half() {
+        let mut direct = generate_plummer(300, 1.0, 1.0, 7, 1.0);
+        let mut tree = direct.clone();
+        let params = test_params(1.0, 0.01);
+        update_accelerations(&mut direct, &params);
+        update_accelerations(&mut tree, &ForceParams { method: ForceMethod::BarnesHut { theta: 0.5 }, ..params });
+
+        let errors: Vec<f64> = direct
+            .iter()
+            .zip(&tree)
+            .map(|(d, t)| to_f64(t.acceleration.distance(d.acceleration) / d.acceleration.length()))
+            .collect();
+        let rms = (errors.iter().map(|e| e * e).sum::<f64>() / errors.len() as f64).sqrt();
+        let max = errors.iter().copied().fold(0.0, f64::max);
+        assert!(rms < 0.01, "rms relative error {:e}", rms);
+        assert!(max < 0.05, "max relative error {:e}", max);
+    }
+}
@@ -0,0 +1,153 @@
+// 块时间步 (block timesteps): 每个粒子有自己的步长, 量化为基本步长 dt 的 1 / 2^k.
+// 每个子步只对到期的活跃粒子用 Hermite 格式求力与校正, 其余粒子只用 Taylor 展开预测到当前时刻作为场源.
+// 取舍:
+// - 活跃粒子的受力仍对全部粒子直接求和, 一次子步的代价为 O(N_active * N);
+// - 非活跃场源只有三阶预测精度, 长步长粒子对短步长粒子的作用误差随预测时间增长;
+// - 步长随状态改变, 格式既不辛也不时间可逆, 能量误差会缓慢累积;
+// - 所有粒子只在每个基本步结束时同步, 输出, 检查点与删除逃逸粒子都只能在同步时刻进行.
+use crate::force::acc_and_jerk;
+use crate::profile;
+use crate::{Body, ForceParams, Real, Vec3};
+use rayon::prelude::*;
+
+// 用 Taylor 展开把粒子的位置与速度预测到 tau 秒之后
+fn predict(body: &Body, tau: Real) -> (Vec3, Vec3) {
+    let tau2 = tau * tau;
+    (
+        body.position + body.velocity * tau + body.acceleration * (tau2 / 2.0) + body.jerk * (tau2 * tau / 6.0),
+        body.velocity + body.acceleration * tau + body.jerk * (tau2 / 2.0),
+    )
+}
+
+// 满足 dt / 2^k <= limit 的最小层级 k, 不超过 max_level
+fn level_for(dt: Real, limit: Real, max_level: u32) -> u32 {
+    let mut level = 0;
+    while level < max_level && dt / (1u64 << level) as Real > limit {
+        level += 1;
+    }
+    level
+}
+
+// 起始层级: 沿用上一个基本步选择的步长, 否则用 eta * |a| / |j| 估计
+fn initial_level(body: &Body, dt: Real, eta: Real, max_level: u32) -> u32 {
+    if body.timestep > 0.0 {
+        return ((dt / body.timestep).log2().round().max(0.0) as u32).min(max_level);
+    }
+    let jerk = body.jerk.length();
+    let limit = if jerk > 0.0 { eta * body.acceleration.length() / jerk } else { Real::INFINITY };
+    level_for(dt, limit, max_level)
+}
+
+// 用块时间步推进一个基本步 dt, 返回期间计算受力的粒子次数 (全局步长下每一步为 N 次).
+// 要求进入时 bodies 的加速度与 jerk 是当前状态对应的值, 返回时所有粒子同步在 t + dt.
+// 步长由 Aarseth 判据 sqrt(eta (|a| |a''| + |a'|^2) / (|a'| |a'''| + |a''|^2)) 决定,
+// 最小为 dt / 2^max_level; 粒子的步长每次最多加倍, 且只在与更大步长对齐的时刻加倍.
+// 受力总是对全部粒子直接求和, 忽略 params 中的方法, 周期性边界与外部势.
+pub fn block_step(bodies: &mut [Body], dt: Real, eta: Real, max_level: u32, params: &ForceParams) -> usize {
+    // 时间以 dt / 2^max_level 为单位用整数表示, 子步的到期判断没有舍入误差
+    let total: u64 = 1 << max_level;
+    let tick = dt / total as Real;
+    let mut levels: Vec<u32> = bodies.iter().map(|b| initial_level(b, dt, eta, max_level)).collect();
+    let mut times = vec![0u64; bodies.len()];
+    let mut evaluations = 0;
+
+    let mut now = 0;
+    while now < total {
+        let next = times.iter().zip(&levels).map(|(t, &k)| t + (total >> k)).min().unwrap_or(total);
+        let active: Vec<usize> = (0..bodies.len()).filter(|&i| times[i] + (total >> levels[i]) == next).collect();
+
+        // 所有粒子预测到 next 时刻, 作为活跃粒子的场源
+        let sources: Vec<_> = bodies
+            .iter()
+            .zip(&times)
+            .map(|(b, &t)| {
+                let (x, v) = predict(b, (next - t) as Real * tick);
+                (x, v, params.g * b.mass, b.softening_sq(params.softening))
+            })
+            .collect();
+        // 固定粒子的加速度与 jerk 保持为零, 校正后位置与速度不变
+        let forces: Vec<_> = profile::time_forces(|| {
+            active
+                .par_iter()
+                .map(|&i| {
+                    if bodies[i].fixed {
+                        return (Vec3::ZERO, Vec3::ZERO);
+                    }
+                    let (x, v, _, softening_sq) = sources[i];
+                    acc_and_jerk(x, v, softening_sq, &sources)
+                })
+                .collect()
+        });
+
+        for (&i, (a1, j1)) in active.iter().zip(forces) {
+            let h = (total >> levels[i]) as Real * tick;
+            let body = &mut bodies[i];
+            let (x0, v0, a0, j0) = (body.position, body.velocity, body.acceleration, body.jerk);
+
+            // Hermite 校正, 与 integrators::hermite_step 相同
+            body.velocity = v0 + (a0 + a1) * (h / 2.0) + (j0 - j1) * (h * h / 12.0);
+            body.position = x0 + (v0 + body.velocity) * (h / 2.0) + (a0 - a1) * (h * h / 12.0);
+            body.acceleration = a1;
+            body.jerk = j1;
+
+            // 由 Hermite 插值得到步末的二阶与三阶导数
+            let crackle = (12.0 * (a0 - a1) + 6.0 * h * (j0 + j1)) / (h * h * h);
+            let snap = (-6.0 * (a0 - a1) - h * (4.0 * j0 + 2.0 * j1)) / (h * h) + crackle * h;
+            let numerator = a1.length() * snap.length() + j1.length_squared();
+            let denominator = j1.length() * crackle.length() + snap.length_squared();
+            let limit = if denominator > 0.0 { (eta * numerator / denominator).sqrt() } else { Real::INFINITY };
+
+            let wanted = level_for(dt, limit, max_level);
+            let current = levels[i];
+            levels[i] = if wanted > current {
+                wanted
+            } else if wanted < current && next % (total >> (current - 1)) == 0 {
+                current - 1
+            } else {
+                current
+            };
+            times[i] = next;
+        }
+
+        evaluations += active.len();
+        now = next;
+    }
+
+    for (body, level) in bodies.iter_mut().zip(levels) {
+        body.timestep = dt / (1u64 << level) as Real;
+    }
+    evaluations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::test_params;
+    use crate::generators::{generate_cold_sphere, generate_kepler};
+    use crate::integrators::{update_forces, Integrator};
+
+    #[test]
+    fn tight_binary_in_slow_cloud_needs_far_fewer_force_evaluations() {
+        // 半径 10 的稀疏冷云, 外加远处一对周期约 2 pi * 1e-3 的紧密双星
+        let mut bodies = generate_cold_sphere(50, 1.0, 10.0, 3);
+        for mut body in generate_kepler(0.5, 0.5, 0.01, 0.0, 1.0) {
+            body.position += Vec3::new(30.0, 0.0, 0.0);
+            bodies.push(body);
+        }
+        Body::assign_ids(&mut bodies);
+        let params = test_params(1.0, 1e-4);
+        update_forces(&mut bodies, Integrator::Hermite, &params);
+
+        let dt = 0.1;
+        let mut evaluations = 0;
+        for _ in 0..3 {
+            evaluations += block_step(&mut bodies, dt, 0.01, 20, &params);
+        }
+
+        // 全局步长必须让所有粒子都用最短的步长
+        let shortest = bodies.iter().map(|b| b.timestep).fold(Real::INFINITY, Real::min);
+        let global = 3 * bodies.len() * (dt / shortest).round() as usize;
+        assert!(bodies[50..].iter().all(|b| b.timestep == shortest), "binary should hold the shortest step");
+        assert!(evaluations * 10 < global, "block {} vs global {}", evaluations, global);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{Real, Vec3};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Body {
+    // 稳定的粒子编号, 删除粒子后其余粒子的编号不变; 读入初始条件时按文件中的顺序编号
+    #[serde(default)]
+    pub id: usize,
+    pub mass: Real,
+    #[serde(with = "vec3_serde")]
+    pub position: Vec3,
+    #[serde(with = "vec3_serde")]
+    pub velocity: Vec3,
+    #[serde(skip)]
+    pub acceleration: Vec3,
+    // 加速度的时间导数, 只有 Hermite 积分器使用
+    #[serde(skip)]
+    pub jerk: Vec3,
+    // 块时间步调度器上一次为该粒子选择的步长; 0 表示尚未选择
+    #[serde(skip)]
+    pub timestep: Real,
+    // 粒子自身的软化长度; 未给出时使用全局 softening_factor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub softening: Option<Real>,
+    // 粒子的物理半径, 用于碰撞检测; 未给出时视为质点, 不参与碰撞
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radius: Option<Real>,
+    // 粒子种类 (如暗物质与恒星), 只用于绘图着色与分种类诊断, 所有种类之间的引力完全相同; 未给出时为 0
+    #[serde(default, skip_serializing_if = "is_default_species")]
+    pub species: u8,
+    // 固定粒子: 作为场源参与受力计算, 但自身不动. Simulation::new 把它的速度置零, 受力计算把它的加速度与 jerk
+    // 置零, 因此各积分器的 kick 与 drift 都不会改变它的位置与速度
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fixed: bool,
+}
+
+fn is_default_species(species: &u8) -> bool {
+    *species == 0
+}
+
+// 结构体数组 (structure-of-arrays) 形式的粒子状态: 每个分量连续存放, 受力计算的内层循环只读取需要的数组.
+// 程序的其余部分 (积分, 输入输出, 绘图) 仍使用 Vec<Body>, 受力计算前用 fill_from 转换, 之后写回加速度
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bodies {
+    pub mass: Vec<Real>,
+    pub position: Vec<Vec3>,
+    pub velocity: Vec<Vec3>,
+    pub acceleration: Vec<Vec3>,
+    // 每个粒子的软化长度平方, 已代入全局默认值
+    pub softening_sq: Vec<Real>,
+}
+
+impl Bodies {
+    pub fn from_bodies(bodies: &[Body], softening: Real) -> Self {
+        let mut soa = Self::default();
+        soa.fill_from(bodies, softening);
+        soa
+    }
+
+    // 用 bodies 的当前状态覆盖全部数组, 保留已分配的容量
+    pub fn fill_from(&mut self, bodies: &[Body], softening: Real) {
+        self.mass.clear();
+        self.mass.extend(bodies.iter().map(|b| b.mass));
+        self.position.clear();
+        self.position.extend(bodies.iter().map(|b| b.position));
+        self.velocity.clear();
+        self.velocity.extend(bodies.iter().map(|b| b.velocity));
+        self.acceleration.clear();
+        self.acceleration.extend(bodies.iter().map(|b| b.acceleration));
+        self.softening_sq.clear();
+        self.softening_sq.extend(bodies.iter().map(|b| b.softening_sq(softening)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.mass.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mass.is_empty()
+    }
+
+    // 把加速度写回对应下标的 Body
+    pub fn store_accelerations(&self, bodies: &mut [Body]) {
+        for (body, acceleration) in bodies.iter_mut().zip(&self.acceleration) {
+            body.acceleration = *acceleration;
+        }
+    }
+}
+
+// 自定义 Vec3 的序列化/反序列化
+mod vec3_serde {
+    use super::{Real, Vec3};
+    use serde::{self, Deserialize, Deserializer, Serializer, Serialize};
+
+    pub fn serialize<S>(vec: &Vec3, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        vec.to_array().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec3, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let arr = <[Real; 3]>::deserialize(deserializer)?;
+        Ok(Vec3::from_array(arr))
+    }
+}
+
+impl Body {
+    pub fn new(mass: Real, position: Vec3, velocity: Vec3) -> Self {
+        Self { id: 0, mass, position, velocity, acceleration: Vec3::ZERO, jerk: Vec3::ZERO, timestep: 0.0, softening: None, radius: None, species: 0, fixed: false }
+    }
+
+    // 软化长度的平方, 没有自己的软化长度时使用全局值 default
+    pub fn softening_sq(&self, default: Real) -> Real {
+        let eps = self.softening.unwrap_or(default);
+        eps * eps
+    }
+
+    // 按下标给粒子编号
+    pub fn assign_ids(bodies: &mut [Body]) {
+        for (id, body) in bodies.iter_mut().enumerate() {
+            body.id = id;
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// 盒子边界条件
+use crate::{Body, Real, Vec3};
+use rayon::prelude::*;
+
+// 粒子所在空间的边界
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoundaryCondition {
+    // 无界空间
+    Open,
+    // 边长为 size 的周期性盒子 [0, L): 位置折回盒内, 受力取最小镜像
+    Periodic { size: Real },
+    // 边长为 size 的反射墙盒子 [0, L]: 穿过墙面的粒子被镜面反射回盒内, 受力与无界空间相同
+    Reflecting { size: Real },
+}
+
+impl BoundaryCondition {
+    // 受力与势能计算中的周期长度; 只有周期性盒子才取最小镜像
+    pub fn periodic_size(&self) -> Option<Real> {
+        match *self {
+            BoundaryCondition::Periodic { size } => Some(size),
+            _ => None,
+        }
+    }
+
+    // 位置更新之后把粒子放回盒内
+    pub fn apply(&self, bodies: &mut [Body]) {
+        match *self {
+            BoundaryCondition::Open => {}
+            BoundaryCondition::Periodic { size } => wrap_positions(bodies, size),
+            BoundaryCondition::Reflecting { size } => reflect_positions(bodies, size),
+        }
+    }
+}
+
+// 把位置折回周期性盒子 [0, L) 内, 速度不变
+pub fn wrap_positions(bodies: &mut [Body], box_size: Real) {
+    bodies.par_iter_mut().for_each(|body| {
+        let wrapped = body.position.rem_euclid(Vec3::splat(box_size));
+        // 极小的负数取模后会舍入为 L 本身
+        body.position = Vec3::select(wrapped.cmpge(Vec3::splat(box_size)), Vec3::ZERO, wrapped);
+    });
+}
+
+// 反射墙: 越过某个墙面的分量按墙面镜像回盒内, 对应的速度分量反向指向盒内, 其余分量不变.
+// 一步越过整个盒子的粒子最后被限制在墙面上
+pub fn reflect_positions(bodies: &mut [Body], box_size: Real) {
+    bodies.par_iter_mut().for_each(|body| {
+        for axis in 0..3 {
+            let (x, v) = (body.position[axis], body.velocity[axis]);
+            if x < 0.0 {
+                body.position[axis] = (-x).min(box_size);
+                body.velocity[axis] = v.abs();
+            } else if x > box_size {
+                body.position[axis] = (2.0 * box_size - x).max(0.0);
+                body.velocity[axis] = -v.abs();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrators::drift;
+
+    #[test]
+    fn periodic_particle_reappears_on_the_opposite_side() {
+        let boundary = BoundaryCondition::Periodic { size: 10.0 };
+        let velocity = Vec3::new(1.0, -0.5, 0.0);
+        let mut bodies = vec![Body::new(1.0, Vec3::new(9.5, 0.25, 5.0), velocity)];
+        drift(&mut bodies, 1.0);
+        boundary.apply(&mut bodies);
+        assert!(bodies[0].position.distance(Vec3::new(0.5, 9.75, 5.0)) < 1e-12, "{:?}", bodies[0].position);
+        assert_eq!(bodies[0].velocity, velocity);
+    }
+
+    #[test]
+    fn reflecting_wall_reverses_only_the_normal_velocity() {
+        let boundary = BoundaryCondition::Reflecting { size: 10.0 };
+        let mut bodies = vec![Body::new(1.0, Vec3::new(0.5, 5.0, 5.0), Vec3::new(-1.0, 0.25, -0.5))];
+        drift(&mut bodies, 1.0);
+        boundary.apply(&mut bodies);
+        assert!(bodies[0].position.distance(Vec3::new(0.5, 5.25, 4.5)) < 1e-12, "{:?}", bodies[0].position);
+        assert_eq!(bodies[0].velocity, Vec3::new(1.0, 0.25, -0.5));
+    }
+}
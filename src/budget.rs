@@ -0,0 +1,252 @@
+// 运行前的估计: 输出的帧数, 文件数与大小 (超出上限时拒绝运行), 以及试运行报告的耗时与内存
+use crate::force::PAIRWISE_BLOCKS;
+use crate::integrators::Integrator;
+use crate::{Body, ForceMethod, Real, SimError, Simulation};
+use log::info;
+use std::path::Path;
+use std::time::Instant;
+
+// 每帧输出文件大小的粗略上限估计: PNG (1024x768, 多为黑色背景) 与 GIF 帧, 快照中每个粒子 (CSV 一行约 200 字节),
+// 柱密度网格的每个格点 (CSV 中的一个数)
+const IMAGE_BYTES: usize = 100 * 1024;
+const SNAPSHOT_BYTES_PER_BODY: usize = 200;
+const GRID_BYTES_PER_CELL: usize = 24;
+
+// 剩余 steps 步中预计的输出: 帧数, 每帧的文件数与字节数, 以及整个运行只写一个的文件数
+pub struct PlannedOutput {
+    pub intervals: usize,
+    pub per_interval: usize,
+    pub frame_bytes: usize,
+    pub single_files: usize,
+}
+
+impl PlannedOutput {
+    pub fn files(&self) -> usize {
+        self.intervals * self.per_interval
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.intervals * self.frame_bytes
+    }
+}
+
+// 按步数输出的规则: 第 i 步 (步前的步数) 是 plot_interval 的倍数时写一帧. Config::validate 保证 interval 不为 0
+pub fn is_frame_step(i: usize, interval: usize) -> bool {
+    i.is_multiple_of(interval)
+}
+
+// [start, end) 中满足 is_frame_step 的步数
+pub fn frame_steps(start: usize, end: usize, interval: usize) -> usize {
+    end.div_ceil(interval).saturating_sub(start.div_ceil(interval))
+}
+
+pub fn planned_outputs(sim: &Simulation, steps: usize) -> PlannedOutput {
+    let (config, start_step, start_time) = (&sim.config, sim.step_count, sim.time);
+    // 主循环在 i % plot_interval == 0 时输出, i 从 start_step 到 start_step + steps - 1
+    let last = start_step + steps;
+    // 按模拟时间输出时为剩余时间中的倍数个数, 加上新运行的初始帧与不在整数倍上的终点帧
+    let intervals = if let Some(interval) = config.output_time_interval {
+        let remaining = if config.adaptive_dt { (sim.t_end() - start_time).max(0.0) } else { steps as f64 * config.dt };
+        (remaining / interval).ceil() as usize + usize::from(start_step == 0)
+    } else {
+        frame_steps(start_step, last, config.plot_interval)
+    };
+    let pngs = match (config.png_frames, config.combined_projections) {
+        (false, _) => 0,
+        (true, true) => 1,
+        (true, false) if config.planar => 1,
+        (true, false) => 3,
+    };
+    let images = pngs + usize::from(config.density_profile) + usize::from(config.phase_space) + usize::from(config.plot_3d);
+    let grids = if config.column_density.is_some() { if config.planar { 1 } else { 3 } } else { 0 };
+    let snapshots = usize::from(config.snapshot_format.is_some());
+    let frame_bytes = (images + usize::from(config.animate.is_some())) * IMAGE_BYTES
+        + snapshots * sim.bodies.len() * SNAPSHOT_BYTES_PER_BODY
+        + grids * config.heatmap_resolution.pow(2) * GRID_BYTES_PER_CELL;
+    let single_files = [
+        config.diagnostics_csv.is_some(),
+        config.dispersion_csv.is_some(),
+        config.lagrangian_csv.is_some(),
+        config.gw_csv.is_some(),
+        config.trajectory.is_some(),
+        config.merger_log.is_some(),
+        config.animate.is_some(),
+        config.checkpoint_interval > 0,
+        config.energy_plot_interval > 0,
+    ]
+    .into_iter()
+    .filter(|&enabled| enabled)
+    .count();
+    PlannedOutput { intervals, per_interval: images + grids + snapshots, frame_bytes, single_files }
+}
+
+// 开始前检查输出的规模: 帧文件数超过 --max-output-files, 或估计的大小超过输出目录所在文件系统的可用空间时拒绝运行.
+// 自适应步长时按最大步长 dt 估计步数, 因此是下限
+pub fn check_output_budget(sim: &Simulation) -> Result<(), SimError> {
+    let config = &sim.config;
+    let steps = if config.adaptive_dt {
+        ((sim.t_end() - sim.time).max(0.0) / config.dt).ceil() as usize
+    } else {
+        config.time_steps.saturating_sub(sim.step_count)
+    };
+    let planned = planned_outputs(sim, steps);
+    let (files, limit) = (planned.files(), config.max_output_files);
+    if limit > 0 && files > limit {
+        let hint = match config.output_time_interval {
+            Some(interval) => format!("--output-time-interval of at least {:e}", interval * files as f64 / limit as f64),
+            None => {
+                // 按比例放大后还要计入起点上的那一帧, 逐个增大直到帧文件数不超过上限
+                let mut interval = (config.plot_interval * files).div_ceil(limit);
+                let end = sim.step_count + steps;
+                while interval < steps && frame_steps(sim.step_count, end, interval) * planned.per_interval > limit {
+                    interval += 1;
+                }
+                format!("--plot-interval of at least {}", interval)
+            }
+        };
+        return Err(SimError::Config(format!(
+            "The run would write {} frame files ({} intervals x {}), more than --max-output-files {}; use a {} or raise the limit",
+            files, planned.intervals, planned.per_interval, limit, hint
+        )));
+    }
+    if let Some(available) = available_space(&config.output_dir) {
+        if planned.bytes() as u64 > available {
+            return Err(SimError::Config(format!(
+                "The run would write about {:.1} MiB of frames, but only {:.1} MiB are free for '{}'; use a larger --plot-interval",
+                planned.bytes() as f64 / (1024.0 * 1024.0),
+                available as f64 / (1024.0 * 1024.0),
+                config.output_dir.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+// 目录所在文件系统中非特权用户可用的字节数; 无法查询时返回 None
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+// 试运行的报告. sim 的受力已是当前值; 计时在副本上推进一步, 与主循环使用同样的 step.
+// 耗时只计积分与受力, 不含绘图与诊断; 自适应步长时总步数按当前步长外推
+pub fn report_dry_run(sim: &Simulation) -> Result<(), SimError> {
+    let (config, bodies, force_params) = (&sim.config, &sim.bodies, sim.force_params());
+    let (start_step, start_time) = (sim.step_count, sim.time);
+    let n = bodies.len();
+    let units = config.units;
+
+    let mut sample = sim.clone();
+    let started = Instant::now();
+    let dt = sample.step()?.dt;
+    let per_step = started.elapsed().as_secs_f64();
+    let steps = if config.adaptive_dt {
+        ((sim.t_end() - start_time).max(0.0) / dt).ceil() as usize
+    } else {
+        config.time_steps.saturating_sub(start_step)
+    };
+
+    // 每次受力计算的粒子对数; 树方法约为 N log2 N 次相互作用, PM 为 M = grid^3 个格点上的两次 3D FFT
+    let pairs = n * n.saturating_sub(1) / 2;
+    let cost = match force_params.method {
+        ForceMethod::BarnesHut { .. } if config.integrator != Integrator::Hermite => {
+            format!("~{:.3e} interactions (Barnes-Hut, N log2 N)", n as f64 * (n.max(2) as f64).log2())
+        }
+        ForceMethod::ParticleMesh { grid } if config.integrator != Integrator::Hermite => {
+            let cells = grid.pow(3);
+            format!("{}^3 = {} cells (particle mesh, N + M log2 M)", grid, cells)
+        }
+        _ => format!("{} pairs (O(N^2))", pairs),
+    };
+    let evaluations = match config.integrator {
+        Integrator::Yoshida4 => 3,
+        Integrator::Rk4 => 4,
+        // 每个接受的子步六次, 子步数取决于容差
+        Integrator::Dopri5 => 6,
+        _ => 1,
+    };
+
+    // 粒子数组与受力计算的 SoA 缓冲区; 对称求和时每块另有一个长度为 N 的加速度数组, RK4 还有一份中间状态
+    let real = std::mem::size_of::<Real>();
+    let mut memory = n * (std::mem::size_of::<Body>() + 11 * real);
+    if force_params.method == ForceMethod::Pairwise {
+        memory += PAIRWISE_BLOCKS.min(n) * n * 3 * real;
+    }
+    if config.integrator == Integrator::Rk4 {
+        memory += n * (std::mem::size_of::<Body>() + 8 * 3 * real);
+    }
+    if config.integrator == Integrator::Dopri5 {
+        memory += n * (std::mem::size_of::<Body>() + 16 * 3 * real);
+    }
+    // PM: 复数的密度网格与逐轴 FFT 的临时副本, 实数的势与三分量力场
+    if let ForceMethod::ParticleMesh { grid } = force_params.method {
+        memory += grid.pow(3) * (2 * 16 + 4 * 8);
+    }
+
+    let planned = planned_outputs(sim, steps);
+
+    info!("Dry run: {} bodies, {} {} steps", n, steps, if config.adaptive_dt { "estimated" } else { "remaining" });
+    info!("Force cost: {} per evaluation, {} evaluation(s) per step", cost, evaluations);
+    info!("Sample step: {:.3e} s of wall time (dt = {:e} {})", per_step, dt, units.time());
+    let wall_time = per_step * steps as f64;
+    info!("Projected wall time: {:.3e} s ({:.2} h), excluding plotting and diagnostics", wall_time, wall_time / 3600.0);
+    info!("Estimated memory: {:.1} MiB", memory as f64 / (1024.0 * 1024.0));
+    info!(
+        "Output: {} plot intervals x {} files = {} files in '{}' (about {:.1} MiB), plus {} run-wide files",
+        planned.intervals,
+        planned.per_interval,
+        planned.files(),
+        config.output_dir.display(),
+        planned.bytes() as f64 / (1024.0 * 1024.0),
+        planned.single_files
+    );
+    if let Some(path) = &config.trajectory {
+        let rows = n * (steps / config.trajectory_interval);
+        info!("Trajectory '{}': about {} rows", path.display(), rows);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::generators::generate_kepler;
+
+    #[test]
+    fn planned_frames_follow_the_main_loop_rule() {
+        for interval in [1, 3, 10] {
+            for (start, end) in [(0, 0), (0, 1), (0, 100), (7, 7), (7, 31), (30, 31), (31, 60)] {
+                let counted = (start..end).filter(|&i| is_frame_step(i, interval)).count();
+                assert_eq!(frame_steps(start, end, interval), counted, "interval {} steps {}..{}", interval, start, end);
+            }
+        }
+    }
+
+    #[test]
+    fn too_many_frames_refuses_to_start() {
+        let bodies = generate_kepler(1.0, 1e-3, 1.0, 0.0, 1.0);
+        let config = Config { time_steps: 1000, plot_interval: 1, max_output_files: 500, ..Config::default() };
+        let sim = Simulation::new(bodies, config.clone()).unwrap();
+        // 1000 帧, 每帧 3 个投影 PNG
+        let message = check_output_budget(&sim).unwrap_err().to_string();
+        assert!(message.contains("3000 frame files") && message.contains("--plot-interval of at least 7"), "{}", message);
+
+        // 建议的间隔刚好够用: 间隔 6 时有 167 帧, 501 个文件
+        let sim = Simulation::new(sim.bodies, Config { plot_interval: 6, ..config }).unwrap();
+        assert!(check_output_budget(&sim).is_err());
+        let sim = Simulation::new(sim.bodies, Config { plot_interval: 7, ..sim.config }).unwrap();
+        assert!(check_output_budget(&sim).is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+// 检查点: 保存完整状态 (含速度), 步数, 模拟时间与随机种子, 用于中断后续算
+use crate::input::{is_gzip, open_input};
+use crate::output::OutputFile;
+use crate::units::Units;
+use crate::{Body, SimError};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    step: usize,
+    time: f64,
+    units: Units,
+    seed: u64,
+    bodies: &'a [Body],
+}
+
+// 读回的检查点. acceleration 不保存, 续算前需要重新计算
+#[derive(Deserialize)]
+pub struct Checkpoint {
+    pub step: usize,
+    pub time: f64,
+    // 旧的检查点没有记录单位制, 一律是 SI
+    #[serde(default)]
+    pub units: Units,
+    // 旧的检查点没有记录随机种子
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub bodies: Vec<Body>,
+}
+
+// 先写临时文件再重命名, 避免写到一半被中断时损坏已有的检查点; 路径以 .gz 结尾时压缩写出
+pub fn save_checkpoint(bodies: &[Body], step: usize, time: f64, units: Units, seed: u64, path: &Path) -> Result<(), SimError> {
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = OutputFile::new(File::create(&tmp_path)?, is_gzip(path));
+    serde_json::to_writer(&mut writer, &CheckpointRef { step, time, units, seed, bodies }).map_err(std::io::Error::from)?;
+    writer.finish()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+pub fn load_checkpoint(path: &Path) -> Result<Checkpoint, SimError> {
+    Ok(serde_json::from_reader(open_input(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Softening};
+    use crate::generators::generate_plummer;
+    use crate::Simulation;
+
+    #[test]
+    fn restart_from_checkpoint_reproduces_a_straight_run() {
+        let softening_factor = Softening::Length(0.01);
+        let config = Config { time_steps: 40, dt: 1e-3, units: Units::Nbody, softening_factor, ..Config::default() };
+        let bodies = generate_plummer(64, 1.0, 1.0, 3, 1.0);
+
+        let mut straight = Simulation::new(bodies.clone(), config.clone()).unwrap();
+        straight.run(40).unwrap();
+
+        let mut first = Simulation::new(bodies, config.clone()).unwrap();
+        first.run(15).unwrap();
+        let path = std::env::temp_dir().join(format!("nbody_restart_test_{}.json", std::process::id()));
+        save_checkpoint(&first.bodies, first.step_count, first.time, config.units, 7, &path).unwrap();
+        let Checkpoint { bodies: saved, step, time, units, seed } = load_checkpoint(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((step, time, units, seed), (15, first.time, Units::Nbody, Some(7)));
+
+        let mut resumed = Simulation::new(saved, config).unwrap();
+        (resumed.step_count, resumed.time) = (step, time);
+        resumed.run(40).unwrap();
+        assert_eq!(resumed.step_count, 40);
+        assert_eq!(resumed.time.to_bits(), straight.time.to_bits());
+        assert_eq!(resumed.bodies, straight.bodies);
+    }
+}
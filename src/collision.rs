@@ -0,0 +1,135 @@
+// 有限大小粒子的碰撞处理
+use crate::precision::to_f64;
+use crate::{Body, Real};
+use serde::{Deserialize, Serialize};
+
+// 重叠粒子的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionMode {
+    /// Merge the pair into one body at their center of mass, conserving mass and momentum
+    Merge,
+    /// Reflect the relative velocity along the line of centers, conserving kinetic energy
+    Elastic,
+}
+
+// 一次合并: 幸存者保留自己的编号, 被吸收者的编号此后不再出现; combined_mass 为合并后的质量.
+// 同一步中幸存者可以继续吸收其他粒子, 按发生的顺序各记一次
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Merger {
+    pub survivor_id: usize,
+    pub absorbed_id: usize,
+    pub combined_mass: f64,
+}
+
+// 检测半径重叠 (|x_i - x_j| < r_i + r_j) 的粒子对并按 mode 处理, 返回处理的碰撞次数, 合并事件追加到 mergers.
+// 没有半径的粒子视为质点, 从不碰撞. 两两比较是 O(N^2) 的, 且不考虑周期性盒子的镜像.
+// 合并会减少粒子数; 碰撞后加速度 (与 jerk) 不再对应新的状态, 调用者需要重新计算受力
+pub fn handle_collisions(bodies: &mut Vec<Body>, mode: CollisionMode, mergers: &mut Vec<Merger>) -> usize {
+    let mut collisions = 0;
+    let mut absorbed = vec![false; bodies.len()];
+    for i in 0..bodies.len() {
+        for j in i + 1..bodies.len() {
+            if absorbed[i] || absorbed[j] {
+                continue;
+            }
+            let (head, tail) = bodies.split_at_mut(j);
+            let (first, second) = (&mut head[i], &mut tail[0]);
+            let (Some(r1), Some(r2)) = (first.radius, second.radius) else {
+                continue;
+            };
+            if first.position.distance_squared(second.position) >= (r1 + r2) * (r1 + r2) {
+                continue;
+            }
+            match mode {
+                CollisionMode::Merge => {
+                    mergers.push(merge(first, second));
+                    absorbed[j] = true;
+                    collisions += 1;
+                }
+                CollisionMode::Elastic => {
+                    if bounce(first, second) {
+                        collisions += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut flags = absorbed.into_iter();
+    bodies.retain(|_| !flags.next().unwrap_or(false));
+    collisions
+}
+
+// 把 second 合并进 first: 质量相加, 位置与速度取质量加权平均, 体积相加.
+// 合并后的粒子保留较重者的编号, 种类与软化长度, 使其轨迹在输出中延续
+fn merge(first: &mut Body, second: &mut Body) -> Merger {
+    if second.mass > first.mass {
+        std::mem::swap(first, second);
+    }
+    let mass = first.mass + second.mass;
+    first.position = (first.position * first.mass + second.position * second.mass) / mass;
+    first.velocity = (first.velocity * first.mass + second.velocity * second.mass) / mass;
+    let (r1, r2) = (first.radius.unwrap_or(0.0), second.radius.unwrap_or(0.0));
+    first.radius = Some((r1.powi(3) + r2.powi(3)).cbrt());
+    first.mass = mass;
+    // 块时间步调度器需要为新粒子重新选择步长
+    first.timestep = 0.0;
+    Merger { survivor_id: first.id, absorbed_id: second.id, combined_mass: to_f64(mass) }
+}
+
+// 弹性碰撞: 相对速度沿连心线的分量反向, 切向分量不变; 动量与动能都守恒.
+// 只处理正在接近的粒子对, 已经在分开的重叠粒子不再反弹, 以免来回振荡. 返回是否发生了反弹
+fn bounce(first: &mut Body, second: &mut Body) -> bool {
+    let normal = (second.position - first.position).normalize_or_zero();
+    let approach = (first.velocity - second.velocity).dot(normal);
+    if approach <= 0.0 {
+        return false;
+    }
+    let impulse: Real = 2.0 * approach / (first.mass + second.mass);
+    first.velocity -= normal * (impulse * second.mass);
+    second.velocity += normal * (impulse * first.mass);
+    first.timestep = 0.0;
+    second.timestep = 0.0;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vec3;
+
+    #[test]
+    fn head_on_merge_conserves_momentum_and_sums_masses() {
+        let mut bodies = vec![
+            Body::new(3.0, Vec3::new(-0.05, 0.0, 0.0), Vec3::new(2.0, 0.5, 0.0)),
+            Body::new(1.0, Vec3::new(0.05, 0.0, 0.0), Vec3::new(-4.0, 0.0, 1.0)),
+        ];
+        for body in &mut bodies {
+            body.radius = Some(0.1);
+        }
+        let momentum: Vec3 = bodies.iter().map(|b| b.velocity * b.mass).sum();
+
+        let collisions = handle_collisions(&mut bodies, CollisionMode::Merge, &mut Vec::new());
+        assert_eq!(collisions, 1);
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0].mass, 4.0);
+        assert!((bodies[0].velocity * bodies[0].mass).distance(momentum) < 1e-12, "{:?} vs {:?}", bodies[0].velocity, momentum);
+    }
+
+    #[test]
+    fn two_body_merge_records_one_merger_event() {
+        let mut bodies = vec![
+            Body { radius: Some(0.5), ..Body::new(1.0, Vec3::ZERO, Vec3::ZERO) },
+            Body { radius: Some(0.5), ..Body::new(2.5, Vec3::new(0.6, 0.0, 0.0), Vec3::ZERO) },
+        ];
+        Body::assign_ids(&mut bodies);
+        let mut mergers = Vec::new();
+        handle_collisions(&mut bodies, CollisionMode::Merge, &mut mergers);
+
+        // 较重的粒子 (编号 1) 存活并吸收另一个
+        assert_eq!(mergers, [Merger { survivor_id: 1, absorbed_id: 0, combined_mass: 3.5 }]);
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0].id, 1);
+    }
+}
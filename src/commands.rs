@@ -0,0 +1,188 @@
+// 不运行模拟的子命令: 生成初始条件, 检查初始条件文件, N-body 单位与物理单位之间的换算
+use crate::config::{Command, Config};
+use crate::precision::{to_dvec3, to_f64};
+use crate::units::{rescale_to_nbody_units, ScaleFactors, Units};
+use crate::{diagnostics, generators, input, kdtree, output, Real, SimError, Vec3};
+use log::{info, warn};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// 执行一个子命令; 随机生成器使用全局的 --seed, 输出使用 config 的单位制
+pub fn run_command(command: &Command, config: &Config) -> Result<(), SimError> {
+    let units = config.units;
+    let g = units.gravitational_constant();
+    match command {
+        Command::Plummer { output, n, total_mass, scale_radius } => {
+            let bodies = generators::generate_plummer(*n, *total_mass as Real, *scale_radius as Real, config.seed, g);
+            output::write_bodies(&bodies, output, 0.0)?;
+            info!("Wrote {} Plummer bodies to '{}'.", bodies.len(), output.display());
+        }
+        Command::Disk { output, n, total_mass, scale_length, scale_height } => {
+            let bodies = generators::generate_disk(
+                *n,
+                *total_mass as Real,
+                *scale_length as Real,
+                *scale_height as Real,
+                config.seed,
+                g,
+            );
+            output::write_bodies(&bodies, output, 0.0)?;
+            info!("Wrote {} disk bodies to '{}'.", bodies.len(), output.display());
+        }
+        Command::ColdSphere { output, n, total_mass, radius } => {
+            let bodies = generators::generate_cold_sphere(*n, *total_mass as Real, *radius as Real, config.seed);
+            output::write_bodies(&bodies, output, 0.0)?;
+            // 自由落体时间 t_ff = (pi / 2) sqrt(R^3 / (2 G M)), 用来选择 dt 与总步数
+            let free_fall = std::f64::consts::FRAC_PI_2 * (radius.powi(3) / (2.0 * to_f64(g) * total_mass)).sqrt();
+            info!("Wrote {} cold-sphere bodies to '{}'.", bodies.len(), output.display());
+            info!("Free-fall time: {:.6e} {}", free_fall, units.time());
+        }
+        Command::Kepler { output, m1, m2, semi_major, eccentricity } => {
+            let bodies = generators::generate_kepler(*m1 as Real, *m2 as Real, *semi_major as Real, *eccentricity as Real, g);
+            output::write_bodies(&bodies, output, 0.0)?;
+            info!("Wrote Kepler two-body system to '{}'.", output.display());
+        }
+        Command::Hierarchical { output, m1, m2, m_planet, inner_a, outer_a, inner_e, outer_e } => {
+            if !(0.0..1.0).contains(inner_e) || !(0.0..1.0).contains(outer_e) {
+                return Err(SimError::Config("hierarchical eccentricities must satisfy 0 <= e < 1".to_string()));
+            }
+            if outer_a * (1.0 - outer_e) <= inner_a * (1.0 + inner_e) {
+                return Err(SimError::Config("the planet's pericenter must lie outside the binary's apocenter".to_string()));
+            }
+            let bodies = generators::generate_hierarchical(
+                *m1 as Real,
+                *m2 as Real,
+                *m_planet as Real,
+                *inner_a as Real,
+                *outer_a as Real,
+                *inner_e as Real,
+                *outer_e as Real,
+                g,
+            );
+            output::write_bodies(&bodies, output, 0.0)?;
+            // Kepler 第三定律 P = 2 pi sqrt(a^3 / (G M)), 用来选择 dt 与总步数
+            let period = |a: f64, mass: f64| std::f64::consts::TAU * (a.powi(3) / (to_f64(g) * mass)).sqrt();
+            let critical = generators::circumbinary_critical_semi_major(*m1, *m2, *inner_a, *inner_e);
+            info!("Wrote hierarchical triple to '{}'.", output.display());
+            info!(
+                "Periods: inner binary {:.6e} {}, planet {:.6e} {}",
+                period(*inner_a, m1 + m2),
+                units.time(),
+                period(*outer_a, m1 + m2 + m_planet),
+                units.time()
+            );
+            if *outer_a < critical {
+                warn!(
+                    "Planet semi-major axis {:.6e} {} is inside the Holman-Wiegert stability limit {:.6e} {}; expect ejection.",
+                    outer_a,
+                    units.length(),
+                    critical,
+                    units.length()
+                );
+            }
+        }
+        Command::Inspect { input } => inspect(input, units)?,
+        Command::NbodyUnits { input, output, virialize, scale_file } => {
+            let mut bodies = input::load_bodies(input)?;
+            let scale = rescale_to_nbody_units(&mut bodies, g, *virialize)?;
+            output::write_bodies(&bodies, output, 0.0)?;
+            let scale_file = scale_file.clone().unwrap_or_else(|| output.with_extension("scale.json"));
+            serde_json::to_writer_pretty(BufWriter::new(File::create(&scale_file)?), &scale).map_err(std::io::Error::from)?;
+            info!("Wrote {} bodies in N-body units to '{}'.", bodies.len(), output.display());
+            info!(
+                "N-body units: length {:.6e} {}, mass {:.6e} {}, time {:.6e} {}, velocity {:.6e} {}, energy {:.6e} {}",
+                scale.length,
+                units.length(),
+                scale.mass,
+                units.mass(),
+                scale.time,
+                units.time(),
+                scale.velocity(),
+                units.speed(),
+                scale.energy(),
+                units.energy()
+            );
+            info!("Saved the scale factors to '{}'; convert results back with physical-units.", scale_file.display());
+        }
+        Command::PhysicalUnits { input, scale_file, output } => {
+            let scale: ScaleFactors = serde_json::from_reader(input::open_input(scale_file)?)?;
+            let mut bodies = input::load_bodies(input)?;
+            scale.to_physical(&mut bodies);
+            output::write_bodies(&bodies, output, 0.0)?;
+            info!("Wrote {} bodies in physical units to '{}'.", bodies.len(), output.display());
+        }
+    }
+    Ok(())
+}
+
+// inspect 估计局部密度时使用的近邻数
+const INSPECT_NEIGHBOURS: usize = 32;
+
+// 初始条件文件的概要, 在正式运行之前检查单位错误与不对称
+pub fn inspect(path: &Path, units: Units) -> Result<(), SimError> {
+    let bodies = input::load_bodies(path)?;
+    let (length, mass, speed) = (units.length(), units.mass(), units.speed());
+    info!("'{}': {} bodies ({} units)", path.display(), bodies.len(), units.label());
+    if bodies.is_empty() {
+        return Ok(());
+    }
+    if let Err(e) = input::validate_bodies(&bodies) {
+        warn!("{}", e);
+    }
+    let duplicates = input::duplicate_positions(&bodies).len();
+    if duplicates > 0 {
+        warn!("{} pairs of bodies share a position.", duplicates);
+    }
+
+    let masses: Vec<f64> = bodies.iter().map(|b| to_f64(b.mass)).collect();
+    let total_mass: f64 = masses.iter().sum();
+    let (min_mass, max_mass) = masses.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &m| (lo.min(m), hi.max(m)));
+    info!("Total mass:   {:.6e} {}", total_mass, mass);
+    info!("Mass range:   {:.6e} .. {:.6e} {} (mean {:.6e})", min_mass, max_mass, mass, total_mass / masses.len() as f64);
+
+    let (min, max) = bodies.iter().fold((Vec3::splat(Real::MAX), Vec3::splat(Real::MIN)), |(lo, hi), b| {
+        (lo.min(b.position), hi.max(b.position))
+    });
+    let (min, max) = (to_dvec3(min), to_dvec3(max));
+    info!("Bounding box: x [{:.6e}, {:.6e}], y [{:.6e}, {:.6e}], z [{:.6e}, {:.6e}] {}", min.x, max.x, min.y, max.y, min.z, max.z, length);
+
+    let (com_position, com_velocity) = diagnostics::center_of_mass(&bodies);
+    let (com_position, com_velocity) = (to_dvec3(com_position), to_dvec3(com_velocity));
+    let (momentum, angular_momentum) = diagnostics::momentum_diagnostics(&bodies);
+    info!("Center of mass:   ({:.6e}, {:.6e}, {:.6e}) {}", com_position.x, com_position.y, com_position.z, length);
+    info!("COM velocity:     ({:.6e}, {:.6e}, {:.6e}) {}", com_velocity.x, com_velocity.y, com_velocity.z, speed);
+    info!("Total momentum:   |P| = {:.6e} {}", momentum.length(), units.momentum());
+    info!("Angular momentum: |L| = {:.6e} {}", angular_momentum.length(), units.angular_momentum());
+
+    // 与系统尺度和速度尺度相比可以忽略时视为质心系
+    let size = (max - min).max_element();
+    let rms_speed = (bodies.iter().map(|b| to_f64(b.velocity.length_squared())).sum::<f64>() / bodies.len() as f64).sqrt();
+    let at_rest = com_position.length() <= 1e-6 * size && com_velocity.length() <= 1e-6 * rms_speed.max(f64::MIN_POSITIVE);
+    info!("COM frame: {}", if at_rest { "yes" } else { "no (see --com-frame)" });
+
+    // 局部密度 (第 32 近邻) 的分布, 与平均密度相比可看出子结构与核
+    let mut densities = kdtree::local_densities(&bodies, INSPECT_NEIGHBOURS);
+    if bodies.len() > 1 {
+        densities.sort_unstable_by(f64::total_cmp);
+        let density_unit = format!("{}/{}^3", mass, length);
+        info!(
+            "Local density ({} neighbours): min {:.6e}, median {:.6e}, max {:.6e} {}",
+            INSPECT_NEIGHBOURS.min(bodies.len() - 1),
+            densities[0],
+            densities[densities.len() / 2],
+            densities[densities.len() - 1],
+            density_unit
+        );
+    }
+
+    let species = diagnostics::species_present(&bodies);
+    if species.len() > 1 {
+        let counts: Vec<String> = species
+            .iter()
+            .map(|&s| format!("{}: {}", s, bodies.iter().filter(|b| b.species == s).count()))
+            .collect();
+        info!("Species: {}", counts.join(", "));
+    }
+    Ok(())
+}
@@ -0,0 +1,936 @@
+// 命令行参数与模拟配置
+use crate::cosmology::Cosmology;
+use crate::diagnostics::QuadrupoleStrain;
+use crate::integrators::DOPRI5_TOLERANCE;
+use crate::plot::{Bounds, ColorMode, PlotOptions, Renderer, SizeMode};
+use crate::units::Units;
+use crate::{
+    Body, BoundaryCondition, CollisionMode, DynamicalFriction, ExternalPotential, ForceMethod, Integrator, Real, SimError, SofteningKernel, Vec3,
+};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use glam::DVec3;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+// 全局软化长度: 直接给出, 或按初始粒子的平均间距自动选择 (命令行与配置文件中写作 "auto")
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SofteningValue", into = "SofteningValue")]
+pub enum Softening {
+    Length(f64),
+    Auto,
+}
+
+// 配置文件中 softening_factor 可以是数或字符串
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SofteningValue {
+    Number(f64),
+    Text(String),
+}
+
+impl std::str::FromStr for Softening {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Softening::Auto);
+        }
+        s.parse().map(Softening::Length).map_err(|_| format!("expected a length or \"auto\", got '{}'", s))
+    }
+}
+
+impl std::fmt::Display for Softening {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Softening::Length(length) => write!(f, "{}", length),
+            Softening::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+impl TryFrom<SofteningValue> for Softening {
+    type Error = String;
+
+    fn try_from(value: SofteningValue) -> Result<Self, Self::Error> {
+        match value {
+            SofteningValue::Number(length) => Ok(Softening::Length(length)),
+            SofteningValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<Softening> for SofteningValue {
+    fn from(softening: Softening) -> Self {
+        match softening {
+            Softening::Length(length) => SofteningValue::Number(length),
+            Softening::Auto => SofteningValue::Text("auto".to_string()),
+        }
+    }
+}
+
+// 引力算法的命令行选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForceAlgorithm {
+    /// Direct summation, O(N^2)
+    Direct,
+    /// Direct summation evaluating each pair once (Newton's third law)
+    Pairwise,
+    /// Barnes-Hut octree, O(N log N); accuracy set by --theta
+    Tree,
+    /// Particle-mesh FFT solver on a --pm-grid^3 mesh, periodic boxes only; forces are smoothed below a few cells
+    Pm,
+    /// Direct summation with Ewald corrections: the exact periodic force, O(N^2) and slow; for validating pm or tree runs
+    Ewald,
+}
+
+// 外部势的命令行选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExternalKind {
+    /// Point mass of --external-mass at --external-position
+    Point,
+    /// NFW halo centered on the origin, set by --nfw-rho0 and --nfw-scale-radius
+    Nfw,
+}
+
+// 盒子边界的命令行选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoundaryKind {
+    /// Unbounded space
+    Open,
+    /// Positions wrap around a box of --box-size; forces use the minimum image
+    Periodic,
+    /// Bodies bounce off the walls of a box of --box-size
+    Reflecting,
+}
+
+// 粒子快照的文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotFormat {
+    /// One row per body: id, mass, x, y, z, vx, vy, vz, time
+    Csv,
+    /// Legacy ASCII VTK point cloud with id, mass and velocity, for ParaView
+    Vtk,
+    /// NumPy (N, 7) float64 array of mass, x, y, z, vx, vy, vz, for np.load
+    Npy,
+    /// Big-endian TIPSY file of single-precision dark-matter records, for pynbody and other TIPSY tools
+    Tipsy,
+}
+
+// 柱密度网格的文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GridFormat {
+    /// Comma-separated rows along the second axis, with the extent in # comment lines, for numpy.loadtxt
+    Csv,
+    /// FITS image of 64-bit floats with linear axis keywords, for astropy and ds9
+    Fits,
+}
+
+/// Direct and tree-code N-body gravitational simulation.
+///
+/// All quantities are in SI units (m, kg, s) unless --units astro selects astronomical units,
+/// solar masses and years; the defaults below are SI values.
+#[derive(Debug, Clone, Parser, Serialize, Deserialize)]
+#[command(version)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Load parameters from a TOML file; flags given on the command line take precedence
+    #[arg(long)]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    /// Initial conditions: a JSON array of bodies with mass, position, velocity,
+    /// a .csv file with columns mass,x,y,z,vx,vy,vz (header row optional),
+    /// a single-file little-endian Gadget-2 snapshot named *.gadget (values read as-is, no unit conversion),
+    /// or a dark-matter-only TIPSY file named *.tipsy
+    #[arg(long, default_value = "particles.json")]
+    pub input_file: PathBuf,
+
+    /// Directory for plots and other per-step output
+    #[arg(long, default_value = "output")]
+    pub output_dir: PathBuf,
+
+    /// Unit system of the initial conditions and all output; sets the gravitational constant
+    #[arg(long, value_enum, default_value_t = Units::Si)]
+    pub units: Units,
+
+    /// Total number of time steps
+    #[arg(long, default_value_t = 1000)]
+    pub time_steps: usize,
+
+    /// Length of one time step, in seconds
+    #[arg(long, default_value_t = 1.0e3)]
+    pub dt: f64,
+
+    /// Choose each step from the bodies' accelerations and speeds instead of a fixed --dt;
+    /// the run then ends at --t-end. This breaks the strict time reversibility of leapfrog
+    #[arg(long)]
+    pub adaptive_dt: bool,
+
+    /// Safety factor multiplying the adaptive step
+    #[arg(long, default_value_t = 0.1)]
+    pub eta: f64,
+
+    /// Smallest allowed adaptive step, in seconds (defaults to dt / 1000)
+    #[arg(long)]
+    pub dt_min: Option<f64>,
+
+    /// Largest allowed adaptive step, in seconds (defaults to dt)
+    #[arg(long)]
+    pub dt_max: Option<f64>,
+
+    /// Simulated time at which an adaptive run stops, in seconds (defaults to time_steps * dt)
+    #[arg(long)]
+    pub t_end: Option<f64>,
+
+    /// Give each body its own step of dt / 2^k, advancing only the bodies that are due
+    /// (block timesteps); requires --integrator hermite and uses direct summation
+    #[arg(long)]
+    pub block_timesteps: bool,
+
+    /// Accuracy parameter of the Aarseth criterion that picks the block timesteps
+    #[arg(long, default_value_t = 0.02)]
+    pub block_eta: f64,
+
+    /// Deepest block level; the smallest block timestep is dt / 2^N
+    #[arg(long, default_value_t = 20)]
+    pub block_max_level: u32,
+
+    /// Sub-cycle a step whenever it exceeds this fraction of the closest encounter time,
+    /// the minimum pairwise distance / relative speed, halving it until it does not
+    /// (costs an extra O(N^2) pass per step)
+    #[arg(long)]
+    pub encounter_eta: Option<f64>,
+
+    /// Most halvings of a sub-cycled step; at most 2^N substeps per step
+    #[arg(long, default_value_t = 10)]
+    pub encounter_max_level: u32,
+
+    /// Plummer softening length, in meters; prevents the singularity at zero separation.
+    /// "auto" uses --softening-fraction of the mean interparticle spacing of the initial bodies
+    /// (re-estimated from the checkpoint on --resume; pass the logged value to keep it fixed)
+    #[arg(long, default_value_t = Softening::Length(1.0e3))]
+    pub softening_factor: Softening,
+
+    /// Fraction of the mean interparticle spacing used by --softening-factor auto
+    #[arg(long, default_value_t = 0.05)]
+    pub softening_fraction: f64,
+
+    /// Exponent n of the 1/r^n force law. Only 2 is Newtonian gravity; other values are toy models for
+    /// teaching and modified-gravity experiments, with the matching potential in the energy diagnostics
+    /// (direct summation and Plummer softening only)
+    #[arg(long, default_value_t = 2.0)]
+    pub force_exponent: f64,
+
+    /// Shape of the softened force; the cubic spline is exactly Newtonian beyond 2.8 softening lengths.
+    /// (the Hermite integrator supports only Plummer)
+    #[arg(long, value_enum, default_value_t = SofteningKernel::Plummer)]
+    pub softening_kernel: SofteningKernel,
+
+    /// Write the projection plots and diagnostics every N steps (at least 1)
+    #[arg(long, default_value_t = 10)]
+    pub plot_interval: usize,
+
+    /// Write snapshots and plots every this much simulation time instead of every --plot-interval steps,
+    /// numbering them by frame; the initial and final states are always written. Diagnostics stay on --plot-interval
+    #[arg(long)]
+    pub output_time_interval: Option<f64>,
+
+    /// Sample the relative energy error every N steps and plot it to output_dir/energy_error.png
+    /// at the end of the run (0 disables; each sample costs an O(N^2) energy evaluation)
+    #[arg(long, default_value_t = 0)]
+    pub energy_plot_interval: usize,
+
+    /// Time integration scheme
+    #[arg(long, value_enum, default_value_t = Integrator::Leapfrog)]
+    pub integrator: Integrator,
+
+    /// Local error tolerance of --integrator dopri5, relative to the largest position and speed;
+    /// each --dt step is split into as many accepted substeps as this requires
+    #[arg(long, default_value_t = DOPRI5_TOLERANCE)]
+    pub dopri_tolerance: f64,
+
+    /// Side length of the box [0, L] used by --boundary, in meters; on its own it selects a periodic
+    /// box, whose forces use the minimum-image convention (an approximation to the true periodic potential)
+    #[arg(long)]
+    pub box_size: Option<f64>,
+
+    /// Boundary of the simulation box (defaults to periodic when --box-size is given, open otherwise)
+    #[arg(long, value_enum)]
+    pub boundary: Option<BoundaryKind>,
+
+    /// Add a fixed external potential to the inter-particle forces
+    #[arg(long, value_enum)]
+    pub external: Option<ExternalKind>,
+
+    /// Mass of the external point-mass potential, in kg
+    #[arg(long, default_value_t = 1.989e30)]
+    pub external_mass: f64,
+
+    /// Position of the external point mass x,y,z, in meters
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true, default_value = "0,0,0")]
+    pub external_position: Vec<f64>,
+
+    /// Characteristic density of the NFW halo, in kg/m^3
+    #[arg(long, default_value_t = 1.0e-21)]
+    pub nfw_rho0: f64,
+
+    /// Scale radius of the NFW halo, in meters
+    #[arg(long, default_value_t = 6.0e20)]
+    pub nfw_scale_radius: f64,
+
+    /// Constrain the simulation to the x-y plane: initial z and vz are set to zero, z forces are dropped,
+    /// and only the x-y projection is plotted
+    #[arg(long)]
+    pub planar: bool,
+
+    /// Apply Chandrasekhar dynamical friction against a uniform background of this density, in kg/m^3,
+    /// at rest in the simulation frame (friction only; add the host's gravity with --external)
+    #[arg(long)]
+    pub friction_density: Option<f64>,
+
+    /// One-dimensional velocity dispersion of the dynamical-friction background, in m/s
+    #[arg(long, default_value_t = 1.0e5)]
+    pub friction_dispersion: f64,
+
+    /// Coulomb logarithm ln(Lambda) of the dynamical friction
+    #[arg(long, default_value_t = 3.0)]
+    pub coulomb_log: f64,
+
+    /// Only bodies at least this massive feel dynamical friction, in kg
+    #[arg(long, default_value_t = 0.0)]
+    pub friction_min_mass: f64,
+
+    /// Gravity solver
+    #[arg(long, value_enum, default_value_t = ForceAlgorithm::Direct)]
+    pub force: ForceAlgorithm,
+
+    /// Evolve in comoving coordinates with Hubble drag, starting from this scale factor at t = 0
+    /// (periodic boxes and the leapfrog integrator only; see cosmology.rs for the equations)
+    #[arg(long)]
+    pub initial_scale_factor: Option<f64>,
+
+    /// Hubble parameter H0 at scale factor 1, in 1/s (1/yr with --units astro); required by --initial-scale-factor
+    #[arg(long)]
+    pub hubble_constant: Option<f64>,
+
+    /// Matter density parameter of the expansion history; the default 1 with omega_lambda 0 is Einstein-de Sitter
+    #[arg(long, default_value_t = 1.0)]
+    pub omega_matter: f64,
+
+    /// Cosmological-constant density parameter of the expansion history
+    #[arg(long, default_value_t = 0.0)]
+    pub omega_lambda: f64,
+
+    /// Barnes-Hut opening angle (node size / distance); smaller is more accurate
+    #[arg(long, default_value_t = 0.5)]
+    pub theta: f64,
+
+    /// Cells per side of the particle-mesh grid (a power of two); the force resolution is a few box_size / pm_grid
+    #[arg(long, default_value_t = 64)]
+    pub pm_grid: usize,
+
+    /// How to color bodies in the projection plots
+    #[arg(long, value_enum, default_value_t = ColorMode::White)]
+    pub color_by: ColorMode,
+
+    /// How to size the dots of the scatter projections
+    #[arg(long, value_enum, default_value_t = SizeMode::Uniform)]
+    pub size_by: SizeMode,
+
+    /// How to draw the projections: individual dots or a binned mass heatmap
+    #[arg(long, value_enum, default_value_t = Renderer::Scatter)]
+    pub renderer: Renderer,
+
+    /// Draw at most this many bodies in the scatter projections, choosing the same random subset
+    /// (by body id) in every frame
+    #[arg(long)]
+    pub max_plot_points: Option<usize>,
+
+    /// Number of grid cells along each axis of the density heatmap and the --column-density grids
+    #[arg(long, default_value_t = 256)]
+    pub heatmap_resolution: usize,
+
+    /// Keep the plot axes fixed to the bounding box of the initial bodies for all frames;
+    /// bodies that later leave the box are clipped
+    #[arg(long)]
+    pub fixed_bounds: bool,
+
+    /// Padding added to each side of the --fixed-bounds box, as a fraction of its size
+    #[arg(long, default_value_t = 0.1)]
+    pub bounds_padding: f64,
+
+    /// Explicit fixed plot bounds xmin,xmax,ymin,ymax,zmin,zmax (overrides --fixed-bounds)
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    pub plot_bounds: Option<Vec<f64>>,
+
+    /// Write the x-y projection of every plot interval as frames of one looping GIF.
+    /// Frames are streamed to disk as they are drawn, so memory stays at one frame,
+    /// but the file grows linearly and per-frame color quantization is slow for many frames
+    #[arg(long)]
+    pub animate: Option<PathBuf>,
+
+    /// Draw a trail of each body's last N positions (recorded every step, fading from newest to oldest)
+    /// in the scatter projections and GIF; 0 disables. Ignored with more than 1000 bodies
+    #[arg(long, default_value_t = 0)]
+    pub trail_length: usize,
+
+    /// Refuse to start a run that would write more than this many per-frame files (PNGs, snapshots,
+    /// grids); the check also refuses runs whose estimated output exceeds the free disk space. 0 disables the limit
+    #[arg(long, default_value_t = 20000)]
+    pub max_output_files: usize,
+
+    /// Delay between GIF frames, in milliseconds
+    #[arg(long, default_value_t = 100)]
+    pub frame_delay: u32,
+
+    /// Plot the projections, GIF and 3D view in the center-of-mass frame rotated so that the total
+    /// angular momentum points along +z: x-y is then face-on and x-z, y-z edge-on (the simulation is unchanged)
+    #[arg(long)]
+    pub face_on: bool,
+
+    /// Write the three projections side by side into one PNG (output_dir/proj_XXXX.png)
+    /// instead of separate xy/xz/yz files
+    #[arg(long)]
+    pub combined_projections: bool,
+
+    /// Also plot the spherically averaged density profile (output_dir/density_profile_XXXX.png) every plot interval
+    #[arg(long)]
+    pub density_profile: bool,
+
+    /// Also plot radius against radial velocity about the center of mass (output_dir/phase_space_XXXX.png)
+    /// every plot interval
+    #[arg(long)]
+    pub phase_space: bool,
+
+    /// Also draw a 3D perspective scatter plot (output_dir/view3d_XXXX.png) every plot interval,
+    /// with nearer bodies drawn larger and brighter
+    #[arg(long)]
+    pub plot_3d: bool,
+
+    /// Viewing azimuth of the 3D plot, in degrees
+    #[arg(long, default_value_t = 30.0)]
+    pub view_yaw: f64,
+
+    /// Viewing elevation of the 3D plot, in degrees
+    #[arg(long, default_value_t = 20.0)]
+    pub view_pitch: f64,
+
+    /// Advance the 3D viewing azimuth by this many degrees per plot interval, for an orbiting camera
+    #[arg(long, default_value_t = 0.0)]
+    pub view_rotation: f64,
+
+    /// Number of logarithmic radial shells in the density profile
+    #[arg(long, default_value_t = 30)]
+    pub profile_bins: usize,
+
+    /// Outer radius of the density profile, in meters (defaults to the farthest body)
+    #[arg(long)]
+    pub profile_r_max: Option<f64>,
+
+    /// Skip the per-frame PNG projections (useful together with --animate)
+    #[arg(long = "no-png", action = clap::ArgAction::SetFalse)]
+    pub png_frames: bool,
+
+    /// Hide the progress bar (it is also hidden automatically when stderr is not a terminal)
+    #[arg(long = "no-progress", action = clap::ArgAction::SetFalse)]
+    pub progress_bar: bool,
+
+    /// Also write a particle snapshot (output_dir/snapshot_XXXX.<ext>) every plot interval
+    #[arg(long, value_enum)]
+    pub snapshot_format: Option<SnapshotFormat>,
+
+    /// Also write the mass-weighted column density of each projection on a heatmap_resolution grid
+    /// (output_dir/xy_column_XXXX.<ext>) every plot interval, over the --fixed-bounds box when given
+    #[arg(long, value_enum)]
+    pub column_density: Option<GridFormat>,
+
+    /// Gzip the CSV or TIPSY snapshots (snapshot_XXXX.csv.gz); they still load directly as --input-file
+    #[arg(long)]
+    pub gzip_snapshots: bool,
+
+    /// Write the whole trajectory to one long-format file (.csv, or .jsonl/.ndjson for JSON lines)
+    #[arg(long)]
+    pub trajectory: Option<PathBuf>,
+
+    /// Append a trajectory row for every body every N steps (at least 1)
+    #[arg(long, default_value_t = 1)]
+    pub trajectory_interval: usize,
+
+    /// Resume from a checkpoint file instead of reading --input-file; the step counter continues
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+
+    /// Save a checkpoint every N steps (0 disables); the final state is always saved when enabled
+    #[arg(long, default_value_t = 0)]
+    pub checkpoint_interval: usize,
+
+    /// Checkpoint file to write (defaults to output_dir/checkpoint.json)
+    #[arg(long)]
+    pub checkpoint_file: Option<PathBuf>,
+
+    /// Append energy and momentum diagnostics to this CSV file
+    #[arg(long)]
+    pub diagnostics_csv: Option<PathBuf>,
+
+    /// Append the temperature (mean kinetic energy per body) and the mass-weighted velocity dispersion
+    /// about the center-of-mass velocity to this CSV file every plot interval
+    #[arg(long)]
+    pub dispersion_csv: Option<PathBuf>,
+
+    /// Every plot interval, check that the body count and total mass changed only as the enabled
+    /// escaper removal and merging allow, and stop with an error otherwise
+    #[arg(long)]
+    pub check_mass: bool,
+
+    /// Shift the initial conditions to the center-of-mass frame (COM at the origin, zero net momentum)
+    #[arg(long)]
+    pub com_frame: bool,
+
+    /// Remove bodies farther than this distance from the center of mass, in meters
+    #[arg(long)]
+    pub escape_radius: Option<f64>,
+
+    /// Look for escaping bodies every N steps
+    #[arg(long, default_value_t = 10)]
+    pub escape_interval: usize,
+
+    /// Handle overlapping bodies that carry a radius after every step; bodies without one never collide
+    #[arg(long, value_enum)]
+    pub collisions: Option<CollisionMode>,
+
+    /// Append one JSON line {step, time, survivor_id, absorbed_id, combined_mass} per merger to this file
+    /// (needs --collisions merge); the survivor, the heavier body, keeps its id
+    #[arg(long)]
+    pub merger_log: Option<PathBuf>,
+
+    /// Find friends-of-friends groups with this linking length (in the length unit) every plot interval
+    /// and report how many have at least --fof-min-members members and the mass of the largest
+    #[arg(long)]
+    pub fof_linking_length: Option<f64>,
+
+    /// Smallest friends-of-friends group counted by --fof-linking-length
+    #[arg(long, default_value_t = 10)]
+    pub fof_min_members: usize,
+
+    /// Report Lagrangian radii every N steps (0 disables)
+    #[arg(long, default_value_t = 0)]
+    pub lagrangian_interval: usize,
+
+    /// Mass fractions whose enclosing radii are reported
+    #[arg(long, value_delimiter = ',', default_value = "0.1,0.5,0.9")]
+    pub lagrangian_fractions: Vec<f64>,
+
+    /// Also append the Lagrangian radii to this CSV file
+    #[arg(long)]
+    pub lagrangian_csv: Option<PathBuf>,
+
+    /// Also report the Lagrangian radii and energies of each species separately (about its own
+    /// center of mass, counting only the interactions within the species)
+    #[arg(long)]
+    pub per_species: bool,
+
+    /// Write the quadrupole-formula gravitational-wave strain h+, hx to this CSV file every plot interval;
+    /// Q_ij is differentiated numerically, so each row lags the run by one plot interval
+    #[arg(long)]
+    pub gw_csv: Option<PathBuf>,
+
+    /// Line of sight x,y,z along which the strain is observed
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true, default_value = "0,0,1")]
+    pub gw_line_of_sight: Vec<f64>,
+
+    /// Distance from the observer to the center of mass (the default is 1 Mpc in meters)
+    #[arg(long, default_value_t = 3.0857e22)]
+    pub gw_distance: f64,
+
+    /// Check for NaN/Inf positions and velocities every N steps (0 disables; on by default in debug builds)
+    #[arg(long, default_value_t = if cfg!(debug_assertions) { 10 } else { 0 })]
+    pub check_finite: usize,
+
+    /// Instead of a normal run, integrate --time-steps steps forward, reverse the velocities,
+    /// integrate back, and report how far the bodies end up from where they started. Leapfrog,
+    /// verlet and yoshida4 return to round-off; rk4 and hermite are not time-symmetric and fail by design
+    #[arg(long)]
+    pub reversibility_test: bool,
+
+    /// Instead of a normal run, integrate the initial conditions --compare-steps steps at the same --dt with
+    /// each of the --compare-list integrators, and plot their relative energy errors together to
+    /// output_dir/integrator_comparison.png
+    #[arg(long)]
+    pub compare_integrators: bool,
+
+    /// Integrators run by --compare-integrators; ones the configuration does not allow are skipped with a warning
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "leapfrog,rk4,yoshida4")]
+    pub compare_list: Vec<Integrator>,
+
+    /// Steps per integrator for --compare-integrators, independent of --time-steps
+    #[arg(long, default_value_t = 2000)]
+    pub compare_steps: usize,
+
+    /// Instead of a normal run, check that the forces match the potential: compare the accelerations
+    /// of up to 32 initial bodies with a central-difference gradient of the total potential energy,
+    /// and fail if they disagree by more than finite-difference error
+    #[arg(long)]
+    pub verify_gradient: bool,
+
+    /// Number of worker threads for the force computation, plots and diagnostics; 0 uses rayon's
+    /// default (RAYON_NUM_THREADS if set, otherwise one per core). Set it to the cores allocated on shared nodes
+    #[arg(long, default_value_t = 0)]
+    pub threads: usize,
+
+    /// Random seed for the initial-condition generators and the plot subsampling. The same seed with the same
+    /// configuration gives identical output; --resume continues with the seed saved in the checkpoint
+    #[arg(long, global = true, default_value_t = 42)]
+    pub seed: u64,
+
+    /// Report at the end how the wall time of the main loop split between force computation,
+    /// integration, diagnostics and output (plots, snapshots, checkpoints)
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Load the initial conditions, time one real step, report the projected wall time, memory
+    /// and number of output files, then exit without simulating or writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::parse_from([env!("CARGO_PKG_NAME")])
+    }
+}
+
+impl Config {
+    // 解析命令行; 若给出 --config, 则以配置文件为基础, 再用命令行中显式给出的参数覆盖
+    pub fn load() -> Result<Self, SimError> {
+        let matches = Self::command().get_matches();
+        let cli = Self::from_arg_matches(&matches).map_err(|e| SimError::Config(e.to_string()))?;
+        let Some(path) = &cli.config else {
+            cli.validate()?;
+            return Ok(cli);
+        };
+
+        let text = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SimError::InputNotFound(path.clone()),
+            _ => SimError::Config(format!("Cannot read config file '{}': {}", path.display(), e)),
+        })?;
+        let invalid = |e: &dyn std::fmt::Display| SimError::Config(format!("Invalid config file '{}': {}", path.display(), e));
+        let file: Config = toml::from_str(&text).map_err(|e| invalid(&e))?;
+
+        // 参数 id 与字段名一致, 因此可以按 id 合并两份配置
+        let mut merged = toml::Table::try_from(&file).map_err(|e| invalid(&e))?;
+        let cli_values = toml::Table::try_from(&cli).map_err(|e| invalid(&e))?;
+        for id in matches.ids() {
+            if matches.value_source(id.as_str()) == Some(ValueSource::CommandLine) {
+                if let Some(value) = cli_values.get(id.as_str()) {
+                    merged.insert(id.to_string(), value.clone());
+                }
+            }
+        }
+
+        let mut config: Config = merged.try_into().map_err(|e| invalid(&e))?;
+        config.config = cli.config;
+        config.command = cli.command;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // 命令行与配置文件合并后才能检查的取值范围; 选项之间的组合在 Simulation::new 中检查
+    fn validate(&self) -> Result<(), SimError> {
+        if self.plot_interval == 0 {
+            return Err(SimError::Config("plot_interval must be at least 1".to_string()));
+        }
+        if self.trajectory_interval == 0 {
+            return Err(SimError::Config("trajectory_interval must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn force_method(&self) -> ForceMethod {
+        match self.force {
+            ForceAlgorithm::Direct => ForceMethod::Direct,
+            ForceAlgorithm::Pairwise => ForceMethod::Pairwise,
+            ForceAlgorithm::Tree => ForceMethod::BarnesHut { theta: self.theta as Real },
+            ForceAlgorithm::Pm => ForceMethod::ParticleMesh { grid: self.pm_grid },
+            ForceAlgorithm::Ewald => ForceMethod::Ewald,
+        }
+    }
+
+    // 未给出 boundary 时, 只要给了 box_size 就是周期性盒子
+    pub fn boundary_condition(&self) -> Result<BoundaryCondition, SimError> {
+        let default = if self.box_size.is_some() { BoundaryKind::Periodic } else { BoundaryKind::Open };
+        let kind = self.boundary.unwrap_or(default);
+        if kind == BoundaryKind::Open {
+            return match self.box_size {
+                None => Ok(BoundaryCondition::Open),
+                Some(_) => Err(SimError::Config("box_size cannot be combined with an open boundary".to_string())),
+            };
+        }
+        let size = self
+            .box_size
+            .ok_or_else(|| SimError::Config("periodic and reflecting boundaries require box_size".to_string()))?;
+        if size <= 0.0 {
+            return Err(SimError::Config(format!("box_size must be positive, got {:e}", size)));
+        }
+        let size = size as Real;
+        Ok(match kind {
+            BoundaryKind::Reflecting => BoundaryCondition::Reflecting { size },
+            _ => BoundaryCondition::Periodic { size },
+        })
+    }
+
+    pub fn external_potential(&self) -> Result<Option<ExternalPotential>, SimError> {
+        Ok(match self.external {
+            Some(ExternalKind::Point) => {
+                let &[x, y, z] = self.external_position.as_slice() else {
+                    let count = self.external_position.len();
+                    return Err(SimError::Config(format!("external_position needs 3 values, got {}", count)));
+                };
+                let position = Vec3::new(x as Real, y as Real, z as Real);
+                Some(ExternalPotential::PointMass { mass: self.external_mass as Real, position })
+            }
+            Some(ExternalKind::Nfw) => Some(ExternalPotential::NFW {
+                rho0: self.nfw_rho0 as Real,
+                scale_radius: self.nfw_scale_radius as Real,
+            }),
+            None => None,
+        })
+    }
+
+    pub fn dynamical_friction(&self) -> Result<Option<DynamicalFriction>, SimError> {
+        let Some(density) = self.friction_density else {
+            return Ok(None);
+        };
+        let positive = |name: &str, value: f64| {
+            if value > 0.0 && value.is_finite() {
+                Ok(value as Real)
+            } else {
+                Err(SimError::Config(format!("{} must be positive, got {:e}", name, value)))
+            }
+        };
+        Ok(Some(DynamicalFriction {
+            background_density: positive("friction_density", density)?,
+            velocity_dispersion: positive("friction_dispersion", self.friction_dispersion)?,
+            coulomb_log: positive("coulomb_log", self.coulomb_log)?,
+            min_mass: self.friction_min_mass as Real,
+        }))
+    }
+
+    pub fn cosmology(&self) -> Result<Option<Cosmology>, SimError> {
+        let Some(initial_scale_factor) = self.initial_scale_factor else {
+            return Ok(None);
+        };
+        if initial_scale_factor <= 0.0 || !initial_scale_factor.is_finite() {
+            return Err(SimError::Config(format!("initial_scale_factor must be positive, got {:e}", initial_scale_factor)));
+        }
+        let hubble_constant = self
+            .hubble_constant
+            .ok_or_else(|| SimError::Config("initial_scale_factor requires hubble_constant".to_string()))?;
+        if hubble_constant <= 0.0 || !hubble_constant.is_finite() {
+            return Err(SimError::Config(format!("hubble_constant must be positive, got {:e}", hubble_constant)));
+        }
+        let cosmology = Cosmology { initial_scale_factor, hubble_constant, omega_matter: self.omega_matter, omega_lambda: self.omega_lambda };
+        if cosmology.hubble_rate(initial_scale_factor) <= 0.0 {
+            return Err(SimError::Config("The expansion history has H^2 <= 0 at the initial scale factor".to_string()));
+        }
+        Ok(Some(cosmology))
+    }
+
+    pub fn quadrupole_strain(&self) -> Result<Option<QuadrupoleStrain>, SimError> {
+        if self.gw_csv.is_none() {
+            return Ok(None);
+        }
+        let &[x, y, z] = self.gw_line_of_sight.as_slice() else {
+            let count = self.gw_line_of_sight.len();
+            return Err(SimError::Config(format!("gw_line_of_sight needs 3 values, got {}", count)));
+        };
+        let line_of_sight = DVec3::new(x, y, z);
+        if line_of_sight == DVec3::ZERO || !line_of_sight.is_finite() {
+            return Err(SimError::Config("gw_line_of_sight must be a nonzero vector".to_string()));
+        }
+        if self.gw_distance <= 0.0 || !self.gw_distance.is_finite() {
+            return Err(SimError::Config(format!("gw_distance must be positive, got {:e}", self.gw_distance)));
+        }
+        let Some(speed_of_light) = self.units.speed_of_light() else {
+            return Err(SimError::Config("--gw-csv needs physical units; N-body units have no speed of light".to_string()));
+        };
+        Ok(Some(QuadrupoleStrain::new(line_of_sight, self.gw_distance, self.units, speed_of_light)))
+    }
+
+    // bodies 为初始状态, --fixed-bounds 时由它确定所有帧的坐标范围
+    pub fn plot_options(&self, bodies: &[Body]) -> Result<PlotOptions, SimError> {
+        let bounds = match &self.plot_bounds {
+            Some(v) => {
+                let &[x0, x1, y0, y1, z0, z1] = v.as_slice() else {
+                    return Err(SimError::Config(format!("plot_bounds needs 6 values, got {}", v.len())));
+                };
+                if !(x0 < x1 && y0 < y1 && z0 < z1) {
+                    return Err(SimError::Config("plot_bounds must be ordered as min,max for each axis".to_string()));
+                }
+                let corner = |x, y, z| Vec3::new(x as Real, y as Real, z as Real);
+                Some(Bounds { min: corner(x0, y0, z0), max: corner(x1, y1, z1) })
+            }
+            None if self.fixed_bounds => Some(Bounds::from_bodies(bodies, self.bounds_padding as Real)),
+            None => None,
+        };
+        Ok(PlotOptions {
+            color: self.color_by,
+            renderer: self.renderer,
+            size: self.size_by,
+            resolution: self.heatmap_resolution,
+            max_points: self.max_plot_points,
+            bounds,
+            units: self.units,
+            seed: self.seed,
+        })
+    }
+}
+
+// 生成初始条件的子命令, 输出可直接作为 --input-file 使用
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Generate a Plummer sphere in its center-of-mass frame
+    Plummer {
+        /// Output file: JSON, or TIPSY if it ends in .tipsy
+        output: PathBuf,
+        /// Number of bodies
+        #[arg(long, default_value_t = 1000)]
+        n: usize,
+        /// Total mass, in kg
+        #[arg(long, default_value_t = 1.0e12)]
+        total_mass: f64,
+        /// Plummer scale radius, in meters
+        #[arg(long, default_value_t = 1.0e5)]
+        scale_radius: f64,
+    },
+    /// Generate a rotating exponential disk in the xy plane, spinning about +z
+    Disk {
+        /// Output file: JSON, or TIPSY if it ends in .tipsy
+        output: PathBuf,
+        /// Number of bodies
+        #[arg(long, default_value_t = 1000)]
+        n: usize,
+        /// Total mass, in kg
+        #[arg(long, default_value_t = 1.0e12)]
+        total_mass: f64,
+        /// Radial scale length of the exponential surface density, in meters
+        #[arg(long, default_value_t = 1.0e5)]
+        scale_length: f64,
+        /// Vertical scale height of the sech^2 profile, in meters
+        #[arg(long, default_value_t = 1.0e4)]
+        scale_height: f64,
+    },
+    /// Generate a uniform sphere of bodies at rest (cold-collapse test case)
+    ColdSphere {
+        /// Output file: JSON, or TIPSY if it ends in .tipsy
+        output: PathBuf,
+        /// Number of bodies
+        #[arg(long, default_value_t = 1000)]
+        n: usize,
+        /// Total mass, in kg
+        #[arg(long, default_value_t = 1.0e12)]
+        total_mass: f64,
+        /// Radius of the sphere, in meters
+        #[arg(long, default_value_t = 1.0e5)]
+        radius: f64,
+    },
+    /// Generate a two-body Kepler orbit starting at pericenter
+    Kepler {
+        /// Output file: JSON, or TIPSY if it ends in .tipsy
+        output: PathBuf,
+        /// Mass of the first body, in kg
+        #[arg(long, default_value_t = 1.0e12)]
+        m1: f64,
+        /// Mass of the second body, in kg
+        #[arg(long, default_value_t = 1.0e10)]
+        m2: f64,
+        /// Semi-major axis of the relative orbit, in meters
+        #[arg(long, default_value_t = 1.0e5)]
+        semi_major: f64,
+        /// Orbital eccentricity, 0 <= e < 1
+        #[arg(long, default_value_t = 0.0)]
+        eccentricity: f64,
+    },
+    /// Generate a hierarchical triple: a stellar binary with a coplanar circumbinary planet, all starting at pericenter
+    Hierarchical {
+        /// Output file: JSON, or TIPSY if it ends in .tipsy
+        output: PathBuf,
+        /// Mass of the primary star, in kg
+        #[arg(long, default_value_t = 1.0e12)]
+        m1: f64,
+        /// Mass of the secondary star, in kg
+        #[arg(long, default_value_t = 5.0e11)]
+        m2: f64,
+        /// Mass of the planet, in kg
+        #[arg(long, default_value_t = 1.0e9)]
+        m_planet: f64,
+        /// Semi-major axis of the inner binary, in meters
+        #[arg(long, default_value_t = 1.0e4)]
+        inner_a: f64,
+        /// Semi-major axis of the planet's orbit about the binary's center of mass, in meters
+        #[arg(long, default_value_t = 5.0e4)]
+        outer_a: f64,
+        /// Eccentricity of the inner binary, 0 <= e < 1
+        #[arg(long, default_value_t = 0.0)]
+        inner_e: f64,
+        /// Eccentricity of the planet's orbit, 0 <= e < 1
+        #[arg(long, default_value_t = 0.0)]
+        outer_e: f64,
+    },
+    /// Summarize an initial-conditions file (counts, masses, extent, center of mass, momentum) and exit
+    Inspect {
+        /// JSON or CSV file of bodies, in the units selected by --units
+        input: PathBuf,
+    },
+    /// Rescale initial conditions given in --units to N-body units (G = 1, total mass 1, virial radius 1),
+    /// print the length, mass and time units and save them next to the output; run the result with --units nbody
+    NbodyUnits {
+        /// Initial conditions in the units selected by --units
+        input: PathBuf,
+        /// Output file: JSON, or TIPSY if it ends in .tipsy
+        output: PathBuf,
+        /// Also scale the velocities about the center of mass to virial equilibrium (2K = -W), so the energy is -1/4
+        #[arg(long)]
+        virialize: bool,
+        /// JSON file for the scale factors, read back by physical-units (default: the output path with extension .scale.json)
+        #[arg(long)]
+        scale_file: Option<PathBuf>,
+    },
+    /// Convert bodies in N-body units (such as a final snapshot of a --units nbody run) back to the units
+    /// of the original initial conditions, using the scale factors saved by nbody-units
+    PhysicalUnits {
+        /// Bodies in N-body units: JSON, CSV, Gadget or TIPSY
+        input: PathBuf,
+        /// Scale factors written by nbody-units
+        scale_file: PathBuf,
+        /// Output file: JSON, or TIPSY if it ends in .tipsy
+        output: PathBuf,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_plot_interval_is_rejected() {
+        let config = Config { plot_interval: 0, ..Config::default() };
+        assert!(matches!(config.validate(), Err(SimError::Config(_))));
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn zero_trajectory_interval_is_rejected() {
+        let config = Config { trajectory_interval: 0, ..Config::default() };
+        assert!(matches!(config.validate(), Err(SimError::Config(_))));
+    }
+}
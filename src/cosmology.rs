@@ -0,0 +1,108 @@
+// 共动坐标下的宇宙学模拟: 粒子位置 x 为共动坐标 (物理坐标 r = a x), 速度为 v = dx/dt.
+// 背景按 Friedmann 方程膨胀:
+//   H(a) = da/dt / a = H0 sqrt(Omega_m a^-3 + Omega_k a^-2 + Omega_Lambda),  Omega_k = 1 - Omega_m - Omega_Lambda
+// (Omega_m = 1, Omega_Lambda = 0 为 Einstein-de Sitter, a^(3/2) = a_i^(3/2) + 3/2 H0 t).
+// 周期性盒子中 (平均密度已从泊松方程中减去), 运动方程为
+//   d^2x/dt^2 + 2 H dx/dt = F(x) / a^3,  F(x) = G sum_j m_j (x_j - x) / |x_j - x|^3,
+// F 即在共动坐标中按通常方式计算的引力 (含软化); Hubble 阻力 2 H v 使均匀膨胀的背景中本动速度按 a^-2 衰减.
+// 记 p = a^2 v, 上式化为 dp/dt = F / a, dx/dt = p / a^2, 没有显含速度的项, 因此可以用
+// kick-drift-kick 的 Leapfrog 积分, kick 因子为 int dt / a, drift 因子为 int dt / a^2 (Quinn et al. 1997).
+// 粒子的加速度字段保存共动引力 F; 动能与势能之和在共动坐标中不守恒 (满足 Layzer-Irvine 方程)
+use crate::{update_accelerations, Body, ForceParams, Real};
+use rayon::prelude::*;
+
+// 从 t = 0 积分 a(t) 时 RK4 的步数; a(t) 光滑, 相对误差远小于单精度
+const EXPANSION_STEPS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cosmology {
+    // t = 0 时的尺度因子
+    pub initial_scale_factor: f64,
+    // a = 1 时的 Hubble 参数 H0, 单位为所选单位制中时间单位的倒数
+    pub hubble_constant: f64,
+    pub omega_matter: f64,
+    pub omega_lambda: f64,
+}
+
+impl Cosmology {
+    // 尺度因子为 a 时的 Hubble 参数; 膨胀停止 (H^2 <= 0) 时返回 0
+    pub fn hubble_rate(&self, a: f64) -> f64 {
+        let curvature = 1.0 - self.omega_matter - self.omega_lambda;
+        let h_sq = self.omega_matter / (a * a * a) + curvature / (a * a) + self.omega_lambda;
+        self.hubble_constant * h_sq.max(0.0).sqrt()
+    }
+
+    // 模拟时间 t 时的尺度因子, 用 RK4 积分 da/dt = a H(a); 只依赖 t, 因此续算时不需要保存
+    pub fn scale_factor(&self, t: f64) -> f64 {
+        let h = t / EXPANSION_STEPS as f64;
+        let rate = |a: f64| a * self.hubble_rate(a);
+        (0..EXPANSION_STEPS).fold(self.initial_scale_factor, |a, _| {
+            let k1 = rate(a);
+            let k2 = rate(a + 0.5 * h * k1);
+            let k3 = rate(a + 0.5 * h * k2);
+            let k4 = rate(a + h * k3);
+            a + h / 6.0 * (k1 + 2.0 * k2 + 2.0 * k3 + k4)
+        })
+    }
+
+    // (int_t0^t1 dt / a, int_t0^t1 dt / a^2), 用 Simpson 公式
+    fn kick_drift_factors(&self, t0: f64, t1: f64) -> (f64, f64) {
+        let (a0, am, a1) = (self.scale_factor(t0), self.scale_factor(0.5 * (t0 + t1)), self.scale_factor(t1));
+        let simpson = |f: fn(f64) -> f64| (t1 - t0) / 6.0 * (f(a0) + 4.0 * f(am) + f(a1));
+        (simpson(|a| 1.0 / a), simpson(|a| 1.0 / (a * a)))
+    }
+}
+
+// 共动坐标中从时间 t 推进 dt 的 Leapfrog 步 (kick-drift-kick 作用于 p = a^2 v).
+// 要求进入时 bodies 的加速度是当前位置的共动引力 F
+pub fn comoving_leapfrog_step(bodies: &mut [Body], t: f64, dt: f64, cosmology: &Cosmology, params: &ForceParams) {
+    let (a0, a1) = (cosmology.scale_factor(t), cosmology.scale_factor(t + dt));
+    let (kick_first, _) = cosmology.kick_drift_factors(t, t + 0.5 * dt);
+    let (kick_second, _) = cosmology.kick_drift_factors(t + 0.5 * dt, t + dt);
+    let (_, drift) = cosmology.kick_drift_factors(t, t + dt);
+
+    // 速度暂时存放 p = a^2 v
+    bodies.par_iter_mut().for_each(|body| {
+        body.velocity = body.velocity * (a0 * a0) as Real + body.acceleration * kick_first as Real;
+        body.position += body.velocity * drift as Real;
+    });
+    params.boundary.apply(bodies);
+    update_accelerations(bodies, params);
+    bodies.par_iter_mut().for_each(|body| {
+        body.velocity = (body.velocity + body.acceleration * kick_second as Real) / (a1 * a1) as Real;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::test_params;
+    use crate::{BoundaryCondition, ForceMethod, Vec3};
+
+    #[test]
+    fn uniform_expanding_field_has_no_peculiar_acceleration() {
+        // 周期性单位盒子中静止于共动坐标的均匀格子: 减去平均密度后没有引力, 本动速度始终为零
+        let cosmology = Cosmology { initial_scale_factor: 0.1, hubble_constant: 1.0, omega_matter: 1.0, omega_lambda: 0.0 };
+        let params = ForceParams { method: ForceMethod::Ewald, boundary: BoundaryCondition::Periodic { size: 1.0 }, ..test_params(1.0, 0.0) };
+        let mut bodies: Vec<Body> = (0..64)
+            .map(|i| Body::new(1.0 / 64.0, Vec3::new((i % 4) as Real, (i / 4 % 4) as Real, (i / 16) as Real) * 0.25 + 0.125, Vec3::ZERO))
+            .collect();
+        let start: Vec<Vec3> = bodies.iter().map(|b| b.position).collect();
+        update_accelerations(&mut bodies, &params);
+
+        let dt = 0.01;
+        for step in 0..20 {
+            comoving_leapfrog_step(&mut bodies, step as f64 * dt, dt, &cosmology, &params);
+        }
+        // 最近邻的单个引力为 16 / 64 = 0.25, 以此作为尺度
+        for (body, start) in bodies.iter().zip(&start) {
+            assert!(body.acceleration.length() < 0.25 * 1e-3, "F = {:?}", body.acceleration);
+            assert!(body.velocity.length() < 1e-6, "v = {:?}", body.velocity);
+            assert!(body.position.distance(*start) < 1e-8, "{:?} -> {:?}", start, body.position);
+        }
+
+        // Einstein-de Sitter: a^(3/2) = a_i^(3/2) + 3/2 H0 t
+        let expected = (0.1f64.powf(1.5) + 1.5 * 0.2).powf(2.0 / 3.0);
+        assert!((cosmology.scale_factor(0.2) / expected - 1.0).abs() < 1e-10);
+    }
+}
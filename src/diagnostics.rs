@@ -0,0 +1,571 @@
+// 守恒量等诊断量
+use crate::force::{minimum_image, pair_softening_sq};
+use crate::precision::{from_dvec3, to_dvec3, to_f64};
+use crate::units::Units;
+use crate::{update_accelerations, Body, ForceParams, Real, SimError, Vec3};
+use glam::{DQuat, DVec3};
+use rayon::prelude::*;
+
+// Kahan 补偿求和: 按给定的顺序累加, 并把每次加法的舍入误差补回下一项, 误差不随项数增长.
+// rayon 的 sum 按线程间的任务划分合并部分和, 顺序 (因而最后几位) 随线程数与调度变化;
+// 诊断量先并行算出各项 (collect 保持顺序), 再用它顺序求和, 同样的输入在任意线程数下逐位相同
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KahanSum<T> {
+    sum: T,
+    compensation: T,
+}
+
+impl<T: Copy + std::ops::Add<Output = T> + std::ops::Sub<Output = T>> KahanSum<T> {
+    pub fn add(&mut self, value: T) {
+        let y = value - self.compensation;
+        let t = self.sum + y;
+        self.compensation = (t - self.sum) - y;
+        self.sum = t;
+    }
+
+    pub fn value(&self) -> T {
+        self.sum
+    }
+}
+
+pub fn kahan_sum(values: impl IntoIterator<Item = f64>) -> f64 {
+    let mut sum = KahanSum::default();
+    values.into_iter().for_each(|v| sum.add(v));
+    sum.value()
+}
+
+// 返回 (动能, 势能, 总能量), 势能使用与受力一致的软化核与力律 (Plummer 为 -G m_i m_j / sqrt(r^2 + eps^2)),
+// 周期性边界下同样取最小镜像距离, 有外部势时计入各粒子在外部势中的势能. 各项以 f64 按固定顺序做 Kahan 求和
+pub fn total_energy(bodies: &[Body], params: &ForceParams) -> (f64, f64, f64) {
+    let (g, softening, box_size) = (params.g, params.softening, params.boundary.periodic_size());
+    let kinetic_terms: Vec<f64> = bodies.par_iter().map(|b| 0.5 * to_f64(b.mass) * to_f64(b.velocity.length_squared())).collect();
+    let kinetic = kahan_sum(kinetic_terms);
+
+    // O(N^2), 按行并行, 各行的和再按行号顺序相加
+    let rows: Vec<f64> = (0..bodies.len())
+        .into_par_iter()
+        .map(|i| {
+            let body_i = &bodies[i];
+            let mut sum = KahanSum::default();
+            for body_j in &bodies[i + 1..] {
+                // 与受力计算一致, 跳过位置重合的粒子对
+                if body_i.position == body_j.position {
+                    continue;
+                }
+                let distance_sq = minimum_image(body_j.position - body_i.position, box_size).length_squared();
+                let softening_sq = pair_softening_sq(body_i.softening_sq(softening), body_j.softening_sq(softening));
+                sum.add(to_f64(params.pair_potential(distance_sq, g * body_i.mass, softening_sq)) * to_f64(body_j.mass));
+            }
+            sum.value()
+        })
+        .collect();
+    let potential = kahan_sum(rows);
+    let external = match &params.external {
+        Some(external) => {
+            let terms: Vec<f64> = bodies.par_iter().map(|b| to_f64(b.mass) * to_f64(external.potential(b.position, g))).collect();
+            kahan_sum(terms)
+        }
+        None => 0.0,
+    };
+    let potential = potential + external;
+
+    (kinetic, potential, kinetic + potential)
+}
+
+// 中心差分的步长, 相对于粒子到最近邻的距离
+const GRADIENT_STEP: f64 = 1e-4;
+
+// 受力与势能的一致性检验: 对均匀选取的至多 samples 个非固定粒子, 用 total_energy 中势能 U 的中心差分
+// a_i = -(dU/dx_i) / m_i 估计加速度, 与 update_accelerations 比较, 返回最大相对误差 |a_fd - a| / |a|.
+// 动力学摩擦不是势力, 平面模拟会丢弃 z 分量, 检验时两者都去掉; 树方法与 PM 的近似误差,
+// 以及 Ewald 求和与最小镜像势能之间的差别都会计入误差. 每个样本需要 6 次 O(N^2) 的势能计算
+pub fn gradient_error(bodies: &[Body], params: &ForceParams, samples: usize) -> f64 {
+    let params = ForceParams { friction: None, planar: false, ..*params };
+    let mut state = bodies.to_vec();
+    update_accelerations(&mut state, &params);
+
+    let candidates: Vec<usize> = (0..state.len()).filter(|&i| !state[i].fixed && state[i].mass > 0.0).collect();
+    let stride = candidates.len().div_ceil(samples.max(1)).max(1);
+    let mut max_error: f64 = 0.0;
+    for &i in candidates.iter().step_by(stride) {
+        let position = state[i].position;
+        let nearest = state
+            .iter()
+            .filter(|b| b.position != position)
+            .map(|b| to_f64(b.position.distance(position)))
+            .fold(f64::INFINITY, f64::min);
+        if !nearest.is_finite() {
+            continue;
+        }
+        let h = GRADIENT_STEP * nearest;
+        let mut gradient = DVec3::ZERO;
+        for axis in 0..3 {
+            // 用舍入后实际的坐标差作分母
+            let mut potential_at = |offset: f64| {
+                state[i].position[axis] = position[axis] + offset as Real;
+                (to_f64(state[i].position[axis]), total_energy(&state, &params).1)
+            };
+            let ((x1, u1), (x0, u0)) = (potential_at(h), potential_at(-h));
+            gradient[axis] = (u1 - u0) / (x1 - x0);
+            state[i].position = position;
+        }
+        let numerical = -gradient / to_f64(state[i].mass);
+        let analytic = to_dvec3(state[i].acceleration);
+        if analytic.length() > 0.0 {
+            max_error = max_error.max((numerical - analytic).length() / analytic.length());
+        }
+    }
+    max_error
+}
+
+// 返回 (总线动量 sum m v, 总角动量 sum m r x v), 只有内部引力时两者都应守恒
+pub fn momentum_diagnostics(bodies: &[Body]) -> (DVec3, DVec3) {
+    let (mut p, mut l) = (KahanSum::default(), KahanSum::default());
+    for b in bodies {
+        let momentum = to_dvec3(b.velocity) * to_f64(b.mass);
+        p.add(momentum);
+        l.add(to_dvec3(b.position).cross(momentum));
+    }
+    (p.value(), l.value())
+}
+
+// 返回 (sigma, (sigma_x, sigma_y, sigma_z)): 相对质心速度的质量加权速度弥散, sigma^2 = sigma_x^2 + sigma_y^2 + sigma_z^2
+// 为三维弥散. 位力平衡的系统中 sigma 趋于稳定, 可作为弛豫的另一个指标
+pub fn velocity_dispersion(bodies: &[Body]) -> (f64, DVec3) {
+    let total_mass: f64 = bodies.iter().map(|b| to_f64(b.mass)).sum();
+    if bodies.is_empty() || total_mass <= 0.0 {
+        return (0.0, DVec3::ZERO);
+    }
+    let mean = to_dvec3(center_of_mass(bodies).1);
+    let variance = bodies.iter().fold(DVec3::ZERO, |sum, b| {
+        let offset = to_dvec3(b.velocity) - mean;
+        sum + offset * offset * to_f64(b.mass)
+    }) / total_mass;
+    (variance.element_sum().sqrt(), variance.powf(0.5))
+}
+
+// "温度": 质心系中每个粒子的平均动能 sum m |v - V|^2 / (2 N)
+pub fn temperature(bodies: &[Body]) -> f64 {
+    if bodies.is_empty() {
+        return 0.0;
+    }
+    let mean = center_of_mass(bodies).1;
+    let kinetic: f64 = bodies.iter().map(|b| 0.5 * to_f64(b.mass) * to_f64((b.velocity - mean).length_squared())).sum();
+    kinetic / bodies.len() as f64
+}
+
+// 返回 (质心位置, 质心速度), 总质量为零时取简单平均
+pub fn center_of_mass(bodies: &[Body]) -> (Vec3, Vec3) {
+    if bodies.is_empty() {
+        return (Vec3::ZERO, Vec3::ZERO);
+    }
+    let total_mass: f64 = bodies.iter().map(|b| to_f64(b.mass)).sum();
+    let (position, velocity) = if total_mass > 0.0 {
+        bodies.iter().fold((DVec3::ZERO, DVec3::ZERO), |(x, v), b| {
+            (x + to_dvec3(b.position) * to_f64(b.mass), v + to_dvec3(b.velocity) * to_f64(b.mass))
+        })
+    } else {
+        bodies.iter().fold((DVec3::ZERO, DVec3::ZERO), |(x, v), b| (x + to_dvec3(b.position), v + to_dvec3(b.velocity)))
+    };
+    let weight = if total_mass > 0.0 { total_mass } else { bodies.len() as f64 };
+    (from_dvec3(position / weight), from_dvec3(velocity / weight))
+}
+
+// 把总角动量 (相对质心, 质心系中) 的方向转到 +z 的旋转; 角动量为零时为恒等变换
+pub fn angular_momentum_rotation(bodies: &[Body]) -> DQuat {
+    let (com_position, com_velocity) = center_of_mass(bodies);
+    let spin = bodies.iter().fold(DVec3::ZERO, |l, b| {
+        let (r, v) = (to_dvec3(b.position - com_position), to_dvec3(b.velocity - com_velocity));
+        l + r.cross(v) * to_f64(b.mass)
+    });
+    match spin.try_normalize() {
+        Some(axis) => DQuat::from_rotation_arc(axis, DVec3::Z),
+        None => DQuat::IDENTITY,
+    }
+}
+
+// 只供绘图的副本: 平移到质心系后旋转, 使总角动量沿 +z. 盘状系统的 x-y 投影即正视图 (face-on),
+// x-z 与 y-z 投影为侧视图 (edge-on); 原粒子不变
+pub fn face_on_view(bodies: &[Body]) -> Vec<Body> {
+    let rotation = angular_momentum_rotation(bodies);
+    let (com_position, com_velocity) = center_of_mass(bodies);
+    bodies
+        .iter()
+        .map(|b| Body {
+            position: from_dvec3(rotation * to_dvec3(b.position - com_position)),
+            velocity: from_dvec3(rotation * to_dvec3(b.velocity - com_velocity)),
+            ..b.clone()
+        })
+        .collect()
+}
+
+// 平移到质心系: 质心位于原点且总动量为零
+pub fn to_com_frame(bodies: &mut [Body]) {
+    let (com_position, com_velocity) = center_of_mass(bodies);
+    for body in bodies.iter_mut() {
+        body.position -= com_position;
+        body.velocity -= com_velocity;
+    }
+}
+
+// 删除与质心距离超过 radius 的粒子, 返回删除的个数. 其余粒子的编号与顺序不变
+pub fn remove_escapers(bodies: &mut Vec<Body>, radius: Real) -> usize {
+    let (center, _) = center_of_mass(bodies);
+    let radius_sq = radius * radius;
+    let before = bodies.len();
+    bodies.retain(|b| b.position.distance_squared(center) <= radius_sq);
+    before - bodies.len()
+}
+
+// 以质心为中心的球平均密度剖面: 把质量分到 n_bins 个对数等距的球壳中, 再除以球壳体积.
+// 最内层从最近的非零半径开始 (至少为 r_max / 1e4), 返回 (球壳的几何平均半径, 密度), 空球壳密度为 0
+pub fn radial_density_profile(bodies: &[Body], n_bins: usize, r_max: f64) -> Vec<(f64, f64)> {
+    if n_bins == 0 || r_max <= 0.0 {
+        return Vec::new();
+    }
+    let (center, _) = center_of_mass(bodies);
+    let radii: Vec<f64> = bodies.iter().map(|b| to_f64(b.position.distance(center))).collect();
+    let nearest = radii.iter().copied().filter(|&r| r > 0.0).fold(r_max, f64::min);
+    // 稍微向内扩展, 保证最近的粒子落在第一个球壳内
+    let r_min = nearest.max(r_max * 1e-4) * (1.0 - 1e-12);
+    let log_width = (r_max / r_min).ln() / n_bins as f64;
+
+    let mut mass = vec![0.0; n_bins];
+    for (body, &r) in bodies.iter().zip(&radii) {
+        if r < r_min || r > r_max {
+            continue;
+        }
+        let bin = (((r / r_min).ln() / log_width) as usize).min(n_bins - 1);
+        mass[bin] += to_f64(body.mass);
+    }
+
+    mass.iter()
+        .enumerate()
+        .map(|(bin, &m)| {
+            let inner = r_min * (log_width * bin as f64).exp();
+            let outer = r_min * (log_width * (bin + 1) as f64).exp();
+            let volume = 4.0 / 3.0 * std::f64::consts::PI * (outer.powi(3) - inner.powi(3));
+            ((inner * outer).sqrt(), m / volume)
+        })
+        .collect()
+}
+
+// 拉格朗日半径: 按到质心的距离排序后累加质量, 返回包含各质量分数 fractions 的最小半径
+pub fn lagrangian_radii(bodies: &[Body], fractions: &[f64]) -> Vec<f64> {
+    let (center, _) = center_of_mass(bodies);
+    let total_mass: f64 = bodies.iter().map(|b| to_f64(b.mass)).sum();
+    let mut shells: Vec<(f64, f64)> = bodies.iter().map(|b| (to_f64(b.position.distance(center)), to_f64(b.mass))).collect();
+    shells.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+    // 累积质量单调递增, 对每个分数二分查找第一个达到目标质量的粒子
+    let mut enclosed = Vec::with_capacity(shells.len());
+    let mut sum = 0.0;
+    for &(_, mass) in &shells {
+        sum += mass;
+        enclosed.push(sum);
+    }
+    fractions
+        .iter()
+        .map(|&fraction| {
+            let index = enclosed.partition_point(|&m| m < fraction * total_mass);
+            shells.get(index).or(shells.last()).map_or(0.0, |s| s.0)
+        })
+        .collect()
+}
+
+// 穿越时间 t_cross = r_h / sigma: 半质量半径 r_h 除以三维速度弥散 sigma; 粒子静止时为无穷大
+pub fn crossing_time(bodies: &[Body]) -> f64 {
+    let half_mass_radius = lagrangian_radii(bodies, &[0.5])[0];
+    let (sigma, _) = velocity_dispersion(bodies);
+    if sigma > 0.0 { half_mass_radius / sigma } else { f64::INFINITY }
+}
+
+// 两体弛豫时间 t_relax = N / (8 ln N) t_cross (Binney & Tremaine 2008, 式 1.38), 即粒子的速度因累积的两体
+// 偏转而改变自身量级所需的时间; 与 Spitzer 的半质量弛豫时间 0.138 N / ln(0.4 N) sqrt(r_h^3 / (G M)) 只差
+// 量级为一的因子. 假设系统大致处于位力平衡, 粒子质量相近且不计软化 (软化使 ln Lambda 变小, 真实弛豫更慢).
+// 运行时间远短于 t_relax 时系统是无碰撞的, 两体相遇可以忽略; 少于三个粒子或粒子静止时为无穷大
+pub fn relaxation_time(bodies: &[Body]) -> f64 {
+    let n = bodies.len() as f64;
+    if bodies.len() < 3 {
+        return f64::INFINITY;
+    }
+    n / (8.0 * n.ln()) * crossing_time(bodies)
+}
+
+// 由平均粒子间距估计软化长度: 以到质心距离的中位数为半径的球内有一半粒子, 间距取 (V / (N / 2))^(1/3),
+// 返回其 fraction 倍. 只用中心一半的粒子, 因此不受少数远离的粒子影响; 少于两个粒子或粒子全部重合时返回 0
+pub fn auto_softening(bodies: &[Body], fraction: f64) -> f64 {
+    if bodies.len() < 2 {
+        return 0.0;
+    }
+    let (center, _) = center_of_mass(bodies);
+    let mut radii: Vec<f64> = bodies.iter().map(|b| to_f64(b.position.distance(center))).collect();
+    let half = radii.len() / 2;
+    let (_, &mut median, _) = radii.select_nth_unstable_by(half, f64::total_cmp);
+    let volume = 4.0 / 3.0 * std::f64::consts::PI * median.powi(3);
+    fraction * (volume / half as f64).cbrt()
+}
+
+// 出现过的粒子种类, 从小到大排列
+pub fn species_present(bodies: &[Body]) -> Vec<u8> {
+    let mut species: Vec<u8> = bodies.iter().map(|b| b.species).collect();
+    species.sort_unstable();
+    species.dedup();
+    species
+}
+
+// 属于 species 的粒子 (的副本), 供分种类的诊断量使用
+pub fn species_subset(bodies: &[Body], species: u8) -> Vec<Body> {
+    bodies.iter().filter(|b| b.species == species).cloned().collect()
+}
+
+// 单一种类的 (动能, 势能, 总能量): 只计入种类内部的粒子对与外部势, 不含与其他种类的相互作用能,
+// 因此各种类之和不等于 total_energy
+pub fn species_energy(bodies: &[Body], params: &ForceParams, species: u8) -> (f64, f64, f64) {
+    total_energy(&species_subset(bodies, species), params)
+}
+
+// 单一种类的拉格朗日半径, 以该种类自身的质心为中心
+pub fn species_lagrangian_radii(bodies: &[Body], fractions: &[f64], species: u8) -> Vec<f64> {
+    lagrangian_radii(&species_subset(bodies, species), fractions)
+}
+
+// 检查所有粒子的位置和速度是否有限, 返回第一个出问题的粒子
+pub fn check_finite(bodies: &[Body], step: usize) -> Result<(), SimError> {
+    match bodies.iter().position(|b| !b.position.is_finite() || !b.velocity.is_finite()) {
+        Some(body) => Err(SimError::NonFiniteState { step, body }),
+        None => Ok(()),
+    }
+}
+
+// 总质量的比较允许的相对舍入误差 (求和顺序随粒子删除而改变)
+const MASS_TOLERANCE: f64 = 1e-10;
+
+// 检查上次检查以来粒子数与总质量的变化是否与启用的功能一致: 删除逃逸粒子时两者都只能减少,
+// 合并碰撞保持总质量而减少粒子数, 都未启用时两者不变. previous 与返回值为 (粒子数, 总质量)
+pub fn check_mass_conservation(
+    bodies: &[Body],
+    previous: (usize, f64),
+    step: usize,
+    escapers: bool,
+    merging: bool,
+) -> Result<(usize, f64), SimError> {
+    let (before_count, before_mass) = previous;
+    let (after_count, after_mass) = (bodies.len(), bodies.iter().map(|b| to_f64(b.mass)).sum::<f64>());
+    let tolerance = MASS_TOLERANCE * before_mass.abs();
+    let (count_ok, mass_ok, expected) = match (escapers, merging) {
+        (false, false) => {
+            (after_count == before_count, (after_mass - before_mass).abs() <= tolerance, "both to stay constant")
+        }
+        (false, true) => (
+            after_count <= before_count,
+            (after_mass - before_mass).abs() <= tolerance,
+            "constant mass and a non-increasing count (merging)",
+        ),
+        (true, _) => (
+            after_count <= before_count,
+            after_mass <= before_mass + tolerance,
+            "a non-increasing mass and count (escaper removal)",
+        ),
+    };
+    if count_ok && mass_ok {
+        Ok((after_count, after_mass))
+    } else {
+        Err(SimError::MassNotConserved { step, before_count, after_count, before_mass, after_mass, expected })
+    }
+}
+
+// 相对质心的约化 (无迹) 质四极矩 Q_ij = sum m (x_i x_j - delta_ij r^2 / 3).
+// 取质心系坐标, 质心匀速运动时不会给二阶导数带来虚假的常数项
+pub fn quadrupole_moment(bodies: &[Body]) -> [[f64; 3]; 3] {
+    let (center, _) = center_of_mass(bodies);
+    let mut q = [[0.0; 3]; 3];
+    for body in bodies {
+        let x = to_dvec3(body.position - center).to_array();
+        let (m, r_sq) = (to_f64(body.mass), x.iter().map(|c| c * c).sum::<f64>());
+        for i in 0..3 {
+            for j in 0..3 {
+                let trace = if i == j { r_sq / 3.0 } else { 0.0 };
+                q[i][j] += m * (x[i] * x[j] - trace);
+            }
+        }
+    }
+    q
+}
+
+// 四极近似下的引力波应变 h+ 与 hx: 由最近三次采样的 Q_ij 用非等距三点差分求二阶导数,
+// 再投影到垂直于视线方向的平面上, h+ = G / (c^4 D) (Q''_pp - Q''_qq), hx = 2 G / (c^4 D) Q''_pq.
+// 结果对应中间一次采样的时刻, 因此比最新的状态滞后一个采样间隔
+pub struct QuadrupoleStrain {
+    // 视线方向的偏振基 p, q; 视线沿 +z 时即 x, y
+    basis: (DVec3, DVec3),
+    distance: f64,
+    // G / c^4, 单位制决定
+    coupling: f64,
+    samples: Vec<(usize, f64, [[f64; 3]; 3])>,
+}
+
+impl QuadrupoleStrain {
+    // line_of_sight 不必归一化, 但不能为零向量; speed_of_light 为所用单位制中的光速
+    pub fn new(line_of_sight: DVec3, distance: f64, units: Units, speed_of_light: f64) -> Self {
+        let n = line_of_sight.normalize();
+        let up = if n.z.abs() > 0.999 { DVec3::Y } else { DVec3::Z };
+        let p = up.cross(n).normalize();
+        let coupling = to_f64(units.gravitational_constant()) / speed_of_light.powi(4);
+        Self { basis: (p, n.cross(p)), distance, coupling, samples: Vec::with_capacity(3) }
+    }
+
+    // 加入一次采样; 攒够三次后返回中间采样的 (步数, 时间, h+, hx)
+    pub fn sample(&mut self, bodies: &[Body], step: usize, time: f64) -> Option<(usize, f64, f64, f64)> {
+        if self.samples.len() == 3 {
+            self.samples.remove(0);
+        }
+        self.samples.push((step, time, quadrupole_moment(bodies)));
+        let [(_, t0, q0), (step1, t1, q1), (_, t2, q2)] = self.samples.as_slice() else {
+            return None;
+        };
+        let (h1, h2) = (t1 - t0, t2 - t1);
+        let mut second = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                second[i][j] = 2.0 * ((q2[i][j] - q1[i][j]) / h2 - (q1[i][j] - q0[i][j]) / h1) / (h1 + h2);
+            }
+        }
+        let project = |a: DVec3, b: DVec3| {
+            let (a, b) = (a.to_array(), b.to_array());
+            (0..3).flat_map(|i| (0..3).map(move |j| (i, j))).map(|(i, j)| a[i] * second[i][j] * b[j]).sum::<f64>()
+        };
+        let (p, q) = self.basis;
+        let scale = self.coupling / self.distance;
+        Some((*step1, *t1, scale * (project(p, p) - project(q, q)), 2.0 * scale * project(p, q)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::test_params;
+    use crate::generators::{gaussian, generate_cold_sphere, generate_disk, generate_kepler};
+    use crate::integrators::{integrate_step, update_forces, Integrator};
+    use glam::DQuat;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn two_body_orbit_conserves_momentum_and_angular_momentum() {
+        let params = test_params(1.0, 0.0);
+        let mut bodies = generate_kepler(0.7, 0.3, 1.0, 0.6, 1.0);
+        let (p0, l0) = momentum_diagnostics(&bodies);
+        // 动量的尺度: 单个粒子的 m v
+        let scale = to_f64(bodies[0].mass * bodies[0].velocity.length());
+        assert!(p0.length() < 1e-14 * scale, "|P0| = {:e}", p0.length());
+
+        update_forces(&mut bodies, Integrator::Leapfrog, &params);
+        for step in 0..5000 {
+            integrate_step(&mut bodies, Integrator::Leapfrog, 1e-3, &params);
+            if step % 500 == 0 {
+                let (p, l) = momentum_diagnostics(&bodies);
+                assert!(p.length() < 1e-12 * scale, "step {}: |P| = {:e}", step, p.length());
+                assert!(l.distance(l0) < 1e-12 * l0.length(), "step {}: L = {:?}, L0 = {:?}", step, l, l0);
+            }
+        }
+    }
+
+    #[test]
+    fn com_frame_has_zero_momentum_and_origin_center() {
+        let mut bodies: Vec<Body> = (0..10)
+            .map(|i| {
+                let x = i as Real;
+                Body::new(1.0 + x, Vec3::new(5.0 + x, -3.0 * x, 2.0), Vec3::new(1.0, x, -0.5 * x))
+            })
+            .collect();
+        to_com_frame(&mut bodies);
+        let (position, velocity) = center_of_mass(&bodies);
+        let (momentum, _) = momentum_diagnostics(&bodies);
+        assert!(position.length() < 1e-12, "COM at {:?}", position);
+        assert!(velocity.length() < 1e-12, "COM velocity {:?}", velocity);
+        assert!(momentum.length() < 1e-12, "P = {:?}", momentum);
+    }
+
+    #[test]
+    fn uniform_sphere_lagrangian_radii_follow_cube_root_scaling() {
+        // 均匀球内 M(<r) / M = (r / R)^3, 即 r_f = R f^(1/3)
+        let bodies = generate_cold_sphere(20000, 1.0, 2.0, 11);
+        let fractions = [0.1, 0.5, 0.9];
+        for (&fraction, radius) in fractions.iter().zip(lagrangian_radii(&bodies, &fractions)) {
+            let expected = 2.0 * fraction.cbrt();
+            assert!((radius / expected - 1.0).abs() < 0.02, "f = {}: r = {}, expected {}", fraction, radius, expected);
+        }
+    }
+
+    #[test]
+    fn maxwellian_sample_recovers_its_dispersion() {
+        // 各方向弥散为 (1, 2, 3) 的高斯速度分布, 叠加一个整体速度
+        let sigma = Vec3::new(1.0, 2.0, 3.0);
+        let mut rng = StdRng::seed_from_u64(17);
+        let bodies: Vec<Body> = (0..20000)
+            .map(|_| {
+                let velocity = Vec3::new(gaussian(&mut rng), gaussian(&mut rng), gaussian(&mut rng)) * sigma;
+                Body::new(1.0, Vec3::ZERO, velocity + Vec3::new(10.0, 0.0, -5.0))
+            })
+            .collect();
+        let (total, per_axis) = velocity_dispersion(&bodies);
+        assert!((total / 14.0_f64.sqrt() - 1.0).abs() < 0.02, "sigma = {}", total);
+        for (measured, expected) in per_axis.to_array().into_iter().zip(to_dvec3(sigma).to_array()) {
+            assert!((measured / expected - 1.0).abs() < 0.02, "{:?} vs {:?}", per_axis, sigma);
+        }
+    }
+
+    #[test]
+    fn face_on_view_untilts_a_disk() {
+        // 标高 0.05 的盘绕 x 轴倾斜 40 度
+        let rotation = DQuat::from_rotation_x(40f64.to_radians());
+        let tilted: Vec<Body> = generate_disk(2000, 1.0, 1.0, 0.05, 8, 1.0)
+            .into_iter()
+            .map(|b| Body {
+                position: from_dvec3(rotation * to_dvec3(b.position)),
+                velocity: from_dvec3(rotation * to_dvec3(b.velocity)),
+                ..b
+            })
+            .collect();
+        let rms_z = |bodies: &[Body]| (bodies.iter().map(|b| to_f64(b.position.z).powi(2)).sum::<f64>() / bodies.len() as f64).sqrt();
+
+        let view = face_on_view(&tilted);
+        let (_, angular_momentum) = momentum_diagnostics(&view);
+        assert!(angular_momentum.z / angular_momentum.length() > 1.0 - 1e-12, "L = {:?}", angular_momentum);
+        assert!(rms_z(&view) < 0.1, "face-on rms z = {}", rms_z(&view));
+        assert!(rms_z(&tilted) > 5.0 * rms_z(&view), "tilted rms z = {}", rms_z(&tilted));
+    }
+
+    #[test]
+    fn kahan_sum_keeps_the_digits_a_naive_sum_drops() {
+        // 每一项都小于 1 的半个 ulp, 逐项直接相加时全部被舍去
+        let values: Vec<f64> = std::iter::once(1.0).chain(std::iter::repeat_n(1e-16, 1_000_000)).collect();
+        let naive = values.iter().fold(0.0, |sum, v| sum + v);
+        assert_eq!(naive, 1.0);
+        let compensated = kahan_sum(values);
+        assert!((compensated - (1.0 + 1e-10)).abs() < 1e-15, "{}", compensated);
+    }
+
+    #[test]
+    fn energy_and_momentum_are_identical_across_thread_counts() {
+        // 用对称求和推进几步后计算诊断量; 受力与诊断量的求和顺序都与线程数无关
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let mut bodies = crate::generators::generate_plummer(500, 1.0, 1.0, 9, 1.0);
+                let params = ForceParams { method: crate::ForceMethod::Pairwise, ..test_params(1.0, 0.01) };
+                update_forces(&mut bodies, Integrator::Leapfrog, &params);
+                for _ in 0..10 {
+                    integrate_step(&mut bodies, Integrator::Leapfrog, 1e-3, &params);
+                }
+                let positions: Vec<Vec3> = bodies.iter().map(|b| b.position).collect();
+                (positions, total_energy(&bodies, &params), momentum_diagnostics(&bodies))
+            })
+        };
+        let (one, four) = (run(1), run(4));
+        assert!(one.0 == four.0, "positions differ between 1 and 4 threads");
+        assert_eq!(one.1, four.1);
+        assert_eq!(one.2, four.2);
+    }
+}
@@ -0,0 +1,766 @@
+// 命令行程序的驱动: 按配置执行子命令, 一致性检验, 或完整的模拟 (准备, 主循环, 收尾) 并写出各种输出
+use crate::budget::{check_output_budget, is_frame_step, report_dry_run};
+use crate::commands::run_command;
+use crate::config::{Config, SnapshotFormat};
+use crate::diagnostics::QuadrupoleStrain;
+use crate::integrators::reversibility_error;
+use crate::output::{write_frame, write_snapshot, TrajectoryWriter};
+use crate::plot::{GifAnimation, PlotOptions, Trails};
+use crate::precision::to_f64;
+use crate::profile::{Phase, Profiler};
+use crate::{checkpoint, diagnostics, input, kdtree, output, plot};
+use crate::{Body, CollisionMode, ForceParams, Real, SimError, Simulation, StepReport};
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, error, info, warn};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+// 按配置运行: 有子命令时执行它; 否则读入初始条件 (或检查点), 执行所选的检验, 或完整地运行模拟
+pub fn simulate(mut config: Config) -> Result<(), SimError> {
+    if let Some(command) = &config.command {
+        return run_command(command, &config);
+    }
+    check_run_options(&config)?;
+    let (bodies, start_step, start_time) = load_initial_state(&mut config)?;
+
+    // --fixed-bounds 的坐标范围同样取自绘图所用的坐标系
+    let plot_options = if config.face_on {
+        config.plot_options(&diagnostics::face_on_view(&bodies))?
+    } else {
+        config.plot_options(&bodies)?
+    };
+
+    // 检查配置组合并计算初始受力; 主循环在两步之间写出各种输出, 因此逐步调用 step 而不是 run
+    let mut sim = Simulation::new(bodies, config)?;
+    sim.time = start_time;
+    sim.step_count = start_step;
+    if run_checks(&sim)? {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&sim.config.output_dir)?;
+    check_output_budget(&sim)?;
+    let mut run = Run::start(&sim, plot_options)?;
+    let interrupted = run.main_loop(&mut sim)?;
+    run.finish(&sim, interrupted)
+}
+
+// 只与输出有关, Simulation::new 不检查的参数组合
+fn check_run_options(config: &Config) -> Result<(), SimError> {
+    let invalid = |message: String| Err(SimError::Config(message));
+    // VTK 与 .npy 的读取程序不认识 gzip 压缩
+    if config.gzip_snapshots && matches!(config.snapshot_format, Some(SnapshotFormat::Vtk | SnapshotFormat::Npy)) {
+        return invalid("--gzip-snapshots only applies to CSV and TIPSY snapshots".to_string());
+    }
+    if let Some(length) = config.fof_linking_length {
+        if length <= 0.0 || !length.is_finite() {
+            return invalid(format!("fof_linking_length must be positive, got {:e}", length));
+        }
+    }
+    if config.merger_log.is_some() && config.collisions != Some(CollisionMode::Merge) {
+        return invalid("--merger-log needs --collisions merge".to_string());
+    }
+    if let Some(interval) = config.output_time_interval {
+        if interval <= 0.0 || !interval.is_finite() {
+            return invalid(format!("output_time_interval must be positive, got {:e}", interval));
+        }
+    }
+    Ok(())
+}
+
+// 读取初始条件, 或从检查点续算, 返回 (粒子, 起始步数, 起始时间).
+// 续算沿用检查点中的随机种子 (写回 config.seed), 使绘图的子样本与不中断的运行一致
+fn load_initial_state(config: &mut Config) -> Result<(Vec<Body>, usize, f64), SimError> {
+    let units = config.units;
+    let (mut bodies, start_step, start_time, seed) = match &config.resume {
+        Some(path) => {
+            info!("Resuming from checkpoint '{}'...", path.display());
+            let checkpoint::Checkpoint { bodies, step, time, units: saved_units, seed: saved_seed } = checkpoint::load_checkpoint(path)?;
+            if saved_units != units {
+                return Err(SimError::Config(format!(
+                    "The checkpoint was written in {} units, not {}",
+                    saved_units.label(),
+                    units.label()
+                )));
+            }
+            let seed = saved_seed.unwrap_or(config.seed);
+            if seed != config.seed {
+                warn!("The checkpoint was written with --seed {}; continuing with it instead of {}.", seed, config.seed);
+            }
+            info!("Loaded {} bodies at step {} (t = {:e} {}).", bodies.len(), step, time, units.time());
+            (bodies, step, time, seed)
+        }
+        None => {
+            info!("Reading initial conditions from '{}'...", config.input_file.display());
+            let bodies = input::load_bodies(&config.input_file)?;
+            info!("Successfully loaded {} bodies.", bodies.len());
+            (bodies, 0, 0.0, config.seed)
+        }
+    };
+    config.seed = seed;
+
+    // 在开始计算前报告不合法的粒子, 而不是等到出现 NaN
+    input::validate_bodies(&bodies)?;
+    for (first, second) in input::duplicate_positions(&bodies) {
+        warn!("Bodies {} and {} share the same position; their mutual force is skipped.", first, second);
+    }
+
+    // 检查点本身已是续算前的状态, 不再重复平移
+    if config.com_frame && config.resume.is_none() {
+        diagnostics::to_com_frame(&mut bodies);
+        info!("Shifted initial conditions to the center-of-mass frame.");
+    }
+    let (com_position, com_velocity) = diagnostics::center_of_mass(&bodies);
+    info!("Units: {}, G = {:e}", units.label(), units.gravitational_constant());
+    info!(
+        "Center of mass: |R| = {:.6e} {}, |V| = {:.6e} {}",
+        com_position.length(),
+        units.length(),
+        com_velocity.length(),
+        units.speed()
+    );
+    Ok((bodies, start_step, start_time))
+}
+
+// 代替正式运行的检验与试运行, 都不写逐帧输出; 执行了其中之一时返回 true
+fn run_checks(sim: &Simulation) -> Result<bool, SimError> {
+    let (config, force_params) = (&sim.config, sim.force_params());
+    let units = config.units;
+    let fixed_dt = |option: &str| {
+        if config.adaptive_dt || config.block_timesteps {
+            Err(SimError::Config(format!("{} uses a fixed --dt; drop --adaptive-dt and --block-timesteps", option)))
+        } else {
+            Ok(())
+        }
+    };
+
+    // 可逆性检验只用固定步长的单一积分器
+    if config.reversibility_test {
+        fixed_dt("--reversibility-test")?;
+        let (steps, dt) = (config.time_steps, config.dt);
+        info!("Reversibility test: {} steps forward and back with dt = {:e} {}...", steps, dt, units.time());
+        let (position_error, velocity_error) = reversibility_error(&sim.bodies, config.integrator, dt as Real, steps, force_params);
+        let (center, _) = diagnostics::center_of_mass(&sim.bodies);
+        let size = sim.bodies.iter().map(|b| to_f64(b.position.distance(center))).fold(0.0, f64::max);
+        let speed = sim.bodies.iter().map(|b| to_f64(b.velocity.length())).fold(0.0, f64::max);
+        let (length, speed_unit) = (units.length(), units.speed());
+        info!("Max position error: {:.6e} {} ({:.3e} of the system radius)", position_error, length, position_error / size);
+        info!("Max velocity error: {:.6e} {} ({:.3e} of the fastest speed)", velocity_error, speed_unit, velocity_error / speed);
+        return Ok(true);
+    }
+
+    if config.compare_integrators {
+        fixed_dt("--compare-integrators")?;
+        compare_integrators(&sim.bodies, config)?;
+        return Ok(true);
+    }
+
+    // 受力与势能的一致性检验; 误差以 f64 的中心差分为准, 单精度构建的截断与舍入误差更大
+    if config.verify_gradient {
+        const SAMPLES: usize = 32;
+        let tolerance = if cfg!(feature = "f32") { 1e-2 } else { 1e-5 };
+        let error = diagnostics::gradient_error(&sim.bodies, force_params, SAMPLES);
+        info!("Force/potential consistency: max relative error {:.3e} over up to {} bodies (tolerance {:.0e})", error, SAMPLES, tolerance);
+        if error > tolerance {
+            return Err(SimError::Config(format!("accelerations disagree with the potential gradient by {:.3e}", error)));
+        }
+        return Ok(true);
+    }
+
+    // 试运行: 计时一个真实的积分步, 报告预计的耗时, 内存与输出文件数后退出, 不创建任何文件
+    if config.dry_run {
+        report_dry_run(sim)?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+// 积分器对比: --compare-list 中的每个积分器从同一初始条件出发, 以相同的 dt 积分 --compare-steps 步,
+// 记录约 200 个相对能量误差采样点 (给出 --energy-plot-interval 时按它采样), 画在同一张图中.
+// 当前配置不支持 (如 Hermite 与外部势) 或中途出错的积分器给出警告后跳过
+pub fn compare_integrators(bodies: &[Body], config: &Config) -> Result<(), SimError> {
+    let steps = config.compare_steps;
+    let interval = if config.energy_plot_interval > 0 { config.energy_plot_interval } else { (steps / 200).max(1) };
+    let mut curves = Vec::new();
+    'integrators: for &integrator in &config.compare_list {
+        let name = integrator.to_possible_value().map_or_else(|| format!("{:?}", integrator), |v| v.get_name().to_string());
+        let mut sim = match Simulation::new(bodies.to_vec(), Config { integrator, time_steps: steps, ..config.clone() }) {
+            Ok(sim) => sim,
+            Err(e) => {
+                warn!("Skipping {}: {}", name, e);
+                continue;
+            }
+        };
+        let params = *sim.force_params();
+        let initial = diagnostics::total_energy(&sim.bodies, &params).2;
+        let mut samples = vec![(0.0, 0.0)];
+        let started = Instant::now();
+        while !sim.is_finished() {
+            if let Err(e) = sim.step() {
+                warn!("Skipping {}: failed at step {}: {}", name, sim.step_count, e);
+                continue 'integrators;
+            }
+            if sim.step_count % interval == 0 || sim.is_finished() {
+                let total = diagnostics::total_energy(&sim.bodies, &params).2;
+                samples.push((sim.time, (total - initial) / initial.abs()));
+            }
+        }
+        let final_error = samples.last().map_or(0.0, |s| s.1);
+        let max_error = samples.iter().map(|s| s.1.abs()).fold(0.0, f64::max);
+        info!(
+            "{:<10} final dE/|E0| = {:+.3e}, max |dE/E0| = {:.3e}, {:.3} s of wall time",
+            name,
+            final_error,
+            max_error,
+            started.elapsed().as_secs_f64()
+        );
+        curves.push((name, samples));
+    }
+
+    if curves.is_empty() {
+        return Err(SimError::Config(
+            "--compare-integrators: none of the --compare-list integrators could run with this configuration".to_string(),
+        ));
+    }
+    std::fs::create_dir_all(&config.output_dir)?;
+    let path = config.output_dir.join("integrator_comparison.png");
+    plot::plot_integrator_comparison(&curves, &path, config.units)?;
+    info!("Wrote the integrator comparison to '{}'.", path.display());
+    Ok(())
+}
+
+// 整个运行只写一个的 CSV 与轨迹文件. 续算时追加到已有的文件, 检查点所在步已在上一次运行中写过;
+// 文件不存在时新建, 首行为检查点所在步
+struct Writers {
+    diagnostics: Option<BufWriter<File>>,
+    dispersion: Option<BufWriter<File>>,
+    lagrangian: Option<BufWriter<File>>,
+    strain: Option<BufWriter<File>>,
+    trajectory: Option<TrajectoryWriter>,
+    mergers: Option<BufWriter<File>>,
+}
+
+// 续算且文件已存在时以追加方式打开, 否则新建并用 start 写入表头与首行
+fn open_csv(
+    path: Option<&PathBuf>,
+    resuming: bool,
+    start: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> std::io::Result<Option<BufWriter<File>>> {
+    match path {
+        Some(path) if resuming && path.exists() => Ok(Some(BufWriter::new(OpenOptions::new().append(true).open(path)?))),
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            start(&mut writer)?;
+            Ok(Some(writer))
+        }
+        None => Ok(None),
+    }
+}
+
+impl Writers {
+    fn open(sim: &Simulation, energy: (f64, f64, f64)) -> Result<Self, SimError> {
+        let (config, bodies) = (&sim.config, &sim.bodies);
+        let (start_step, start_time, resuming) = (sim.step_count, sim.time, config.resume.is_some());
+        let diagnostics = open_csv(config.diagnostics_csv.as_ref(), resuming, |writer| {
+            writeln!(writer, "step,time,kinetic,potential,total,px,py,pz,lx,ly,lz")?;
+            output::write_diagnostics_row(writer, start_step, start_time, energy, diagnostics::momentum_diagnostics(bodies))
+        })?;
+        let dispersion = open_csv(config.dispersion_csv.as_ref(), resuming, |writer| {
+            writeln!(writer, "step,time,temperature,sigma,sigma_x,sigma_y,sigma_z")?;
+            let (temperature, dispersion) = (diagnostics::temperature(bodies), diagnostics::velocity_dispersion(bodies));
+            output::write_dispersion_row(writer, start_step, start_time, temperature, dispersion)
+        })?;
+        let fractions = &config.lagrangian_fractions;
+        let lagrangian = open_csv(config.lagrangian_csv.as_ref(), resuming, |writer| {
+            output::write_lagrangian_header(writer, fractions)?;
+            output::write_lagrangian_row(writer, start_step, start_time, &diagnostics::lagrangian_radii(bodies, fractions))
+        })?;
+        // 应变需要三次采样才能求二阶导数, 首行在采样足够后才写出
+        let strain = open_csv(config.gw_csv.as_ref(), resuming, |writer| writeln!(writer, "step,time,h_plus,h_cross"))?;
+        let trajectory = match &config.trajectory {
+            Some(path) if resuming && path.exists() => Some(TrajectoryWriter::append(path)?),
+            Some(path) => {
+                let mut writer = TrajectoryWriter::create(path)?;
+                writer.write_step(bodies, start_step, start_time)?;
+                Some(writer)
+            }
+            None => None,
+        };
+        let mergers = open_csv(config.merger_log.as_ref(), resuming, |_| Ok(()))?;
+        Ok(Self { diagnostics, dispersion, lagrangian, strain, trajectory, mergers })
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        for mut writer in [self.diagnostics, self.lagrangian, self.dispersion, self.strain, self.mergers].into_iter().flatten() {
+            writer.flush()?;
+        }
+        if let Some(writer) = self.trajectory {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+// 一次完整运行中跨步保留的状态: 输出文件, 能量参考值, 绘图用的尾迹与动画, 进度条与计时
+struct Run {
+    force_params: ForceParams,
+    plot_options: PlotOptions,
+    checkpoint_path: PathBuf,
+    writers: Writers,
+    strain: Option<QuadrupoleStrain>,
+    // 合并与逃逸都改变粒子数, 能量参考值随之重置, 使 dE 只反映积分误差
+    initial_energy: f64,
+    // 能量误差曲线的采样 (时间, 相对误差), 结束时绘图; 起点的误差按定义为零
+    energy_errors: Vec<(f64, f64)>,
+    trails: Option<Trails>,
+    animation: Option<GifAnimation>,
+    // 质量检查的参考值 (粒子数, 总质量), 每次检查后更新
+    mass_totals: (usize, f64),
+    // 按模拟时间输出时已写出的帧数与下一个要越过的 interval 倍数
+    frames: usize,
+    next_multiple: usize,
+    profiler: Profiler,
+    throughput: Throughput,
+    pb: ProgressBar,
+}
+
+impl Run {
+    // 报告初始状态, 打开各种输出, 新的运行按模拟时间输出时先写出初始帧
+    fn start(sim: &Simulation, plot_options: PlotOptions) -> Result<Self, SimError> {
+        let (config, bodies, force_params) = (&sim.config, &sim.bodies, *sim.force_params());
+        let (units, start_step, start_time, resuming) = (config.units, sim.step_count, sim.time, config.resume.is_some());
+
+        // 初始能量, 作为后续能量漂移的参考
+        let (kinetic, potential, initial_energy) = diagnostics::total_energy(bodies, &force_params);
+        let (momentum, angular_momentum) = diagnostics::momentum_diagnostics(bodies);
+        info!(
+            "Initial energy: E_kin = {:.6e} {u}, E_pot = {:.6e} {u}, E_tot = {:.6e} {u}",
+            kinetic,
+            potential,
+            initial_energy,
+            u = units.energy()
+        );
+        info!(
+            "Initial momentum: |P| = {:.6e} {}, |L| = {:.6e} {}",
+            momentum.length(),
+            units.momentum(),
+            angular_momentum.length(),
+            units.angular_momentum()
+        );
+        // 少体系统或静止的初始条件没有有意义的弛豫时间
+        let relaxation = diagnostics::relaxation_time(bodies);
+        if relaxation.is_finite() {
+            info!(
+                "Two-body relaxation time: {:.6e} {u} (crossing time {:.6e} {u}); the run spans {:.3e} relaxation times",
+                relaxation,
+                diagnostics::crossing_time(bodies),
+                (sim.t_end() - start_time) / relaxation,
+                u = units.time()
+            );
+        }
+
+        let writers = Writers::open(sim, (kinetic, potential, initial_energy))?;
+        // 续算时从检查点所在步重新开始采样应变
+        let mut strain = config.quadrupole_strain()?;
+        if let Some(strain) = strain.as_mut() {
+            strain.sample(bodies, start_step, start_time);
+        }
+        let energy_errors = if config.energy_plot_interval > 0 { vec![(start_time, 0.0)] } else { Vec::new() };
+
+        // 轨迹尾迹只在少体系统中记录, 从初始位置开始
+        let mut trails = match config.trail_length {
+            0 => None,
+            _ if config.face_on => return Err(SimError::Config("--trail-length is drawn in simulation coordinates; drop --face-on".to_string())),
+            _ if bodies.len() > plot::MAX_TRAIL_BODIES => {
+                warn!("{} bodies exceed the trail limit of {}; trails disabled.", bodies.len(), plot::MAX_TRAIL_BODIES);
+                None
+            }
+            length => Some(Trails::new(length)),
+        };
+        if let Some(trails) = trails.as_mut() {
+            trails.record(bodies);
+        }
+        let animation = match &config.animate {
+            Some(path) => Some(GifAnimation::create(path, ('x', 'y'), config.frame_delay, plot_options.clone())?),
+            None => None,
+        };
+
+        // indicatif 在 stderr 不是终端时不绘制, 重定向到文件的日志里不会混入控制字符;
+        // 自适应步长时进度条按 t_end 的千分比显示
+        let length = if config.adaptive_dt { 1000 } else { config.time_steps as u64 };
+        let pb = if config.progress_bar { ProgressBar::new(length) } else { ProgressBar::hidden() };
+        let template = if config.adaptive_dt {
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {percent}% of t_end ({eta}) {msg}"
+        } else {
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}"
+        };
+        let style = ProgressStyle::default_bar().template(template).map_err(|e| SimError::Config(e.to_string()))?;
+        pb.set_style(style.progress_chars("##-"));
+
+        let checkpoint_path = config.checkpoint_file.clone().unwrap_or_else(|| config.output_dir.join("checkpoint.json"));
+        let mass_totals = (bodies.len(), bodies.iter().map(|b| to_f64(b.mass)).sum::<f64>());
+        let mut run = Self {
+            force_params,
+            plot_options,
+            checkpoint_path,
+            writers,
+            strain,
+            initial_energy,
+            energy_errors,
+            trails,
+            animation,
+            mass_totals,
+            frames: 0,
+            next_multiple: 0,
+            profiler: Profiler::new(config.profile),
+            throughput: Throughput::default(),
+            pb,
+        };
+
+        // 按模拟时间输出: 新的运行先写出初始状态; 续算时从检查点时间之后的下一个倍数开始
+        if let Some(interval) = config.output_time_interval {
+            run.next_multiple = elapsed_multiples(start_time, interval) + 1;
+            run.frames = run.next_multiple;
+            if !resuming {
+                write_frame(bodies, 0, 0, start_time, config, &run.plot_options, run.animation.as_ref(), run.trails.as_ref())?;
+            }
+        }
+        Ok(run)
+    }
+
+    // 进度条的位置: 步数, 自适应步长时为 t_end 的千分比
+    fn progress(sim: &Simulation) -> u64 {
+        if sim.config.adaptive_dt {
+            (sim.time / sim.t_end() * 1000.0) as u64
+        } else {
+            sim.step_count as u64
+        }
+    }
+
+    // 推进到结束或被 Ctrl-C 中断, 每步之后写出各种输出; 被中断时返回 true
+    fn main_loop(&mut self, sim: &mut Simulation) -> Result<bool, SimError> {
+        install_interrupt_handler()?;
+        info!("Starting simulation...");
+        if let Some(a) = sim.scale_factor() {
+            info!("Comoving coordinates, scale factor a = {:.6e}", a);
+        }
+        self.pb.set_position(Self::progress(sim));
+
+        while !sim.is_finished() {
+            // 中断时当前步已经完整结束, 状态可直接写入检查点
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                return Ok(true);
+            }
+            let i = sim.step_count;
+            let (started, evaluations) = (Instant::now(), sim.force_evaluations);
+            let report = sim.step()?;
+            self.profiler.lap(Phase::Step);
+            if let Some(trails) = self.trails.as_mut() {
+                trails.record(&sim.bodies);
+            }
+            if !self.pb.is_hidden() {
+                // 每次受力计算的粒子与 N 个场源相互作用; 子步与块时间步也都计入
+                let seconds = started.elapsed().as_secs_f64().max(1e-9);
+                let interactions = ((sim.force_evaluations - evaluations) * sim.bodies.len()) as f64;
+                self.throughput.update(seconds, interactions);
+                self.pb.set_message(self.throughput.message());
+            }
+            self.after_step(sim, i, &report)?;
+            self.pb.set_position(Self::progress(sim));
+        }
+        Ok(false)
+    }
+
+    // 第 i 步完成之后: 报告事件, 写出逐步的输出与检查点, 每 plot_interval 步记录诊断量并写一帧
+    fn after_step(&mut self, sim: &Simulation, i: usize, report: &StepReport) -> Result<(), SimError> {
+        let (config, bodies) = (&sim.config, &sim.bodies);
+        let (step, time, finished, units) = (sim.step_count, sim.time, sim.is_finished(), config.units);
+        let pb = &self.pb;
+
+        if report.substeps > 1 {
+            pb.suspend(|| debug!("Step {}: close encounter, {} substeps", step, report.substeps));
+        }
+        if report.collisions > 0 {
+            pb.suspend(|| info!("Step {}: {} collisions, {} bodies remain", step, report.collisions, bodies.len()));
+        }
+        if report.escaped > 0 {
+            pb.suspend(|| info!("Step {}: removed {} escaping bodies, {} remain", step, report.escaped, bodies.len()));
+        }
+        if report.bodies_removed() {
+            self.initial_energy = diagnostics::total_energy(bodies, &self.force_params).2;
+        }
+
+        self.profiler.lap(Phase::Diagnostics);
+        if let Some(writer) = self.writers.trajectory.as_mut() {
+            if step % config.trajectory_interval == 0 {
+                writer.write_step(bodies, step, time)?;
+            }
+        }
+        if let Some(writer) = self.writers.mergers.as_mut() {
+            for merger in &sim.mergers {
+                output::write_merger_event(writer, step, time, merger)?;
+            }
+        }
+        self.profiler.lap(Phase::Output);
+
+        let fractions = &config.lagrangian_fractions;
+        if config.lagrangian_interval > 0 && step % config.lagrangian_interval == 0 {
+            let format_radii = |radii: &[f64]| -> Vec<String> {
+                fractions.iter().zip(radii).map(|(f, r)| format!("r_{} = {:.6e} {}", f, r, units.length())).collect()
+            };
+            let radii = diagnostics::lagrangian_radii(bodies, fractions);
+            pb.suspend(|| info!("Step {}: Lagrangian radii {}", step, format_radii(&radii).join(", ")));
+            if config.per_species {
+                for species in diagnostics::species_present(bodies) {
+                    let radii = diagnostics::species_lagrangian_radii(bodies, fractions, species);
+                    pb.suspend(|| info!("Step {}: species {} Lagrangian radii {}", step, species, format_radii(&radii).join(", ")));
+                }
+            }
+            if let Some(writer) = self.writers.lagrangian.as_mut() {
+                output::write_lagrangian_row(writer, step, time, &radii)?;
+            }
+        }
+
+        if config.energy_plot_interval > 0 && (step % config.energy_plot_interval == 0 || finished) {
+            let total = diagnostics::total_energy(bodies, &self.force_params).2;
+            self.energy_errors.push((time, (total - self.initial_energy) / self.initial_energy.abs()));
+        }
+
+        self.profiler.lap(Phase::Diagnostics);
+        if config.checkpoint_interval > 0 && (step % config.checkpoint_interval == 0 || finished) {
+            checkpoint::save_checkpoint(bodies, step, time, units, config.seed, &self.checkpoint_path)?;
+        }
+        self.profiler.lap(Phase::Output);
+
+        if is_frame_step(i, config.plot_interval) {
+            self.record_diagnostics(sim)?;
+        }
+
+        // 按模拟时间输出时, 越过 interval 的下一个整数倍 (或到达终点) 就写一帧, 帧号依次递增;
+        // 一步越过多个倍数时只写一帧
+        let frame = match config.output_time_interval {
+            Some(interval) if elapsed_multiples(time, interval) >= self.next_multiple || finished => {
+                self.next_multiple = elapsed_multiples(time, interval) + 1;
+                self.frames += 1;
+                Some((self.frames - 1, self.frames - 1))
+            }
+            Some(_) => None,
+            None => is_frame_step(i, config.plot_interval).then_some((i, i / config.plot_interval)),
+        };
+        self.profiler.lap(Phase::Diagnostics);
+        // 输出失败 (如磁盘写满) 时先尽量写检查点再停止, 腾出空间后可以 --resume
+        if let Some((label, turn)) = frame {
+            let written = write_frame(bodies, label, turn, time, config, &self.plot_options, self.animation.as_ref(), self.trails.as_ref());
+            if let Err(e) = written {
+                self.pb.abandon();
+                match checkpoint::save_checkpoint(bodies, step, time, units, config.seed, &self.checkpoint_path) {
+                    Ok(()) => error!("Wrote checkpoint '{}'; continue with --resume once there is space.", self.checkpoint_path.display()),
+                    Err(checkpoint_error) => error!("Could not write the checkpoint either: {}", checkpoint_error),
+                }
+                return Err(SimError::FrameWrite { label, step, source: Box::new(e) });
+            }
+        }
+        self.profiler.lap(Phase::Output);
+        Ok(())
+    }
+
+    // 每个绘图间隔的诊断量: 检查质量, 记录能量, 动量, 速度弥散与 FoF 群, 写入对应的 CSV
+    fn record_diagnostics(&mut self, sim: &Simulation) -> Result<(), SimError> {
+        let (config, bodies, force_params) = (&sim.config, &sim.bodies, &self.force_params);
+        let (step, time, units, energy_unit) = (sim.step_count, sim.time, config.units, config.units.energy());
+        let pb = &self.pb;
+
+        if config.check_mass {
+            let merging = config.collisions == Some(CollisionMode::Merge);
+            self.mass_totals =
+                diagnostics::check_mass_conservation(bodies, self.mass_totals, step, config.escape_radius.is_some(), merging)?;
+        }
+        let (kinetic, potential, total) = diagnostics::total_energy(bodies, force_params);
+        let (momentum, angular_momentum) = diagnostics::momentum_diagnostics(bodies);
+        pb.suspend(|| {
+            debug!(
+                "Step {}: E_kin = {:.6e} {u}, E_pot = {:.6e} {u}, E_tot = {:.6e} {u}, dE/|E0| = {:.3e}",
+                step,
+                kinetic,
+                potential,
+                total,
+                (total - self.initial_energy) / self.initial_energy.abs(),
+                u = energy_unit
+            );
+            debug!(
+                "Step {}: |P| = {:.6e} {}, |L| = {:.6e} {}",
+                step,
+                momentum.length(),
+                units.momentum(),
+                angular_momentum.length(),
+                units.angular_momentum()
+            );
+        });
+        let temperature = diagnostics::temperature(bodies);
+        let (sigma, sigma_axes) = diagnostics::velocity_dispersion(bodies);
+        pb.suspend(|| {
+            debug!(
+                "Step {}: T = {:.6e} {}, sigma = {:.6e} {u} (x {:.6e}, y {:.6e}, z {:.6e})",
+                step,
+                temperature,
+                energy_unit,
+                sigma,
+                sigma_axes.x,
+                sigma_axes.y,
+                sigma_axes.z,
+                u = units.speed()
+            )
+        });
+        if config.per_species {
+            for species in diagnostics::species_present(bodies) {
+                let (kinetic, potential, total) = diagnostics::species_energy(bodies, force_params, species);
+                pb.suspend(|| {
+                    debug!(
+                        "Step {}: species {} E_kin = {:.6e} {u}, E_pot = {:.6e} {u}, E_tot = {:.6e} {u}",
+                        step,
+                        species,
+                        kinetic,
+                        potential,
+                        total,
+                        u = energy_unit
+                    )
+                });
+            }
+        }
+        if let Some(linking_length) = config.fof_linking_length {
+            let groups = kdtree::find_groups(bodies, linking_length);
+            let halos: Vec<f64> = groups
+                .iter()
+                .filter(|group| group.len() >= config.fof_min_members)
+                .map(|group| group.iter().map(|&j| to_f64(bodies[j].mass)).sum())
+                .collect();
+            let largest = halos.iter().copied().fold(0.0, f64::max);
+            pb.suspend(|| {
+                info!(
+                    "Step {}: {} FoF groups with >= {} members, largest {:.6e} {}",
+                    step,
+                    halos.len(),
+                    config.fof_min_members,
+                    largest,
+                    units.mass()
+                )
+            });
+        }
+        if let Some(writer) = self.writers.diagnostics.as_mut() {
+            output::write_diagnostics_row(writer, step, time, (kinetic, potential, total), (momentum, angular_momentum))?;
+        }
+        if let Some(writer) = self.writers.dispersion.as_mut() {
+            output::write_dispersion_row(writer, step, time, temperature, (sigma, sigma_axes))?;
+        }
+        if let (Some(strain), Some(writer)) = (self.strain.as_mut(), self.writers.strain.as_mut()) {
+            if let Some((sample_step, sample_time, h_plus, h_cross)) = strain.sample(bodies, step, time) {
+                output::write_strain_row(writer, sample_step, sample_time, (h_plus, h_cross))?;
+            }
+        }
+        Ok(())
+    }
+
+    // 收尾: 中断时写检查点与最终快照, 画能量误差曲线, 刷新输出文件并报告各阶段的耗时
+    fn finish(mut self, sim: &Simulation, interrupted: bool) -> Result<(), SimError> {
+        let (config, units) = (&sim.config, sim.config.units);
+        self.pb.finish();
+        if interrupted {
+            checkpoint::save_checkpoint(&sim.bodies, sim.step_count, sim.time, units, config.seed, &self.checkpoint_path)?;
+            let snapshot = config.output_dir.join("snapshot_final");
+            let format = config.snapshot_format.unwrap_or(SnapshotFormat::Csv);
+            write_snapshot(&sim.bodies, format, config.gzip_snapshots, &snapshot, sim.time)?;
+            warn!(
+                "Interrupted at step {} (t = {:e} {}); wrote checkpoint '{}' and a final snapshot. Continue with --resume.",
+                sim.step_count,
+                sim.time,
+                units.time(),
+                self.checkpoint_path.display()
+            );
+        } else {
+            info!("Simulation complete: {} steps, t = {:e} {}.", sim.step_count, sim.time, units.time());
+        }
+        if let Some(a) = sim.scale_factor() {
+            info!("Final scale factor a = {:.6e}", a);
+        }
+        if config.block_timesteps {
+            let smallest = sim.bodies.iter().map(|b| b.timestep).fold(Real::INFINITY, Real::min);
+            info!("Force evaluations: {} (smallest current block step {:e} {})", sim.force_evaluations, smallest, units.time());
+        }
+
+        if self.energy_errors.len() > 1 {
+            let path = config.output_dir.join("energy_error.png");
+            plot::plot_energy_error(&self.energy_errors, &path, units)?;
+            info!("Wrote the energy error curve to '{}'.", path.display());
+        }
+        self.writers.finish()?;
+
+        if config.profile {
+            self.profiler.lap(Phase::Output);
+            let report = self.profiler.report();
+            let total = report.last().map_or(0.0, |(_, t)| t.as_secs_f64()).max(f64::MIN_POSITIVE);
+            for (name, time) in report {
+                info!("Profile: {:<12} {:>10.3} s ({:5.1}%)", name, time.as_secs_f64(), 100.0 * time.as_secs_f64() / total);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Ctrl-C 只设置标志, 主循环在每步开始前检查, 结束当前步后写检查点退出
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// 第二次 Ctrl-C 立即退出, 以防收尾时卡住; 信号处理函数中只调用异步信号安全的 _exit
+#[cfg(unix)]
+extern "C" fn handle_sigint(_signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(130) };
+    }
+}
+
+#[cfg(unix)]
+fn install_interrupt_handler() -> std::io::Result<()> {
+    let handler = handle_sigint as extern "C" fn(libc::c_int);
+    if unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) } == libc::SIG_ERR {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// 其他平台保留默认行为: Ctrl-C 直接终止进程
+#[cfg(not(unix))]
+fn install_interrupt_handler() -> std::io::Result<()> {
+    Ok(())
+}
+
+// 进度条上显示的吞吐量: 积分步本身 (不含输出) 的耗时与相互作用数的指数滑动平均, 约平滑最近 20 步
+#[derive(Default)]
+struct Throughput {
+    seconds: f64,
+    interactions: f64,
+}
+
+impl Throughput {
+    const WEIGHT: f64 = 0.05;
+
+    fn update(&mut self, seconds: f64, interactions: f64) {
+        if self.seconds == 0.0 {
+            (self.seconds, self.interactions) = (seconds, interactions);
+        } else {
+            self.seconds += Self::WEIGHT * (seconds - self.seconds);
+            self.interactions += Self::WEIGHT * (interactions - self.interactions);
+        }
+    }
+
+    fn message(&self) -> String {
+        format!("{:.1} steps/s, {:.3e} interactions/s", 1.0 / self.seconds, self.interactions / self.seconds)
+    }
+}
+
+// time 中包含的 interval 的整数倍个数; 逐步累加 dt 的舍入误差可能使 time 略小于整数倍, 比较时留出相对 1e-9 的余量
+fn elapsed_multiples(time: f64, interval: f64) -> usize {
+    (time / interval + 1e-9).floor().max(0.0) as usize
+}
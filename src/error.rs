@@ -0,0 +1,85 @@
+// 模拟过程中的错误
+use plotters::drawing::DrawingAreaErrorKind;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SimError {
+    // 输入文件 (初始条件, 检查点, 配置文件) 不存在
+    #[error("Input file '{}' not found", .0.display())]
+    InputNotFound(PathBuf),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    // JSON 格式错误或字段缺失
+    #[error("Invalid JSON: {0}")]
+    Deserialize(#[from] serde_json::Error),
+
+    // CSV 初始条件中第 line 行无法解析
+    #[error("Invalid CSV at line {line}: {message}")]
+    InvalidCsv { line: u64, message: String },
+
+    // Gadget-2 快照的记录标记不匹配, 或块的长度与文件头中的粒子数不符
+    #[error("Invalid Gadget file: {0}")]
+    InvalidGadget(String),
+
+    // TIPSY 文件头不合法, 或文件长度与头中的粒子数不符
+    #[error("Invalid TIPSY file: {0}")]
+    InvalidTipsy(String),
+
+    // 配置文件或命令行参数不合法
+    #[error("{0}")]
+    Config(String),
+
+    // 初始条件中第 index 个粒子的 field 不合法 (质量非正, 或出现 NaN/Inf)
+    #[error("Body {index} has an invalid {field}: {value}")]
+    InvalidBody { index: usize, field: &'static str, value: f64 },
+
+    // 投影轴只能是 'x', 'y', 'z'
+    #[error("Invalid projection axis '{0}', expected 'x', 'y' or 'z'")]
+    InvalidAxis(char),
+
+    #[error("Plotting failed: {0}")]
+    Plot(String),
+
+    // 运行中写出第 label 帧失败 (如磁盘写满); 调用方已尽量写出检查点
+    #[error("Writing frame {label} at step {step} failed: {source}")]
+    FrameWrite { label: usize, step: usize, source: Box<SimError> },
+
+    // 第 step 步时第 body 个粒子的位置或速度出现 NaN/Inf
+    #[error("Body {body} has a non-finite position or velocity at step {step}")]
+    NonFiniteState { step: usize, body: usize },
+
+    // 两次检查之间粒子数或总质量的变化与启用的功能不符, expected 描述允许的变化
+    #[error(
+        "Mass check failed at step {step}: {before_count} bodies of total mass {before_mass:e} became \
+         {after_count} bodies of total mass {after_mass:e}, expected {expected}"
+    )]
+    MassNotConserved {
+        step: usize,
+        before_count: usize,
+        after_count: usize,
+        before_mass: f64,
+        after_mass: f64,
+        expected: &'static str,
+    },
+}
+
+// plotters 的绘图错误统一转为字符串, 避免错误类型依赖具体的后端
+impl<E: std::error::Error + Send + Sync> From<DrawingAreaErrorKind<E>> for SimError {
+    fn from(error: DrawingAreaErrorKind<E>) -> Self {
+        SimError::Plot(error.to_string())
+    }
+}
+
+impl SimError {
+    // 打开文件失败时区分文件不存在与其他 I/O 错误
+    pub fn open(path: &std::path::Path, error: std::io::Error) -> Self {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            SimError::InputNotFound(path.to_path_buf())
+        } else {
+            SimError::Io(error)
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// Ewald 求和: 周期性盒子中一个质点 (连同它所有的周期像, 扣除平均密度) 产生的真实引力.
+// 周期格林函数分成收敛很快的实空间和与倒空间和 (Hernquist, Bouchet & Suto 1991), 两者之和减去
+// 最小镜像的牛顿引力就是修正项. 修正项只依赖位移, 在边长为 1 的盒子中预先制表, 其他盒长按 L^-2 缩放
+use crate::precision::{from_dvec3, to_dvec3, to_f64};
+use crate::{Real, Vec3};
+use glam::DVec3;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+// 表格在 [0, L/2]^3 上每个方向的格数; 三线性插值后修正项的相对误差约为 1e-3
+const TABLE_CELLS: usize = 32;
+
+// 实空间与倒空间的劈分参数 alpha = 2 / L, 以及两个求和的截断 (Hernquist 等的取法)
+const ALPHA: f64 = 2.0;
+const REAL_IMAGES: i32 = 4;
+const RECIPROCAL_MAX_SQ: i32 = 10;
+
+// 互补误差函数, Chebyshev 拟合, 相对误差小于 1.2e-7 (Numerical Recipes, erfcc)
+pub fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let polynomial = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let value = t * polynomial.exp();
+    if x >= 0.0 {
+        value
+    } else {
+        2.0 - value
+    }
+}
+
+// 单位盒子, G m = 1 时, 位移为 d (源减去受力粒子) 的周期引力减去 d 本身的牛顿引力
+fn correction_exact(d: DVec3) -> DVec3 {
+    let mut force = DVec3::ZERO;
+    for nx in -REAL_IMAGES..=REAL_IMAGES {
+        for ny in -REAL_IMAGES..=REAL_IMAGES {
+            for nz in -REAL_IMAGES..=REAL_IMAGES {
+                let offset = d - DVec3::new(nx as f64, ny as f64, nz as f64);
+                let r = offset.length();
+                if r == 0.0 {
+                    continue;
+                }
+                let weight = erfc(ALPHA * r) + 2.0 * ALPHA * r / PI.sqrt() * (-ALPHA * ALPHA * r * r).exp();
+                force += offset * (weight / (r * r * r));
+            }
+        }
+    }
+
+    let limit = (RECIPROCAL_MAX_SQ as f64).sqrt() as i32;
+    for hx in -limit..=limit {
+        for hy in -limit..=limit {
+            for hz in -limit..=limit {
+                let h_sq = hx * hx + hy * hy + hz * hz;
+                if h_sq == 0 || h_sq > RECIPROCAL_MAX_SQ {
+                    continue;
+                }
+                let k = DVec3::new(hx as f64, hy as f64, hz as f64) * (2.0 * PI);
+                let k_sq = k.length_squared();
+                force += k * (4.0 * PI / k_sq * (-k_sq / (4.0 * ALPHA * ALPHA)).exp() * k.dot(d).sin());
+            }
+        }
+    }
+
+    let r = d.length();
+    if r > 0.0 {
+        force -= d / (r * r * r);
+    }
+    force
+}
+
+// 修正项在 [0, 1/2]^3 的 (TABLE_CELLS + 1)^3 个格点上的值; 其余卦限由对称性得到
+pub struct EwaldTable {
+    corrections: Vec<DVec3>,
+}
+
+impl EwaldTable {
+    fn new() -> Self {
+        let points = TABLE_CELLS + 1;
+        let spacing = 0.5 / TABLE_CELLS as f64;
+        let corrections = (0..points * points * points)
+            .into_par_iter()
+            .map(|index| {
+                let (i, j, k) = (index % points, index / points % points, index / (points * points));
+                correction_exact(DVec3::new(i as f64, j as f64, k as f64) * spacing)
+            })
+            .collect();
+        Self { corrections }
+    }
+
+    // 最小镜像位移 direction (源减去受力粒子) 处, G m = 1 时的修正加速度.
+    // 修正项的每个分量在该方向上是奇函数, 在另外两个方向上是偶函数
+    pub fn correction(&self, direction: Vec3, box_size: Real) -> Vec3 {
+        let size = to_f64(box_size);
+        let d = to_dvec3(direction) / size;
+        let cell = (d.abs() * (2.0 * TABLE_CELLS as f64)).min(DVec3::splat(TABLE_CELLS as f64));
+        let base = cell.floor().min(DVec3::splat(TABLE_CELLS as f64 - 1.0));
+        let fraction = cell - base;
+        let points = TABLE_CELLS + 1;
+        let (i, j, k) = (base.x as usize, base.y as usize, base.z as usize);
+
+        let mut value = DVec3::ZERO;
+        for corner in 0..8 {
+            let (dx, dy, dz) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let w = |offset: usize, f: f64| if offset == 1 { f } else { 1.0 - f };
+            let weight = w(dx, fraction.x) * w(dy, fraction.y) * w(dz, fraction.z);
+            value += self.corrections[(i + dx) + (j + dy) * points + (k + dz) * points * points] * weight;
+        }
+        let sign = DVec3::new(d.x.signum(), d.y.signum(), d.z.signum());
+        from_dvec3(value * sign / (size * size))
+    }
+}
+
+// 修正表与盒长和质量无关, 第一次使用时计算一次
+pub fn ewald_table() -> &'static EwaldTable {
+    static TABLE: OnceLock<EwaldTable> = OnceLock::new();
+    TABLE.get_or_init(EwaldTable::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::{ewald_accelerations, pairwise_accelerations, SofteningKernel};
+    use crate::{Body, Real};
+
+    // 单位盒子中 4^3 个等质量粒子组成的简单立方格子
+    fn lattice() -> Vec<Body> {
+        (0..64)
+            .map(|i| Body::new(1.0, Vec3::new((i % 4) as Real, (i / 4 % 4) as Real, (i / 16) as Real) * 0.25 + 0.125, Vec3::ZERO))
+            .collect()
+    }
+
+    #[test]
+    fn periodic_lattice_feels_no_force() {
+        // 格点间距 0.25 处单个近邻的引力为 16, 以此作为尺度
+        let mut bodies = lattice();
+        ewald_accelerations(&mut bodies, 1.0, 0.0, 1.0, SofteningKernel::Plummer);
+        let worst = bodies.iter().map(|b| b.acceleration.length()).fold(0.0, Real::max);
+        assert!(worst < 16.0 * 1e-3, "Ewald residual {}", worst);
+
+        // 最小镜像在 L / 2 处的取舍不对称, 留下明显的净力
+        let mut bodies = lattice();
+        pairwise_accelerations(&mut bodies, 1.0, 0.0, Some(1.0), SofteningKernel::Plummer);
+        let worst = bodies.iter().map(|b| b.acceleration.length()).fold(0.0, Real::max);
+        assert!(worst > 1.0, "minimum image residual {}", worst);
+    }
+}
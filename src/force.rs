@@ -0,0 +1,518 @@
+// 引力计算
+use crate::barnes_hut::Octree;
+use crate::ewald::ewald_table;
+use crate::pm_solver::pm_accelerations;
+use crate::profile;
+use crate::{Bodies, Body, BoundaryCondition, DynamicalFriction, ExternalPotential, Real, Vec3};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+
+// 引力的计算方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForceMethod {
+    // 直接求和, O(N^2)
+    Direct,
+    // 利用牛顿第三定律的对称直接求和, 每对粒子只计算一次
+    Pairwise,
+    // Barnes-Hut 八叉树, O(N log N), theta 越小越精确
+    BarnesHut { theta: Real },
+    // 周期性盒子上 grid^3 网格的粒子-网格 FFT 求解器, 见 pm_solver.rs; grid 必须是 2 的幂
+    ParticleMesh { grid: usize },
+    // 周期性盒子中的直接求和, 最小镜像的引力加上 Ewald 修正 (见 ewald.rs), 计入所有周期像
+    Ewald,
+}
+
+// 软化核: 决定近距离时引力如何偏离牛顿引力, eps 为 (组合后的) 软化长度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SofteningKernel {
+    /// Plummer sphere: G m / (r^2 + eps^2), slightly weaker than Newtonian at every distance
+    #[default]
+    Plummer,
+    /// Cubic spline with compact support: exactly Newtonian beyond 2.8 eps
+    #[serde(rename = "cubic-spline")]
+    CubicSpline,
+}
+
+// 样条核的半径 h 与软化长度之比; 取 2.8 时核中心的势 -G m / eps 与同样 eps 的 Plummer 核一致 (Springel 2005)
+pub const SPLINE_RADIUS_FACTOR: Real = 2.8;
+
+impl SofteningKernel {
+    // 引力参数为 gm = G * m, 相对位移为 direction 的粒子产生的加速度, softening_sq 为 eps^2
+    pub fn acceleration(self, direction: Vec3, gm: Real, softening_sq: Real) -> Vec3 {
+        match self {
+            SofteningKernel::Plummer => softened_acceleration(direction, gm, softening_sq),
+            SofteningKernel::CubicSpline => spline_acceleration(direction, gm, softening_sq),
+        }
+    }
+
+    // 距离平方为 distance_sq 时的势能 phi / m_i, 与 acceleration 对应
+    pub fn potential(self, distance_sq: Real, gm: Real, softening_sq: Real) -> Real {
+        match self {
+            SofteningKernel::Plummer => -gm / (distance_sq + softening_sq).sqrt(),
+            SofteningKernel::CubicSpline => spline_potential(distance_sq, gm, softening_sq),
+        }
+    }
+}
+
+// 三次样条软化 (Monaghan & Lattanzio 1985 的核, 形式同 GADGET-2), h = 2.8 eps, u = r / h:
+//   a = G m d / h^3 * (32/3 - 192/5 u^2 + 32 u^3),                                0 <= u < 1/2
+//   a = G m d / h^3 * (64/3 - 48 u + 192/5 u^2 - 32/3 u^3 - 1 / (15 u^3)),        1/2 <= u < 1
+//   a = G m d / r^3,                                                             u >= 1
+// 质量分布在半径 h 以内, 此外与牛顿引力完全相同. eps = 0 时退化为牛顿引力
+pub fn spline_acceleration(direction: Vec3, gm: Real, softening_sq: Real) -> Vec3 {
+    let r_sq = direction.length_squared();
+    let h_sq = SPLINE_RADIUS_FACTOR * SPLINE_RADIUS_FACTOR * softening_sq;
+    if r_sq >= h_sq {
+        let inv_r = r_sq.sqrt().recip();
+        return direction * (gm * inv_r * inv_r * inv_r);
+    }
+    let h = h_sq.sqrt();
+    let u = r_sq.sqrt() / h;
+    let factor = if u < 0.5 {
+        32.0 / 3.0 + u * u * (-192.0 / 5.0 + 32.0 * u)
+    } else {
+        64.0 / 3.0 - 48.0 * u + 192.0 / 5.0 * u * u - 32.0 / 3.0 * u * u * u - 1.0 / (15.0 * u * u * u)
+    };
+    direction * (gm * factor / (h_sq * h))
+}
+
+// 与 spline_acceleration 对应的势 phi / m_i:
+//   phi = G m / h * (-14/5 + 16/3 u^2 - 48/5 u^4 + 32/5 u^5),                                 0 <= u < 1/2
+//   phi = G m / h * (-16/5 + 1 / (15 u) + 32/3 u^2 - 16 u^3 + 48/5 u^4 - 32/15 u^5),          1/2 <= u < 1
+//   phi = -G m / r,                                                                           u >= 1
+pub fn spline_potential(distance_sq: Real, gm: Real, softening_sq: Real) -> Real {
+    let h_sq = SPLINE_RADIUS_FACTOR * SPLINE_RADIUS_FACTOR * softening_sq;
+    if distance_sq >= h_sq {
+        return -gm / distance_sq.sqrt();
+    }
+    let h = h_sq.sqrt();
+    let u = distance_sq.sqrt() / h;
+    let u2 = u * u;
+    let w = if u < 0.5 {
+        -14.0 / 5.0 + u2 * (16.0 / 3.0 + u2 * (-48.0 / 5.0 + 32.0 / 5.0 * u))
+    } else {
+        -16.0 / 5.0 + 1.0 / (15.0 * u) + u2 * (32.0 / 3.0 + u * (-16.0 + u * (48.0 / 5.0 - 32.0 / 15.0 * u)))
+    };
+    gm * w / h
+}
+
+// 引力计算参数
+#[derive(Debug, Clone, Copy)]
+pub struct ForceParams {
+    // 万有引力常数, 由单位制决定 (见 units::Units)
+    pub g: Real,
+    pub softening: Real,
+    pub kernel: SofteningKernel,
+    pub method: ForceMethod,
+    // 盒子边界; 只有周期性边界影响受力 (最小镜像)
+    pub boundary: BoundaryCondition,
+    // 叠加在粒子间引力之上的外部静态势
+    pub external: Option<ExternalPotential>,
+    // 对均匀背景的动力学摩擦, 与速度有关
+    pub friction: Option<DynamicalFriction>,
+    // 平面模拟: 丢弃加速度的 z 分量, 使初始 z = vz = 0 的粒子始终留在 x-y 平面内
+    pub planar: bool,
+    // 力律 1 / r^n 的指数 n; 只有 2 是牛顿引力, 其他值见 power_law_acceleration
+    pub force_exponent: Real,
+}
+
+impl ForceParams {
+    // 粒子对的势能 phi / m_i, 与 update_accelerations 所用的力律一致
+    pub fn pair_potential(&self, distance_sq: Real, gm: Real, softening_sq: Real) -> Real {
+        if self.force_exponent == 2.0 {
+            self.kernel.potential(distance_sq, gm, softening_sq)
+        } else {
+            power_law_potential(distance_sq, gm, softening_sq, self.force_exponent)
+        }
+    }
+}
+
+// 单元测试用的受力参数: 无界空间中 Plummer 软化的直接求和, 没有外力
+#[cfg(test)]
+pub(crate) fn test_params(g: Real, softening: Real) -> ForceParams {
+    ForceParams {
+        g,
+        softening,
+        kernel: SofteningKernel::Plummer,
+        method: ForceMethod::Direct,
+        boundary: BoundaryCondition::Open,
+        external: None,
+        friction: None,
+        planar: false,
+        force_exponent: 2.0,
+    }
+}
+
+// 最小镜像约定: 每个分量平移整数个盒长, 取最近的周期像.
+// 这只计入最近的一个像, 并不是真正的周期势 (需要 Ewald 求和), 只是近似.
+pub fn minimum_image(direction: Vec3, box_size: Option<Real>) -> Vec3 {
+    match box_size {
+        Some(size) => direction - size * (direction / size).round(),
+        None => direction,
+    }
+}
+
+// 引力参数为 gm = G * m, 相对位移为 direction 的粒子产生的 Plummer 软化引力加速度:
+// a = G * m * d / (|d|^2 + eps^2)^1.5, 不做 normalize, 极近距离时也保持有限.
+// (r^2)^-1.5 由一次 sqrt 与一次倒数再立方得到, 比 powf 快得多, 与其相差仅几个 ulp
+pub fn softened_acceleration(direction: Vec3, gm: Real, softening_sq: Real) -> Vec3 {
+    let inv_r = (direction.length_squared() + softening_sq).sqrt().recip();
+    direction * (gm * inv_r * inv_r * inv_r)
+}
+
+// 非牛顿力律的玩具模型 (教学与修改引力的实验), Plummer 式软化 r^2 -> s = r^2 + eps^2:
+//   a = G m d / s^((n + 1) / 2),  phi = -G m / ((n - 1) s^((n - 1) / 2)),  n = 1 时 phi = G m ln(s) / 2.
+// n = 2 时即 softened_acceleration. G 沿用所选单位制中的数值, 其量纲只在 n = 2 时与单位制一致
+pub fn power_law_acceleration(direction: Vec3, gm: Real, softening_sq: Real, exponent: Real) -> Vec3 {
+    let s = direction.length_squared() + softening_sq;
+    direction * (gm * s.powf(-0.5 * (exponent + 1.0)))
+}
+
+pub fn power_law_potential(distance_sq: Real, gm: Real, softening_sq: Real, exponent: Real) -> Real {
+    let s = distance_sq + softening_sq;
+    if exponent == 1.0 {
+        0.5 * gm * s.ln()
+    } else {
+        -gm / ((exponent - 1.0) * s.powf(0.5 * (exponent - 1.0)))
+    }
+}
+
+// 两个粒子之间的组合软化: eps_ij^2 = (eps_i^2 + eps_j^2) / 2, 对 i, j 对称, 保证牛顿第三定律
+pub fn pair_softening_sq(softening_sq_i: Real, softening_sq_j: Real) -> Real {
+    0.5 * (softening_sq_i + softening_sq_j)
+}
+
+// 计算引力并更新加速度
+pub fn update_accelerations(bodies: &mut [Body], params: &ForceParams) {
+    profile::time_forces(|| compute_accelerations(bodies, params));
+}
+
+fn compute_accelerations(bodies: &mut [Body], params: &ForceParams) {
+    match params.method {
+        // 非牛顿力律只有直接求和 (Simulation::new 中检查), n = 2 时走下面原有的路径
+        _ if params.force_exponent != 2.0 => power_law_accelerations(
+            bodies,
+            params.g,
+            params.softening,
+            params.boundary.periodic_size(),
+            params.force_exponent,
+        ),
+        ForceMethod::Direct => {
+            direct_accelerations(bodies, params.g, params.softening, params.boundary.periodic_size(), params.kernel)
+        }
+        ForceMethod::Pairwise => {
+            pairwise_accelerations(bodies, params.g, params.softening, params.boundary.periodic_size(), params.kernel)
+        }
+        ForceMethod::BarnesHut { theta } => {
+            let tree = Octree::new(bodies, params.softening);
+            let (box_size, kernel) = (params.boundary.periodic_size(), params.kernel);
+            bodies.par_iter_mut().for_each(|body| {
+                body.acceleration = tree.compute_acceleration(body, theta, params.g, params.softening, box_size, kernel);
+            });
+        }
+        // PM 与 Ewald 只在周期性盒子中有定义 (Simulation::new 中检查), 其他边界时退回直接求和
+        ForceMethod::ParticleMesh { grid } => match params.boundary.periodic_size() {
+            Some(size) => pm_accelerations(bodies, params.g, size, grid),
+            None => direct_accelerations(bodies, params.g, params.softening, None, params.kernel),
+        },
+        ForceMethod::Ewald => match params.boundary.periodic_size() {
+            Some(size) => ewald_accelerations(bodies, params.g, params.softening, size, params.kernel),
+            None => direct_accelerations(bodies, params.g, params.softening, None, params.kernel),
+        },
+    }
+
+    if let Some(external) = &params.external {
+        bodies.par_iter_mut().for_each(|body| {
+            body.acceleration += external.external_acceleration(body.position, params.g);
+        });
+    }
+
+    if let Some(friction) = &params.friction {
+        bodies.par_iter_mut().for_each(|body| {
+            body.acceleration += friction.acceleration(body.mass, body.velocity, params.g);
+        });
+    }
+
+    // 平面内粒子之间的引力本身没有 z 分量, 这里去掉外部势 (如不在平面内的点质量) 与舍入带来的部分
+    if params.planar {
+        bodies.par_iter_mut().for_each(|body| body.acceleration.z = 0.0);
+    }
+
+    bodies.par_iter_mut().filter(|body| body.fixed).for_each(|body| body.acceleration = Vec3::ZERO);
+}
+
+// 直接求和同时计算加速度与 jerk (加速度的时间导数), 供 Hermite 积分器使用:
+// j = G * m * (v / r^3 - 3 (d . v) d / r^5), 其中 r^2 = |d|^2 + eps^2, v 为相对速度.
+// 只有 Plummer 软化核, 不支持树方法, 周期性边界与外部势
+pub fn update_acc_and_jerk(bodies: &mut [Body], g: Real, softening: Real) {
+    profile::time_forces(|| compute_acc_and_jerk(bodies, g, softening));
+}
+
+fn compute_acc_and_jerk(bodies: &mut [Body], g: Real, softening: Real) {
+    let sources: Vec<_> =
+        bodies.iter().map(|b| (b.position, b.velocity, g * b.mass, b.softening_sq(softening))).collect();
+
+    bodies.par_iter_mut().for_each(|body| {
+        (body.acceleration, body.jerk) = if body.fixed {
+            (Vec3::ZERO, Vec3::ZERO)
+        } else {
+            acc_and_jerk(body.position, body.velocity, body.softening_sq(softening), &sources)
+        };
+    });
+}
+
+// 位于 position, 速度为 velocity 的粒子受到 sources (位置, 速度, 引力参数 G m, eps^2) 产生的加速度与 jerk,
+// 跳过与自身位置重合的场源
+pub fn acc_and_jerk(
+    position: Vec3,
+    velocity: Vec3,
+    softening_sq: Real,
+    sources: &[(Vec3, Vec3, Real, Real)],
+) -> (Vec3, Vec3) {
+    let mut acceleration = Vec3::ZERO;
+    let mut jerk = Vec3::ZERO;
+    for (pos_j, vel_j, gm_j, softening_sq_j) in sources {
+        if position == *pos_j {
+            continue;
+        }
+        let direction = *pos_j - position;
+        let relative_velocity = *vel_j - velocity;
+        let r_sq = direction.length_squared() + pair_softening_sq(softening_sq, *softening_sq_j);
+        let inv_r3 = gm_j / (r_sq * r_sq.sqrt());
+        acceleration += direction * inv_r3;
+        jerk += (relative_velocity - direction * (3.0 * direction.dot(relative_velocity) / r_sq)) * inv_r3;
+    }
+    (acceleration, jerk)
+}
+
+// 直接求和所用的结构体数组缓冲区, 在调用线程内跨步复用, 避免每一步都重新分配 N 个元素.
+// 使用时把它取出, 用完再放回, 因此嵌套调用只会退化为重新分配而不会冲突
+thread_local! {
+    static SOURCES: Cell<Bodies> = Cell::new(Bodies::default());
+}
+
+// 取出复用的缓冲区, 并用 bodies 的当前状态填充
+fn take_sources(bodies: &[Body], softening_factor: Real) -> Bodies {
+    let mut sources = SOURCES.take();
+    sources.fill_from(bodies, softening_factor);
+    sources
+}
+
+// 直接求和的分块 (tiling): 每个任务负责 SINK_TILE 个目标粒子, 场源按 SOURCE_TILE 个一块依次读入,
+// 一块场源 (约 40 KiB) 留在缓存中被任务内的所有目标粒子复用, 而不是每个目标粒子都从内存重新读一遍全部 N 个场源.
+// 每个目标粒子仍按 j 从小到大的顺序累加, 结果与逐粒子求和逐位相同. 单核上核函数的计算量占主导, 两者耗时相当
+// (benches/nbody.rs 的 direct_tiling); 多个线程共享末级缓存时才能减少内存带宽
+const SINK_TILE: usize = 64;
+const SOURCE_TILE: usize = 1024;
+
+// 直接求和计算加速度
+pub fn direct_accelerations(
+    bodies: &mut [Body],
+    g: Real,
+    softening_factor: Real,
+    box_size: Option<Real>,
+    kernel: SofteningKernel,
+) {
+    let mut positions_masses = take_sources(bodies, softening_factor);
+    let Bodies { mass, position, acceleration, softening_sq, .. } = &mut positions_masses;
+    let n = position.len();
+
+    // 使用 Rayon 按目标块并行; 每块场源依次对块内的所有目标粒子求和, 内层循环读取连续的位置, 质量与软化数组
+    acceleration.par_chunks_mut(SINK_TILE).enumerate().for_each(|(tile, sinks)| {
+        sinks.fill(Vec3::ZERO);
+        for start in (0..n).step_by(SOURCE_TILE) {
+            let end = (start + SOURCE_TILE).min(n);
+            let sources = position[start..end].iter().zip(&mass[start..end]).zip(&softening_sq[start..end]);
+            for (k, acceleration_i) in sinks.iter_mut().enumerate() {
+                let i = tile * SINK_TILE + k;
+                let (pos_i, softening_sq_i) = (position[i], softening_sq[i]);
+                for ((pos_j, mass_j), softening_sq_j) in sources.clone() {
+                    if pos_i == *pos_j {
+                        continue;
+                    }
+                    let direction = minimum_image(*pos_j - pos_i, box_size);
+                    let softening_sq = pair_softening_sq(softening_sq_i, *softening_sq_j);
+                    *acceleration_i += kernel.acceleration(direction, g * *mass_j, softening_sq);
+                }
+            }
+        }
+    });
+
+    positions_masses.store_accelerations(bodies);
+    SOURCES.set(positions_masses);
+}
+
+// 1 / r^n 力律的直接求和, 见 power_law_acceleration
+fn power_law_accelerations(bodies: &mut [Body], g: Real, softening_factor: Real, box_size: Option<Real>, exponent: Real) {
+    let mut positions_masses = take_sources(bodies, softening_factor);
+    let Bodies { mass, position, acceleration, softening_sq, .. } = &mut positions_masses;
+
+    acceleration.par_iter_mut().enumerate().for_each(|(i, acceleration_i)| {
+        let (pos_i, softening_sq_i) = (position[i], softening_sq[i]);
+        let mut total_acceleration = Vec3::ZERO;
+        for ((pos_j, mass_j), softening_sq_j) in position.iter().zip(mass.iter()).zip(softening_sq.iter()) {
+            if pos_i == *pos_j {
+                continue;
+            }
+            let direction = minimum_image(*pos_j - pos_i, box_size);
+            let softening_sq = pair_softening_sq(softening_sq_i, *softening_sq_j);
+            total_acceleration += power_law_acceleration(direction, g * *mass_j, softening_sq, exponent);
+        }
+        *acceleration_i = total_acceleration;
+    });
+
+    positions_masses.store_accelerations(bodies);
+    SOURCES.set(positions_masses);
+}
+
+// 周期性盒子中的直接求和: 软化核作用在最小镜像上, 其余周期像与平均密度的贡献由 Ewald 修正表插值得到.
+// 修正项在最小镜像距离上是光滑的, 不做软化
+pub fn ewald_accelerations(bodies: &mut [Body], g: Real, softening_factor: Real, box_size: Real, kernel: SofteningKernel) {
+    let table = ewald_table();
+    let mut positions_masses = take_sources(bodies, softening_factor);
+    let Bodies { mass, position, acceleration, softening_sq, .. } = &mut positions_masses;
+
+    acceleration.par_iter_mut().enumerate().for_each(|(i, acceleration_i)| {
+        let (pos_i, softening_sq_i) = (position[i], softening_sq[i]);
+        let mut total_acceleration = Vec3::ZERO;
+        for ((pos_j, mass_j), softening_sq_j) in position.iter().zip(mass.iter()).zip(softening_sq.iter()) {
+            if pos_i == *pos_j {
+                continue;
+            }
+            let direction = minimum_image(*pos_j - pos_i, Some(box_size));
+            let softening_sq = pair_softening_sq(softening_sq_i, *softening_sq_j);
+            let gm = g * *mass_j;
+            total_acceleration += kernel.acceleration(direction, gm, softening_sq) + table.correction(direction, box_size) * gm;
+        }
+        *acceleration_i = total_acceleration;
+    });
+
+    positions_masses.store_accelerations(bodies);
+    SOURCES.set(positions_masses);
+}
+
+// 对称直接求和: 每对 (i, j) 只计算一次核函数, 分别给 i 加 +k*m_j, 给 j 加 -k*m_i.
+// 行按 i mod PAIRWISE_BLOCKS 交错分成固定数目的块 (三角形的工作量因此大致均衡), 每块累加到自己的加速度数组,
+// 最后每个粒子按块号顺序求和. 分块与求和顺序都与线程数无关, 同样的输入在任意线程数下逐位相同
+pub const PAIRWISE_BLOCKS: usize = 32;
+
+pub fn pairwise_accelerations(
+    bodies: &mut [Body],
+    g: Real,
+    softening_factor: Real,
+    box_size: Option<Real>,
+    kernel: SofteningKernel,
+) {
+    let n = bodies.len();
+    let positions_masses = take_sources(bodies, softening_factor);
+    let blocks = PAIRWISE_BLOCKS.min(n);
+
+    let partial: Vec<Vec<Vec3>> = (0..blocks)
+        .into_par_iter()
+        .map(|block| {
+            let Bodies { mass, position, softening_sq, .. } = &positions_masses;
+            let mut acc = vec![Vec3::ZERO; n];
+            for i in (block..n).step_by(blocks) {
+                let (pos_i, mass_i, softening_sq_i) = (position[i], mass[i], softening_sq[i]);
+                let (head, tail) = acc.split_at_mut(i + 1);
+                let sources = position[i + 1..].iter().zip(&mass[i + 1..]).zip(&softening_sq[i + 1..]);
+                for (acc_j, ((pos_j, mass_j), softening_sq_j)) in tail.iter_mut().zip(sources) {
+                    if pos_i == *pos_j {
+                        continue;
+                    }
+                    let softening_sq = pair_softening_sq(softening_sq_i, *softening_sq_j);
+                    let k = kernel.acceleration(minimum_image(*pos_j - pos_i, box_size), g, softening_sq);
+                    head[i] += k * *mass_j;
+                    *acc_j -= k * mass_i;
+                }
+            }
+            acc
+        })
+        .collect();
+    let accelerations: Vec<Vec3> =
+        (0..n).into_par_iter().map(|j| partial.iter().fold(Vec3::ZERO, |sum, acc| sum + acc[j])).collect();
+
+    for (body, acceleration) in bodies.iter_mut().zip(accelerations) {
+        body.acceleration = acceleration;
+    }
+    SOURCES.set(positions_masses);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // actual 与 expected 的相对误差不超过 tolerance
+    fn assert_close(actual: Vec3, expected: Vec3, tolerance: Real) {
+        let scale = expected.length().max(Real::MIN_POSITIVE);
+        assert!(actual.distance(expected) <= tolerance * scale, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn softened_force_stays_finite_at_tiny_separation() {
+        let (g, softening, separation) = (1.0, 0.1, 1e-12);
+        let mut bodies = vec![
+            Body::new(2.0, Vec3::ZERO, Vec3::ZERO),
+            Body::new(3.0, Vec3::new(separation, 0.0, 0.0), Vec3::ZERO),
+        ];
+        update_accelerations(&mut bodies, &test_params(g, softening));
+
+        // a = G m d / (d^2 + eps^2)^1.5
+        let closed_form = |mass: Real| g * mass * separation / (separation * separation + softening * softening).powf(1.5);
+        assert!(bodies.iter().all(|b| b.acceleration.is_finite()));
+        assert_close(bodies[0].acceleration, Vec3::new(closed_form(3.0), 0.0, 0.0), 1e-12);
+        assert_close(bodies[1].acceleration, Vec3::new(-closed_form(2.0), 0.0, 0.0), 1e-12);
+    }
+
+    #[test]
+    fn spline_force_is_newtonian_beyond_the_kernel_radius() {
+        let (gm, softening) = (2.0, 0.1);
+        let h = SPLINE_RADIUS_FACTOR * softening;
+        let newtonian = |r: Real| Vec3::new(gm / (r * r), 0.0, 0.0);
+        for r in [h, 1.01 * h, 2.0 * h, 10.0 * h] {
+            let acceleration = spline_acceleration(Vec3::new(r, 0.0, 0.0), gm, softening * softening);
+            assert_close(acceleration, newtonian(r), 64.0 * Real::EPSILON);
+        }
+        // 核内比牛顿力弱, 且在核边界处连续
+        let inside = spline_acceleration(Vec3::new(0.5 * h, 0.0, 0.0), gm, softening * softening);
+        assert!(inside.x < newtonian(0.5 * h).x);
+        let edge = spline_acceleration(Vec3::new(0.999 * h, 0.0, 0.0), gm, softening * softening);
+        assert_close(edge, newtonian(h), 0.01);
+    }
+
+    #[test]
+    fn exponent_two_is_the_newtonian_force_law() {
+        let mut bodies = crate::generators::generate_plummer(50, 1.0, 1.0, 3, 1.0);
+        let (g, softening) = (1.0, 0.05);
+        // 独立写出的牛顿直接求和: a_i = sum_j G m_j d / (|d|^2 + eps^2)^1.5
+        let newtonian: Vec<Vec3> = bodies
+            .iter()
+            .map(|bi| {
+                bodies.iter().filter(|bj| bj.position != bi.position).fold(Vec3::ZERO, |sum, bj| {
+                    let d = bj.position - bi.position;
+                    sum + d * (g * bj.mass / (d.length_squared() + softening * softening).powf(1.5))
+                })
+            })
+            .collect();
+
+        // force_exponent = 2 的受力与闭式解一致; 幂律公式本身在 n = 2 时也只差几个 ulp
+        let params = ForceParams { force_exponent: 2.0, ..test_params(g, softening) };
+        update_accelerations(&mut bodies, &params);
+        for (body, expected) in bodies.iter().zip(&newtonian) {
+            assert_close(body.acceleration, *expected, 1e3 * Real::EPSILON);
+        }
+        power_law_accelerations(&mut bodies, g, softening, None, 2.0);
+        for (body, expected) in bodies.iter().zip(&newtonian) {
+            assert_close(body.acceleration, *expected, 1e3 * Real::EPSILON);
+        }
+
+        let (gm, softening_sq) = (2.0, 0.01);
+        for distance_sq in [1e-4, 0.3, 4.0] {
+            let potential = power_law_potential(distance_sq, gm, softening_sq, 2.0);
+            let plummer = params.pair_potential(distance_sq, gm, softening_sq);
+            assert!((potential - plummer).abs() <= 16.0 * Real::EPSILON * plummer.abs(), "{} != {}", potential, plummer);
+        }
+    }
+}
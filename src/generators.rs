@@ -0,0 +1,295 @@
+// 初始条件生成器
+//
+// 可复现性: 所有随机性都来自配置中的 --seed. 每个生成器从参数 seed 初始化自己的 StdRng, 按固定顺序抽样;
+// 绘图的子采样按 seed 与粒子编号哈希 (见 plot::subsample), 不消耗随机数流. 模拟本身是确定性的.
+// 相同的 seed 与相同的配置 (以及 Cargo.lock 锁定的同一 rand 版本) 逐位得到相同的初始条件与输出.
+// 检查点保存 seed, 续算时沿用, 因此续算与不中断的运行结果一致. 以后在运行中引入随机性的功能
+// 应同样从 seed 派生, 并把随机数状态写入检查点
+use crate::diagnostics::to_com_frame;
+use crate::precision::consts::TAU;
+use crate::{Body, Real, Vec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// 单位球面上均匀分布的随机方向
+fn random_direction(rng: &mut StdRng) -> Vec3 {
+    let cos_theta: Real = rng.gen_range(-1.0..1.0);
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let phi = rng.gen_range(0.0..TAU);
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+// Plummer 球 (Aarseth, Henon & Wielen 1974):
+// 半径由累积质量分布反解, 速度按各向同性分布函数用舍选法抽样.
+// 累积质量超过 0.999 的粒子重新抽样, 避免极远处的离群粒子. g 为所用单位制下的万有引力常数.
+pub fn generate_plummer(n: usize, total_mass: Real, scale_radius: Real, seed: u64, g: Real) -> Vec<Body> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mass = total_mass / n as Real;
+
+    let mut bodies: Vec<Body> = (0..n)
+        .map(|_| {
+            let mass_fraction: Real = loop {
+                let x: Real = rng.gen();
+                if x > 0.0 && x < 0.999 {
+                    break x;
+                }
+            };
+            let r = scale_radius / (mass_fraction.powf(-2.0 / 3.0) - 1.0).sqrt();
+            let position = random_direction(&mut rng) * r;
+
+            // 速度与逃逸速度之比 q 满足 g(q) = q^2 (1 - q^2)^3.5, 其最大值小于 0.1
+            let q = loop {
+                let q: Real = rng.gen();
+                let y: Real = rng.gen_range(0.0..0.1);
+                if y < q * q * (1.0 - q * q).powf(3.5) {
+                    break q;
+                }
+            };
+            let escape_velocity = (2.0 * g * total_mass).sqrt() * (r * r + scale_radius * scale_radius).powf(-0.25);
+            let velocity = random_direction(&mut rng) * (q * escape_velocity);
+
+            Body::new(mass, position, velocity)
+        })
+        .collect();
+
+    to_com_frame(&mut bodies);
+    Body::assign_ids(&mut bodies);
+    bodies
+}
+
+// 标准正态分布的随机数 (Box-Muller)
+pub(crate) fn gaussian(rng: &mut StdRng) -> Real {
+    let u: Real = 1.0 - rng.gen::<Real>();
+    let phi = rng.gen_range(0.0..TAU);
+    (-2.0 * u.ln()).sqrt() * phi.cos()
+}
+
+// 旋转支撑的指数盘: 面密度 Sigma(R) ∝ exp(-R / R_d), 垂直方向 rho(z) ∝ sech^2(z / z_0), 绕 +z 轴逆时针旋转.
+// 半径由 x e^-x 分布 (两个指数分布之和) 抽样, z 由 sech^2 的累积分布反解, 超过 DISK_CUTOFF 个标长的都重新抽样.
+// 每个粒子取其半径内质量对应的圆速度 v_c = sqrt(G M(<R) / R) (按球对称近似, 未计软化与非对称漂移),
+// 再在三个方向上各叠加 DISK_DISPERSION * v_c 的高斯速度弥散
+pub fn generate_disk(n: usize, total_mass: Real, scale_length: Real, scale_height: Real, seed: u64, g: Real) -> Vec<Body> {
+    const DISK_CUTOFF: Real = 10.0;
+    const DISK_DISPERSION: Real = 0.1;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mass = total_mass / n as Real;
+    // 半径 x R_d 以内的质量分数, 按截断后的总质量归一
+    let enclosed = |x: Real| (1.0 - (1.0 + x) * (-x).exp()) / (1.0 - (1.0 + DISK_CUTOFF) * (-DISK_CUTOFF).exp());
+
+    let mut bodies: Vec<Body> = (0..n)
+        .map(|_| {
+            let x = loop {
+                let (u1, u2): (Real, Real) = (1.0 - rng.gen::<Real>(), 1.0 - rng.gen::<Real>());
+                let x = -(u1 * u2).ln();
+                if x < DISK_CUTOFF {
+                    break x;
+                }
+            };
+            let radius = x * scale_length;
+            let phi = rng.gen_range(0.0..TAU);
+            let z = loop {
+                let z = scale_height * rng.gen_range(-1.0..1.0 as Real).atanh();
+                if z.abs() < DISK_CUTOFF * scale_height {
+                    break z;
+                }
+            };
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let position = Vec3::new(radius * cos_phi, radius * sin_phi, z);
+
+            let circular_speed = (g * total_mass * enclosed(x) / radius).sqrt();
+            let rotation = Vec3::new(-sin_phi, cos_phi, 0.0) * circular_speed;
+            let dispersion = Vec3::new(gaussian(&mut rng), gaussian(&mut rng), gaussian(&mut rng));
+            Body::new(mass, position, rotation + dispersion * (DISK_DISPERSION * circular_speed))
+        })
+        .collect();
+
+    to_com_frame(&mut bodies);
+    Body::assign_ids(&mut bodies);
+    bodies
+}
+
+// 冷塌缩检验: 半径 radius 的均匀球内均匀分布的静止粒子, 处于质心系.
+// 球在自由落体时间 t_ff = (pi / 2) sqrt(R^3 / (2 G M)) 附近收缩到最小, 随后反弹并经暴烈弛豫趋于位力平衡 (2K / |W| -> 1).
+// 总能量应在整个过程中守恒, 是检验积分器与步长的经典算例. 最大收缩时粒子间距极小,
+// 若软化长度远小于此时的平均间距 (约 R N^{-1/3} 的若干分之一), 能量误差会在该时刻突然增大,
+// 这本身就演示了软化长度与步长的选择对近距离交会的影响
+pub fn generate_cold_sphere(n: usize, total_mass: Real, radius: Real, seed: u64) -> Vec<Body> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mass = total_mass / n as Real;
+
+    let mut bodies: Vec<Body> = (0..n)
+        .map(|_| {
+            // 体积均匀分布: 半径按 u^(1/3) 抽样
+            let r = radius * rng.gen::<Real>().cbrt();
+            Body::new(mass, random_direction(&mut rng) * r, Vec3::ZERO)
+        })
+        .collect();
+
+    to_com_frame(&mut bodies);
+    Body::assign_ids(&mut bodies);
+    bodies
+}
+
+// 二体 Kepler 轨道, 两体位于近心点, 处于质心系.
+// 近心距 r_p = a (1 - e), 相对速度由活力公式 v^2 = G M (2 / r - 1 / a) 给出.
+pub fn generate_kepler(m1: Real, m2: Real, semi_major: Real, eccentricity: Real, g: Real) -> Vec<Body> {
+    let total_mass = m1 + m2;
+    let separation = semi_major * (1.0 - eccentricity);
+    let relative_speed = (g * total_mass * (2.0 / separation - 1.0 / semi_major)).sqrt();
+
+    let mut bodies = vec![
+        Body::new(
+            m1,
+            Vec3::new(-separation * m2 / total_mass, 0.0, 0.0),
+            Vec3::new(0.0, -relative_speed * m2 / total_mass, 0.0),
+        ),
+        Body::new(
+            m2,
+            Vec3::new(separation * m1 / total_mass, 0.0, 0.0),
+            Vec3::new(0.0, relative_speed * m1 / total_mass, 0.0),
+        ),
+    ];
+    Body::assign_ids(&mut bodies);
+    bodies
+}
+
+// 层级三体: 紧密的内双星加上绕其质心运行的环双星行星, 三者共面顺行, 各自从近心点出发.
+// 内双星在自身质心系中按 generate_kepler 放置, 再整体作为质量 m1 + m2 的一体与行星组成外轨道;
+// 外轨道同样在质心系中, 因此三体的总质心与总动量为零
+#[allow(clippy::too_many_arguments)]
+pub fn generate_hierarchical(
+    m1: Real,
+    m2: Real,
+    m_planet: Real,
+    inner_a: Real,
+    outer_a: Real,
+    inner_e: Real,
+    outer_e: Real,
+    g: Real,
+) -> Vec<Body> {
+    let outer = generate_kepler(m1 + m2, m_planet, outer_a, outer_e, g);
+    let mut bodies = generate_kepler(m1, m2, inner_a, inner_e, g);
+    for body in &mut bodies {
+        body.position += outer[0].position;
+        body.velocity += outer[0].velocity;
+    }
+    bodies.push(outer[1].clone());
+    Body::assign_ids(&mut bodies);
+    bodies
+}
+
+// 环双星轨道稳定的临界半长轴 (Holman & Wiegert 1999, 拟合范围 0 <= e <= 0.7, 0.1 <= mu <= 0.5):
+// 外轨道半长轴小于它的行星通常在 1e4 个内双星周期内被抛出或碰撞
+pub fn circumbinary_critical_semi_major(m1: f64, m2: f64, inner_a: f64, inner_e: f64) -> f64 {
+    let (mu, e) = (m2 / (m1 + m2), inner_e);
+    inner_a * (1.60 + 5.10 * e - 2.22 * e * e + 4.12 * mu - 4.27 * e * mu - 5.09 * mu * mu + 4.61 * e * e * mu * mu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{momentum_diagnostics, total_energy};
+    use crate::precision::to_f64;
+    use crate::force::test_params;
+    use crate::integrators::{integrate_step, update_forces, Integrator};
+
+    #[test]
+    fn plummer_sphere_is_in_virial_equilibrium() {
+        let bodies = generate_plummer(2000, 1.0, 1.0, 42, 1.0);
+        let (kinetic, potential, _) = total_energy(&bodies, &test_params(1.0, 0.0));
+        let ratio = 2.0 * kinetic / potential.abs();
+        assert!((ratio - 1.0).abs() < 0.05, "2T/|W| = {}", ratio);
+    }
+
+    #[test]
+    fn kepler_orbit_has_the_expected_energy_and_period() {
+        let (m1, m2, a, e, g) = (0.8, 0.2, 1.0, 0.5, 1.0);
+        let bodies = generate_kepler(m1, m2, a, e, g);
+        let params = test_params(g, 0.0);
+        let (_, _, energy) = total_energy(&bodies, &params);
+        let expected = -to_f64(g * m1 * m2 / (2.0 * a));
+        assert!((energy - expected).abs() < 1e-12 * expected.abs(), "E = {}, expected {}", energy, expected);
+
+        // Kepler 第三定律 P = 2 pi sqrt(a^3 / (G M)): 积分一个周期后两体回到近心点
+        let period = TAU * (a * a * a / (g * (m1 + m2))).sqrt();
+        let steps = 2000;
+        let mut state = bodies.clone();
+        update_forces(&mut state, Integrator::Yoshida4, &params);
+        for _ in 0..steps {
+            integrate_step(&mut state, Integrator::Yoshida4, period / steps as Real, &params);
+        }
+        for (start, end) in bodies.iter().zip(&state) {
+            assert!(start.position.distance(end.position) < 1e-4 * a, "{:?} -> {:?}", start.position, end.position);
+        }
+    }
+
+    #[test]
+    fn disk_rotates_about_z_with_circular_speed_set_by_enclosed_mass() {
+        let bodies = generate_disk(5000, 1.0, 1.0, 0.1, 5, 1.0);
+        let (_, angular_momentum) = momentum_diagnostics(&bodies);
+        assert!(angular_momentum.z / angular_momentum.length() > 0.99, "L = {:?}", angular_momentum);
+
+        // 各圆柱半径环内平均的 v_phi^2 应接近 G M(<R) / R, 离中心太近的环样本少且受弥散影响大, 不检查
+        let mut radii: Vec<(f64, f64)> = bodies
+            .iter()
+            .map(|b| {
+                let (x, y) = (to_f64(b.position.x), to_f64(b.position.y));
+                let radius = x.hypot(y);
+                (radius, (x * to_f64(b.velocity.y) - y * to_f64(b.velocity.x)) / radius)
+            })
+            .collect();
+        radii.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mass = 1.0 / bodies.len() as f64;
+        for (index, ring) in radii.chunks(500).enumerate().skip(1).take(7) {
+            let start = index * 500;
+            let radius = ring.iter().map(|r| r.0).sum::<f64>() / ring.len() as f64;
+            let speed_sq = ring.iter().map(|r| r.1 * r.1).sum::<f64>() / ring.len() as f64;
+            let expected = (start + ring.len() / 2) as f64 * mass / radius;
+            assert!((speed_sq / expected - 1.0).abs() < 0.1, "R = {}: v^2 = {}, expected {}", radius, speed_sq, expected);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_identical_initial_conditions() {
+        assert_eq!(generate_plummer(200, 1.0, 1.0, 42, 1.0), generate_plummer(200, 1.0, 1.0, 42, 1.0));
+        assert_eq!(generate_disk(200, 1.0, 1.0, 0.1, 42, 1.0), generate_disk(200, 1.0, 1.0, 0.1, 42, 1.0));
+        assert_eq!(generate_cold_sphere(200, 1.0, 1.0, 42), generate_cold_sphere(200, 1.0, 1.0, 42));
+        assert_ne!(generate_plummer(200, 1.0, 1.0, 42, 1.0), generate_plummer(200, 1.0, 1.0, 43, 1.0));
+    }
+
+    #[test]
+    fn hierarchical_triple_conserves_energy_and_inner_period_follows_kepler() {
+        let (m1, m2, inner_a) = (1.0, 0.5, 1.0);
+        let params = test_params(1.0, 0.0);
+        let mut bodies = generate_hierarchical(m1, m2, 1e-3, inner_a, 5.0, 0.1, 0.05, 1.0);
+        let initial_energy = total_energy(&bodies, &params).2;
+        let (momentum, initial_l) = momentum_diagnostics(&bodies);
+        assert!(momentum.length() < 1e-15, "P = {:?}", momentum);
+
+        // Kepler 第三定律: P = 2 pi sqrt(a^3 / (G (m1 + m2)))
+        let period = TAU * (inner_a.powi(3) / (m1 + m2)).sqrt();
+        let dt = period / 1000.0;
+        let start = bodies[1].position - bodies[0].position;
+        let mut crossings = Vec::new();
+        let mut previous = 0.0;
+        update_forces(&mut bodies, Integrator::Yoshida4, &params);
+        for step in 1..=10_500 {
+            integrate_step(&mut bodies, Integrator::Yoshida4, dt, &params);
+            // 内双星的相对位置矢量每转过起始方向一次记一次, 时间按线性插值
+            let relative = bodies[1].position - bodies[0].position;
+            let side = start.cross(relative).z;
+            if previous < 0.0 && side >= 0.0 && start.dot(relative) > 0.0 {
+                crossings.push((step as Real - side / (side - previous)) * dt);
+            }
+            previous = side;
+        }
+
+        let energy = total_energy(&bodies, &params).2;
+        let (_, angular_momentum) = momentum_diagnostics(&bodies);
+        assert!(((energy - initial_energy) / initial_energy).abs() < 1e-8, "E {} -> {}", initial_energy, energy);
+        assert!(angular_momentum.distance(initial_l) < 1e-8 * initial_l.length(), "L {:?} -> {:?}", initial_l, angular_momentum);
+        assert_eq!(crossings.len(), 10);
+        let measured = (crossings[9] - crossings[0]) / 9.0;
+        assert!((measured / period - 1.0).abs() < 1e-3, "period {} vs Kepler {}", measured, period);
+    }
+}
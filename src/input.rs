@@ -0,0 +1,356 @@
+// 读取初始条件
+use crate::precision::to_f64;
+use crate::{Body, Real, SimError, Vec3};
+use std::collections::HashMap;
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+// CSV 初始条件需要的列, 无表头时按此顺序排列
+const CSV_COLUMNS: [&str; 7] = ["mass", "x", "y", "z", "vx", "vy", "vz"];
+
+// 以 .gz 结尾的文件按 gzip 压缩读写
+pub fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "gz")
+}
+
+// 打开输入文件, .gz 文件透明解压
+pub fn open_input(path: &Path) -> Result<Box<dyn Read>, SimError> {
+    let file = BufReader::new(File::open(path).map_err(|e| SimError::open(path, e))?);
+    Ok(if is_gzip(path) { Box::new(BufReader::new(GzDecoder::new(file))) } else { Box::new(file) })
+}
+
+// 读取粒子 (质量, 位置, 速度), 编号为粒子在文件中的下标.
+// 格式由扩展名决定: .csv 为 CSV, .gadget 为 Gadget-2 快照, .tipsy 为 TIPSY, 其余按 JSON 读取;
+// 压缩文件看去掉 .gz 后的扩展名 (如 particles.json.gz)
+pub fn load_bodies(path: &Path) -> Result<Vec<Body>, SimError> {
+    let format_path = if is_gzip(path) { Path::new(path.file_stem().unwrap_or_default()) } else { path };
+    let mut bodies = match format_path.extension().and_then(|e| e.to_str()) {
+        Some("gadget") => return read_gadget(path),
+        Some("tipsy") => return read_tipsy(path),
+        Some("csv") => load_bodies_csv(open_input(path)?)?,
+        _ => serde_json::from_reader(open_input(path)?)?,
+    };
+    Body::assign_ids(&mut bodies);
+    Ok(bodies)
+}
+
+// 每行一个粒子. 首行若不是数字则视为表头, 按列名取 mass,x,y,z,vx,vy,vz (其余列如 id, time 忽略),
+// 因此 write_snapshot_csv 写出的快照也能直接读回; 无表头时必须恰好按上述顺序排列
+fn load_bodies_csv(file: impl Read) -> Result<Vec<Body>, SimError> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).trim(csv::Trim::All).from_reader(file);
+    let mut columns: [usize; 7] = std::array::from_fn(|i| i);
+    let mut bodies = Vec::new();
+
+    // 复用同一个记录缓冲区, 避免每行分配
+    let mut record = csv::StringRecord::new();
+    for row in 0.. {
+        let more = reader.read_record(&mut record).map_err(|e| SimError::InvalidCsv {
+            line: e.position().map_or(0, |p| p.line()),
+            message: e.to_string(),
+        })?;
+        if !more {
+            break;
+        }
+        let line = record.position().map_or(0, |p| p.line());
+
+        if row == 0 && record.iter().any(|field| field.parse::<f64>().is_err()) {
+            for (column, name) in columns.iter_mut().zip(CSV_COLUMNS) {
+                *column = record.iter().position(|field| field == name).ok_or_else(|| SimError::InvalidCsv {
+                    line,
+                    message: format!("header has no '{}' column", name),
+                })?;
+            }
+            continue;
+        }
+
+        let mut values: [Real; 7] = [0.0; 7];
+        for ((value, &column), name) in values.iter_mut().zip(&columns).zip(CSV_COLUMNS) {
+            let field = record.get(column).ok_or_else(|| SimError::InvalidCsv {
+                line,
+                message: format!("missing '{}' column", name),
+            })?;
+            *value = field.parse().map_err(|_| SimError::InvalidCsv {
+                line,
+                message: format!("cannot parse {} '{}' as a number", name, field),
+            })?;
+        }
+        let [mass, x, y, z, vx, vy, vz] = values;
+        bodies.push(Body::new(mass, Vec3::new(x, y, z), Vec3::new(vx, vy, vz)));
+    }
+    Ok(bodies)
+}
+
+// Gadget-2 快照中的粒子种类数 (气体, 暗晕, 盘, 核球, 恒星, 边界)
+const GADGET_TYPES: usize = 6;
+
+// 按 Fortran 无格式记录依次读取 Gadget-2 快照的各个块: 每块前后各有一个 4 字节小端长度标记
+struct GadgetRecords<'a> {
+    data: &'a [u8],
+    offset: usize,
+    // SnapFormat = 2 在每块前多一个 8 字节的记录, 存放 4 字符的块名与下一块的长度; 由第一个记录的长度判断
+    labelled: bool,
+}
+
+impl<'a> GadgetRecords<'a> {
+    fn next(&mut self, name: &str) -> Result<&'a [u8], SimError> {
+        if self.offset == 0 {
+            self.labelled = self.data.get(..4) == Some(&8u32.to_le_bytes());
+        }
+        if self.labelled {
+            self.record(name)?;
+        }
+        self.record(name)
+    }
+
+    fn record(&mut self, name: &str) -> Result<&'a [u8], SimError> {
+        let truncated = || SimError::InvalidGadget(format!("file ends inside the {} block", name));
+        let marker = |offset: usize| -> Result<usize, SimError> {
+            let bytes = self.data.get(offset..offset + 4).ok_or_else(truncated)?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+        };
+        let size = marker(self.offset)?;
+        let start = self.offset + 4;
+        let body = self.data.get(start..start + size).ok_or_else(truncated)?;
+        let end_marker = marker(start + size)?;
+        if end_marker != size {
+            return Err(SimError::InvalidGadget(format!(
+                "record markers of the {} block do not match ({} and {} bytes)",
+                name, size, end_marker
+            )));
+        }
+        self.offset = start + size + 4;
+        Ok(body)
+    }
+}
+
+// count 个浮点数, 按块长度判断是单精度还是双精度
+fn gadget_floats(block: &[u8], count: usize, name: &str) -> Result<Vec<Real>, SimError> {
+    if block.len() == 4 * count {
+        Ok(block.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap()) as Real).collect())
+    } else if block.len() == 8 * count {
+        Ok(block.chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap()) as Real).collect())
+    } else {
+        Err(SimError::InvalidGadget(format!("{} block has {} bytes, expected {} floats", name, block.len(), count)))
+    }
+}
+
+// 读取小端的 Gadget-2 快照 (SnapFormat 1 或 2, 单精度或双精度) 中的位置, 速度与质量.
+// 粒子种类 0-5 存为 species; 文件头中质量表为零的种类从 MASS 块读取各自的质量.
+// 数值按原样解释为 --units 选定单位制中的量, 不做 Gadget 内部单位 (kpc/h, 1e10 Msun/h, km/s) 的换算;
+// 宇宙学快照的速度是 sqrt(a) dx/dt, 也不做修正. 只支持单个文件的快照, 忽略 ID 及之后的块
+pub fn read_gadget(path: &Path) -> Result<Vec<Body>, SimError> {
+    let mut data = Vec::new();
+    open_input(path)?.read_to_end(&mut data)?;
+    let mut bodies = parse_gadget(&data)?;
+    Body::assign_ids(&mut bodies);
+    Ok(bodies)
+}
+
+fn parse_gadget(data: &[u8]) -> Result<Vec<Body>, SimError> {
+    let mut records = GadgetRecords { data, offset: 0, labelled: false };
+    let header = records.next("HEAD")?;
+    if header.len() != 256 {
+        return Err(SimError::InvalidGadget(format!(
+            "header block has {} bytes, expected 256 (big-endian files are not supported)",
+            header.len()
+        )));
+    }
+    let u32_at = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    let f64_at = |offset: usize| f64::from_le_bytes(header[offset..offset + 8].try_into().unwrap());
+    let counts: [usize; GADGET_TYPES] = std::array::from_fn(|t| u32_at(4 * t) as usize);
+    let table_masses: [f64; GADGET_TYPES] = std::array::from_fn(|t| f64_at(24 + 8 * t));
+    let num_files = u32_at(124);
+    if num_files > 1 {
+        return Err(SimError::InvalidGadget(format!("snapshots split over {} files are not supported", num_files)));
+    }
+
+    let total: usize = counts.iter().sum();
+    let positions = gadget_floats(records.next("POS")?, 3 * total, "POS")?;
+    let velocities = gadget_floats(records.next("VEL")?, 3 * total, "VEL")?;
+    records.next("ID")?;
+    let variable_mass: usize = (0..GADGET_TYPES).filter(|&t| table_masses[t] == 0.0).map(|t| counts[t]).sum();
+    let masses = if variable_mass > 0 { gadget_floats(records.next("MASS")?, variable_mass, "MASS")? } else { Vec::new() };
+
+    // 各块中的粒子都按种类依次排列
+    let mut bodies = Vec::with_capacity(total);
+    let mut variable = masses.into_iter();
+    for (species, &count) in counts.iter().enumerate() {
+        for _ in 0..count {
+            let i = bodies.len();
+            let mass = if table_masses[species] == 0.0 { variable.next().unwrap_or_default() } else { table_masses[species] as Real };
+            let position = Vec3::new(positions[3 * i], positions[3 * i + 1], positions[3 * i + 2]);
+            let velocity = Vec3::new(velocities[3 * i], velocities[3 * i + 1], velocities[3 * i + 2]);
+            bodies.push(Body { species: species as u8, ..Body::new(mass, position, velocity) });
+        }
+    }
+    Ok(bodies)
+}
+
+// TIPSY 文件头 (time, nbodies, ndim, nsph, ndark, nstar, 4 字节填充) 与暗物质粒子记录
+// (mass, pos[3], vel[3], eps, phi) 的字节数
+pub const TIPSY_HEADER_BYTES: usize = 32;
+pub const TIPSY_DARK_BYTES: usize = 36;
+
+// 读取 TIPSY 文件中的暗物质粒子: 标准格式为大端, 也接受小端的本机格式 (由 ndim 是否在 1..=3 判断).
+// eps 为正时存为粒子自身的软化长度, phi 忽略. 只支持纯暗物质的文件, 含气体或恒星粒子时报错
+pub fn read_tipsy(path: &Path) -> Result<Vec<Body>, SimError> {
+    let mut data = Vec::new();
+    open_input(path)?.read_to_end(&mut data)?;
+    let mut bodies = parse_tipsy(&data)?;
+    Body::assign_ids(&mut bodies);
+    Ok(bodies)
+}
+
+fn parse_tipsy(data: &[u8]) -> Result<Vec<Body>, SimError> {
+    let header = data
+        .get(..TIPSY_HEADER_BYTES)
+        .ok_or_else(|| SimError::InvalidTipsy(format!("file has {} bytes, shorter than the header", data.len())))?;
+    let big_endian = (1..=3).contains(&i32::from_be_bytes(header[12..16].try_into().unwrap()));
+    let int_at = |offset: usize| {
+        let bytes = data[offset..offset + 4].try_into().unwrap();
+        if big_endian { i32::from_be_bytes(bytes) } else { i32::from_le_bytes(bytes) }
+    };
+    let float_at = |offset: usize| {
+        let bytes = data[offset..offset + 4].try_into().unwrap();
+        (if big_endian { f32::from_be_bytes(bytes) } else { f32::from_le_bytes(bytes) }) as Real
+    };
+
+    let (nbodies, ndim, nsph, ndark, nstar) = (int_at(8), int_at(12), int_at(16), int_at(20), int_at(24));
+    if !(1..=3).contains(&ndim) {
+        return Err(SimError::InvalidTipsy(format!("ndim is {} in either byte order", ndim)));
+    }
+    if nsph != 0 || nstar != 0 {
+        return Err(SimError::InvalidTipsy(format!(
+            "only dark-matter particles are supported, the file has {} gas and {} star particles",
+            nsph, nstar
+        )));
+    }
+    if ndark < 0 || nbodies != ndark {
+        return Err(SimError::InvalidTipsy(format!("nbodies = {} does not match ndark = {}", nbodies, ndark)));
+    }
+    let expected = TIPSY_HEADER_BYTES + ndark as usize * TIPSY_DARK_BYTES;
+    if data.len() < expected {
+        return Err(SimError::InvalidTipsy(format!("{} dark particles need {} bytes, the file has {}", ndark, expected, data.len())));
+    }
+
+    Ok((0..ndark as usize)
+        .map(|i| {
+            let record = TIPSY_HEADER_BYTES + i * TIPSY_DARK_BYTES;
+            let value = |field: usize| float_at(record + 4 * field);
+            let position = Vec3::new(value(1), value(2), value(3));
+            let velocity = Vec3::new(value(4), value(5), value(6));
+            let eps = value(7);
+            Body { softening: (eps > 0.0).then_some(eps), ..Body::new(value(0), position, velocity) }
+        })
+        .collect())
+}
+
+// 检查质量为有限正数, 位置与速度的每个分量都有限; 返回第一个不合法的粒子及字段
+pub fn validate_bodies(bodies: &[Body]) -> Result<(), SimError> {
+    for (index, body) in bodies.iter().enumerate() {
+        if !(body.mass.is_finite() && body.mass > 0.0) {
+            return Err(SimError::InvalidBody { index, field: "mass", value: to_f64(body.mass) });
+        }
+        let components = [
+            ("position.x", body.position.x),
+            ("position.y", body.position.y),
+            ("position.z", body.position.z),
+            ("velocity.x", body.velocity.x),
+            ("velocity.y", body.velocity.y),
+            ("velocity.z", body.velocity.z),
+        ];
+        for (field, value) in components {
+            if !value.is_finite() {
+                return Err(SimError::InvalidBody { index, field, value: to_f64(value) });
+            }
+        }
+        if let Some(softening) = body.softening {
+            if !(softening.is_finite() && softening >= 0.0) {
+                return Err(SimError::InvalidBody { index, field: "softening", value: to_f64(softening) });
+            }
+        }
+        if let Some(radius) = body.radius {
+            if !(radius.is_finite() && radius >= 0.0) {
+                return Err(SimError::InvalidBody { index, field: "radius", value: to_f64(radius) });
+            }
+        }
+    }
+    Ok(())
+}
+
+// 位置完全相同的粒子对 (先出现者, 后出现者); 受力计算会跳过这些粒子之间的相互作用
+pub fn duplicate_positions(bodies: &[Body]) -> Vec<(usize, usize)> {
+    let mut seen = HashMap::with_capacity(bodies.len());
+    let mut duplicates = Vec::new();
+    for (index, body) in bodies.iter().enumerate() {
+        // 加 0.0 把 -0.0 统一为 0.0, 与 == 的比较结果一致
+        let key = (body.position + 0.0).to_array().map(Real::to_bits);
+        if let Some(&first) = seen.get(&key) {
+            duplicates.push((first, index));
+        } else {
+            seen.insert(key, index);
+        }
+    }
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::generate_plummer;
+    use crate::output::write_tipsy;
+
+    // 一个 Fortran 无格式记录: 前后各一个 4 字节的长度标记
+    fn record(payload: &[u8]) -> Vec<u8> {
+        let marker = (payload.len() as u32).to_le_bytes();
+        [&marker[..], payload, &marker[..]].concat()
+    }
+
+    #[test]
+    fn hand_built_gadget_snapshot_loads() {
+        // SnapFormat 1, 三个暗晕 (种类 1) 粒子, 质量取自文件头的质量表, 单精度位置与速度
+        let mut header = [0u8; 256];
+        header[4..8].copy_from_slice(&3u32.to_le_bytes());
+        header[32..40].copy_from_slice(&0.5f64.to_le_bytes());
+        header[124..128].copy_from_slice(&1u32.to_le_bytes());
+        let floats = |values: &[f32]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+        let positions = floats(&[1.0, 2.0, 3.0, -1.0, 0.0, 0.5, 4.0, 4.0, 4.0]);
+        let velocities = floats(&[0.0, 0.0, 1.0, 0.25, 0.0, 0.0, 0.0, -2.0, 0.0]);
+        let ids: Vec<u8> = (1u32..=3).flat_map(|id| id.to_le_bytes()).collect();
+        let data = [record(&header), record(&positions), record(&velocities), record(&ids)].concat();
+
+        let path = std::env::temp_dir().join(format!("nbody_gadget_test_{}.gadget", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let bodies = load_bodies(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(bodies.len(), 3);
+        assert!(bodies.iter().all(|b| b.mass == 0.5 && b.species == 1));
+        assert_eq!(bodies[1].position, Vec3::new(-1.0, 0.0, 0.5));
+        assert_eq!(bodies[2].velocity, Vec3::new(0.0, -2.0, 0.0));
+        assert_eq!(bodies.iter().map(|b| b.id).collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn tipsy_round_trip_keeps_bodies_to_single_precision() {
+        let mut bodies = generate_plummer(50, 1.0, 1.0, 9, 1.0);
+        bodies[3].softening = Some(0.02);
+        let path = std::env::temp_dir().join(format!("nbody_tipsy_test_{}.tipsy", std::process::id()));
+        write_tipsy(&bodies, &path, 1.5).unwrap();
+        let loaded = load_bodies(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // TIPSY 以单精度保存, 读回的值等于原值舍入到 f32
+        let single = |x: Real| to_f64(x) as f32 as Real;
+        assert_eq!(loaded.len(), bodies.len());
+        for (original, loaded) in bodies.iter().zip(&loaded) {
+            assert_eq!(loaded.id, original.id);
+            assert_eq!(loaded.mass, single(original.mass));
+            assert_eq!(loaded.position, Vec3::new(single(original.position.x), single(original.position.y), single(original.position.z)));
+            assert_eq!(loaded.velocity, Vec3::new(single(original.velocity.x), single(original.velocity.y), single(original.velocity.z)));
+            assert_eq!(loaded.softening, original.softening.map(single));
+        }
+    }
+}
@@ -0,0 +1,457 @@
+// 时间积分器
+use crate::force::{minimum_image, update_acc_and_jerk};
+use crate::precision::to_f64;
+use crate::{update_accelerations, Body, ForceParams, Real, Vec3};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Kick: 用当前加速度更新速度
+pub fn kick(bodies: &mut [Body], dt: Real) {
+    bodies.par_iter_mut().for_each(|body| body.velocity += body.acceleration * dt);
+}
+
+// Drift: 用当前速度更新位置
+pub fn drift(bodies: &mut [Body], dt: Real) {
+    bodies.par_iter_mut().for_each(|body| body.position += body.velocity * dt);
+}
+
+// 在位置更新 (drift) 之后施加盒子边界
+fn apply_boundary(bodies: &mut [Body], params: &ForceParams) {
+    params.boundary.apply(bodies);
+}
+
+// Leapfrog 积分法 (kick-drift-kick)
+// 要求进入时 bodies 的加速度已是当前位置对应的值
+pub fn leapfrog_integrator(bodies: &mut [Body], dt: Real, params: &ForceParams) {
+    kick(bodies, dt / 2.0);
+    drift(bodies, dt);
+    apply_boundary(bodies, params);
+    update_accelerations(bodies, params);
+    kick(bodies, dt / 2.0);
+}
+
+// Velocity Verlet 积分法: 先更新位置, 重新计算一次加速度, 再用新旧加速度的平均更新速度
+pub fn velocity_verlet_step(bodies: &mut [Body], dt: Real, params: &ForceParams) {
+    bodies.par_iter_mut().for_each(|body| {
+        body.position += body.velocity * dt + body.acceleration * (0.5 * dt * dt);
+        // 先加上旧加速度的一半贡献
+        body.velocity += body.acceleration * (0.5 * dt);
+    });
+
+    apply_boundary(bodies, params);
+    update_accelerations(bodies, params);
+
+    bodies.par_iter_mut().for_each(|body| body.velocity += body.acceleration * (0.5 * dt));
+}
+
+// Yoshida (1990) 四阶辛积分法: 步长依次为 w1 dt, w0 dt, w1 dt 的三次 Leapfrog 复合,
+// 其中 w1 = 1 / (2 - 2^(1/3)), w0 = -2^(1/3) / (2 - 2^(1/3)), 中间一步向后推进.
+// 每步需要三次受力计算, 能量误差有界且比 Leapfrog 高两阶
+pub fn yoshida4_step(bodies: &mut [Body], dt: Real, params: &ForceParams) {
+    let cbrt2 = (2.0 as Real).cbrt();
+    let w1 = 1.0 / (2.0 - cbrt2);
+    let w0 = -cbrt2 / (2.0 - cbrt2);
+    leapfrog_integrator(bodies, w1 * dt, params);
+    leapfrog_integrator(bodies, w0 * dt, params);
+    leapfrog_integrator(bodies, w1 * dt, params);
+}
+
+// 四阶 Runge-Kutta 积分法
+// 每个中间阶段在临时副本上计算加速度, 不会在步内修改真实状态.
+// 注意 RK4 不是辛积分器, 长时间运行时能量会持续漂移, 只适合短轨道的高精度验证.
+pub fn rk4_step(bodies: &mut [Body], dt: Real, params: &ForceParams) {
+    let x0: Vec<Vec3> = bodies.iter().map(|b| b.position).collect();
+    let v0: Vec<Vec3> = bodies.iter().map(|b| b.velocity).collect();
+    let mut stage = bodies.to_vec();
+
+    // 在 x0 + h * dx, v0 + h * dv 处求斜率 (dx/dt, dv/dt)
+    let mut slope = |kx: &[Vec3], kv: &[Vec3], h: Real| -> (Vec<Vec3>, Vec<Vec3>) {
+        stage.par_iter_mut().enumerate().for_each(|(i, body)| {
+            body.position = x0[i] + kx[i] * h;
+            body.velocity = v0[i] + kv[i] * h;
+        });
+        update_accelerations(&mut stage, params);
+        (
+            stage.iter().map(|b| b.velocity).collect(),
+            stage.iter().map(|b| b.acceleration).collect(),
+        )
+    };
+
+    // k1 直接使用当前状态
+    let k1x = v0.clone();
+    let k1v: Vec<Vec3> = bodies.iter().map(|b| b.acceleration).collect();
+    let (k2x, k2v) = slope(&k1x, &k1v, dt / 2.0);
+    let (k3x, k3v) = slope(&k2x, &k2v, dt / 2.0);
+    let (k4x, k4v) = slope(&k3x, &k3v, dt);
+
+    bodies.par_iter_mut().enumerate().for_each(|(i, body)| {
+        body.position = x0[i] + (k1x[i] + 2.0 * k2x[i] + 2.0 * k3x[i] + k4x[i]) * (dt / 6.0);
+        body.velocity = v0[i] + (k1v[i] + 2.0 * k2v[i] + 2.0 * k3v[i] + k4v[i]) * (dt / 6.0);
+    });
+
+    // 为下一步准备当前位置的加速度; 中间阶段不施加边界, 最小镜像位移与折回无关
+    apply_boundary(bodies, params);
+    update_accelerations(bodies, params);
+}
+
+// Dormand-Prince 5(4) 的 Butcher 表: 第 s 级的系数 a_sj (j < s), 第七级即五阶解 (FSAL), 以及五阶与四阶权重之差
+const DOPRI5_A: [&[f64]; 6] = [
+    &[1.0 / 5.0],
+    &[3.0 / 40.0, 9.0 / 40.0],
+    &[44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0],
+    &[19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0],
+    &[9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0],
+    &[35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+];
+const DOPRI5_ERROR: [f64; 7] =
+    [71.0 / 57600.0, 0.0, -71.0 / 16695.0, 71.0 / 1920.0, -17253.0 / 339200.0, 22.0 / 525.0, -1.0 / 40.0];
+
+// 没有给出容差时 (integrate_step) 使用的局部误差容差, 也是 --dopri-tolerance 的默认值
+pub const DOPRI5_TOLERANCE: f64 = 1e-9;
+
+// 带嵌入误差估计的 Dormand-Prince 5(4) 积分法, 用内部子步推进 dt, 返回 (接受的子步数, 受力计算次数).
+// 每个子步用七级 (第一级沿用上一子步末的加速度) 同时得到五阶解与四阶解, 两者之差作为局部误差,
+// 以所有粒子中最大的 |x| 与 |v| 为尺度; 误差超过 tolerance 时拒绝并缩短子步, 否则接受五阶解,
+// 下一个子步长按 0.9 (tolerance / error)^(1/5) 调整, 在 [0.2, 5] 倍之间且不超过 dt.
+// 子步长从 dt 开始, 每次调用都重新选择; 短于 dt 的 1e-12 倍时不再拒绝, 以免无法前进.
+// 与 RK4 一样不是辛积分器, 能量会长期漂移, 适合短时间, 要求精度的运行 (如近距离交会)
+pub fn dopri5_step(bodies: &mut [Body], dt: Real, tolerance: Real, params: &ForceParams) -> (usize, usize) {
+    let mut stage = bodies.to_vec();
+    let (mut done, mut h) = (0.0, dt);
+    let (mut steps, mut evaluations) = (0, 0);
+    loop {
+        // 最后一个子步正好落在 dt 上
+        let last = h.abs() >= (dt - done).abs();
+        if last {
+            h = dt - done;
+        }
+        let x0: Vec<Vec3> = bodies.iter().map(|b| b.position).collect();
+        let v0: Vec<Vec3> = bodies.iter().map(|b| b.velocity).collect();
+        let mut kx = vec![v0.clone()];
+        let mut kv = vec![bodies.iter().map(|b| b.acceleration).collect::<Vec<Vec3>>()];
+        for row in DOPRI5_A {
+            stage.par_iter_mut().enumerate().for_each(|(i, body)| {
+                let (dx, dv) = row.iter().enumerate().fold((Vec3::ZERO, Vec3::ZERO), |(dx, dv), (j, &a)| {
+                    (dx + kx[j][i] * a as Real, dv + kv[j][i] * a as Real)
+                });
+                body.position = x0[i] + dx * h;
+                body.velocity = v0[i] + dv * h;
+            });
+            update_accelerations(&mut stage, params);
+            kx.push(stage.iter().map(|b| b.velocity).collect());
+            kv.push(stage.iter().map(|b| b.acceleration).collect());
+        }
+        evaluations += DOPRI5_A.len();
+
+        // 误差尺度也考虑一个子步内的变化量, 使静止或位于原点的系统仍有非零的尺度
+        let max_length = |values: &[Vec3]| values.iter().map(|v| v.length()).fold(0.0, Real::max);
+        let position_scale = max_length(&x0).max(max_length(&v0) * h.abs()).max(Real::MIN_POSITIVE);
+        let velocity_scale = max_length(&v0).max(max_length(&kv[0]) * h.abs()).max(Real::MIN_POSITIVE);
+        let error = (0..bodies.len())
+            .into_par_iter()
+            .map(|i| {
+                let (ex, ev) = DOPRI5_ERROR.iter().enumerate().fold((Vec3::ZERO, Vec3::ZERO), |(ex, ev), (j, &e)| {
+                    (ex + kx[j][i] * e as Real, ev + kv[j][i] * e as Real)
+                });
+                ((ex * h).length() / position_scale).max((ev * h).length() / velocity_scale)
+            })
+            .reduce(|| 0.0, Real::max)
+            / tolerance;
+
+        let factor = if error > 0.0 { (0.9 * error.powf(-0.2)).clamp(0.2, 5.0) } else { 5.0 };
+        if error <= 1.0 || h.abs() <= dt.abs() * 1e-12 {
+            // 第七级的状态即五阶解, 其加速度留给下一个子步的第一级
+            for (body, staged) in bodies.iter_mut().zip(&stage) {
+                (body.position, body.velocity, body.acceleration) = (staged.position, staged.velocity, staged.acceleration);
+            }
+            apply_boundary(bodies, params);
+            done += h;
+            steps += 1;
+            if last {
+                return (steps, evaluations);
+            }
+        }
+        h = (h * factor).clamp(-dt.abs(), dt.abs());
+    }
+}
+
+// 四阶 Hermite 预测-校正积分法 (Makino & Aarseth 1992), 每步一次受力计算:
+// 用 a, j 的 Taylor 展开预测位置与速度, 在预测点求新的 a1, j1, 再用 Hermite 插值校正.
+// 要求进入时 bodies 的加速度与 jerk 都是当前状态对应的值; 结束时保留预测点上的 a1, j1.
+// 受力总是用直接求和, 忽略 params 中的方法, 适合近距离交会重要的少体问题
+pub fn hermite_step(bodies: &mut [Body], dt: Real, params: &ForceParams) {
+    let old: Vec<_> = bodies.iter().map(|b| (b.position, b.velocity, b.acceleration, b.jerk)).collect();
+
+    // 预测
+    let dt2 = dt * dt;
+    bodies.par_iter_mut().for_each(|body| {
+        body.position += body.velocity * dt + body.acceleration * (dt2 / 2.0) + body.jerk * (dt2 * dt / 6.0);
+        body.velocity += body.acceleration * dt + body.jerk * (dt2 / 2.0);
+    });
+
+    // 求值
+    update_acc_and_jerk(bodies, params.g, params.softening);
+
+    // 校正: 先校正速度, 位置再使用校正后的速度
+    bodies.par_iter_mut().zip(old).for_each(|(body, (x0, v0, a0, j0))| {
+        let (a1, j1) = (body.acceleration, body.jerk);
+        body.velocity = v0 + (a0 + a1) * (dt / 2.0) + (j0 - j1) * (dt2 / 12.0);
+        body.position = x0 + (v0 + body.velocity) * (dt / 2.0) + (a0 - a1) * (dt2 / 12.0);
+    });
+}
+
+// 自适应全局时间步: 每个粒子取 sqrt(eps/|a|) 与 eps/|v| 中较小者, 再对所有粒子取最小并乘以 eta.
+// 加速度与速度都为零时返回无穷大, 由调用者限制在 [dt_min, dt_max] 内.
+// 注意步长随状态变化后 Leapfrog 不再严格时间可逆, 长期能量误差也不再有界.
+pub fn adaptive_dt(bodies: &[Body], eta: Real, softening: Real) -> Real {
+    let dt = bodies
+        .par_iter()
+        .map(|body| {
+            let acc = body.acceleration.length();
+            let speed = body.velocity.length();
+            let dt_acc = if acc > 0.0 { (softening / acc).sqrt() } else { Real::INFINITY };
+            let dt_vel = if speed > 0.0 { softening / speed } else { Real::INFINITY };
+            dt_acc.min(dt_vel)
+        })
+        .reduce(|| Real::INFINITY, Real::min);
+    eta * dt
+}
+
+// 最近交会的时间尺度: 所有粒子对中 |d| / |v_rel| 的最小值, d 在周期性盒子中取最小镜像.
+// 没有相对运动时返回无穷大. O(N^2), 按行并行
+pub fn encounter_timescale(bodies: &[Body], box_size: Option<Real>) -> Real {
+    (0..bodies.len())
+        .into_par_iter()
+        .map(|i| {
+            let body_i = &bodies[i];
+            bodies[i + 1..]
+                .iter()
+                .map(|body_j| {
+                    let distance = minimum_image(body_j.position - body_i.position, box_size).length();
+                    let speed = (body_j.velocity - body_i.velocity).length();
+                    if speed > 0.0 { distance / speed } else { Real::INFINITY }
+                })
+                .fold(Real::INFINITY, Real::min)
+        })
+        .reduce(|| Real::INFINITY, Real::min)
+}
+
+// 近距离交会时的子步: 把 dt 对半分, 直到子步长不超过 eta 倍的交会时间尺度, 最多分 2^max_level 份,
+// 再用子步长连续推进, 结束时正好推进 dt. 交会时间尺度只在步首计算一次. 返回子步数 (不需要细分时为 1)
+pub fn subcycled_step(
+    bodies: &mut [Body],
+    integrator: Integrator,
+    dt: Real,
+    eta: Real,
+    max_level: u32,
+    params: &ForceParams,
+) -> usize {
+    let timescale = encounter_timescale(bodies, params.boundary.periodic_size());
+    let mut level = 0;
+    while level < max_level && dt / (1u64 << level) as Real > eta * timescale {
+        level += 1;
+    }
+    let substeps = 1usize << level;
+    let h = dt / substeps as Real;
+    for _ in 0..substeps {
+        integrate_step(bodies, integrator, h, params);
+    }
+    substeps
+}
+
+// 可选的积分器
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrator {
+    Leapfrog,
+    #[value(name = "verlet")]
+    #[serde(rename = "verlet")]
+    VelocityVerlet,
+    Yoshida4,
+    Rk4,
+    Hermite,
+    Dopri5,
+}
+
+// 为积分器准备当前状态的受力: Hermite 还需要 jerk, 其余只需要加速度
+pub fn update_forces(bodies: &mut [Body], integrator: Integrator, params: &ForceParams) {
+    match integrator {
+        Integrator::Hermite => update_acc_and_jerk(bodies, params.g, params.softening),
+        _ => update_accelerations(bodies, params),
+    }
+}
+
+// 推进一个时间步
+pub fn integrate_step(bodies: &mut [Body], integrator: Integrator, dt: Real, params: &ForceParams) {
+    match integrator {
+        Integrator::Leapfrog => leapfrog_integrator(bodies, dt, params),
+        Integrator::VelocityVerlet => velocity_verlet_step(bodies, dt, params),
+        Integrator::Yoshida4 => yoshida4_step(bodies, dt, params),
+        Integrator::Rk4 => rk4_step(bodies, dt, params),
+        Integrator::Hermite => hermite_step(bodies, dt, params),
+        Integrator::Dopri5 => {
+            dopri5_step(bodies, dt, DOPRI5_TOLERANCE as Real, params);
+        }
+    }
+}
+
+// 时间可逆性检验: 从 bodies 出发向前积分 steps 步, 速度反号后再积分 steps 步, 最后把速度翻回.
+// 对称的积分器 (Leapfrog, Verlet, Yoshida) 只剩舍入误差, RK4 与 Hermite 不对称, 会留下截断误差.
+// 返回 (最大位置误差, 最大速度误差); 周期性盒子中位置差取最小镜像. bodies 本身不变
+pub fn reversibility_error(bodies: &[Body], integrator: Integrator, dt: Real, steps: usize, params: &ForceParams) -> (f64, f64) {
+    let mut state = bodies.to_vec();
+    update_forces(&mut state, integrator, params);
+    for _ in 0..steps {
+        integrate_step(&mut state, integrator, dt, params);
+    }
+    // 加速度与速度无关, jerk 则随速度反号, 因此反向前重新计算受力
+    state.iter_mut().for_each(|b| b.velocity = -b.velocity);
+    update_forces(&mut state, integrator, params);
+    for _ in 0..steps {
+        integrate_step(&mut state, integrator, dt, params);
+    }
+    state.iter_mut().for_each(|b| b.velocity = -b.velocity);
+
+    let box_size = params.boundary.periodic_size();
+    bodies.iter().zip(&state).fold((0.0, 0.0), |(dx, dv), (start, end)| {
+        let position_error = to_f64(minimum_image(end.position - start.position, box_size).length());
+        let velocity_error = to_f64((end.velocity - start.velocity).length());
+        (position_error.max(dx), velocity_error.max(dv))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::total_energy;
+    use crate::force::test_params;
+    use crate::generators::generate_kepler;
+    use crate::precision::consts::TAU;
+
+    // G = 1 下总质量 1, 半长轴 1 的等质量圆轨道, 周期为 2 pi
+    fn circular_orbit() -> Vec<Body> {
+        generate_kepler(0.5, 0.5, 1.0, 0.0, 1.0)
+    }
+
+    // 以 steps 步积分一个周期 (或 periods 个周期), 返回结束时的状态
+    fn integrate_periods(integrator: Integrator, bodies: &[Body], periods: usize, steps: usize, params: &ForceParams) -> Vec<Body> {
+        let mut state = bodies.to_vec();
+        update_forces(&mut state, integrator, params);
+        let dt = TAU / steps as Real;
+        for _ in 0..periods * steps {
+            integrate_step(&mut state, integrator, dt, params);
+        }
+        state
+    }
+
+    #[test]
+    fn velocity_verlet_circular_orbit_returns_after_one_period() {
+        let params = test_params(1.0, 0.0);
+        let start = circular_orbit();
+        let end = integrate_periods(Integrator::VelocityVerlet, &start, 1, 1000, &params);
+        for (start, end) in start.iter().zip(&end) {
+            assert!(start.position.distance(end.position) < 1e-4, "{:?} -> {:?}", start.position, end.position);
+            assert!(start.velocity.distance(end.velocity) < 1e-4, "{:?} -> {:?}", start.velocity, end.velocity);
+        }
+    }
+
+    // 偏心轨道上逐步积分, 返回全程最大的相对能量误差
+    fn max_energy_error(integrator: Integrator, periods: usize, steps: usize, params: &ForceParams) -> f64 {
+        let mut state = generate_kepler(0.5, 0.5, 1.0, 0.5, 1.0);
+        let initial = total_energy(&state, params).2;
+        update_forces(&mut state, integrator, params);
+        let dt = TAU / steps as Real;
+        let mut worst: f64 = 0.0;
+        for _ in 0..periods * steps {
+            integrate_step(&mut state, integrator, dt, params);
+            worst = worst.max(((total_energy(&state, params).2 - initial) / initial).abs());
+        }
+        worst
+    }
+
+    #[test]
+    fn yoshida4_energy_error_is_far_below_leapfrog() {
+        let params = test_params(1.0, 0.0);
+        let leapfrog = max_energy_error(Integrator::Leapfrog, 20, 500, &params);
+        let yoshida = max_energy_error(Integrator::Yoshida4, 20, 500, &params);
+        // 两者的误差都是有界振荡, 四阶格式应小两个数量级以上
+        assert!(yoshida < leapfrog / 100.0, "yoshida {:e}, leapfrog {:e}", yoshida, leapfrog);
+        assert!(yoshida < 1e-6, "yoshida {:e}", yoshida);
+    }
+
+    // 两个粒子以 0.1 的碰撞参数相向飞过, 在 t = 4 附近最接近
+    fn near_miss() -> Vec<Body> {
+        vec![
+            Body::new(0.5, Vec3::new(-2.0, -0.05, 0.0), Vec3::new(0.5, 0.0, 0.0)),
+            Body::new(0.5, Vec3::new(2.0, 0.05, 0.0), Vec3::new(-0.5, 0.0, 0.0)),
+        ]
+    }
+
+    // 近距离飞过, 返回 80 个 dt = 0.05 之后的相对能量误差
+    fn near_miss_energy_error(encounter_eta: Option<Real>, params: &ForceParams) -> f64 {
+        let mut state = near_miss();
+        let initial = total_energy(&state, params).2;
+        update_forces(&mut state, Integrator::Leapfrog, params);
+        for _ in 0..80 {
+            match encounter_eta {
+                Some(eta) => {
+                    subcycled_step(&mut state, Integrator::Leapfrog, 0.05, eta, 16, params);
+                }
+                None => integrate_step(&mut state, Integrator::Leapfrog, 0.05, params),
+            }
+        }
+        ((total_energy(&state, params).2 - initial) / initial).abs()
+    }
+
+    #[test]
+    fn subcycling_conserves_energy_through_a_near_miss() {
+        let params = test_params(1.0, 0.0);
+        let global = near_miss_energy_error(None, &params);
+        let subcycled = near_miss_energy_error(Some(0.001), &params);
+        assert!(subcycled < 1e-3, "subcycled {:e}", subcycled);
+        assert!(subcycled < global / 100.0, "subcycled {:e}, global {:e}", subcycled, global);
+    }
+
+    #[test]
+    fn dopri5_shrinks_its_substeps_through_a_close_encounter() {
+        let params = test_params(1.0, 0.0);
+        let mut state = near_miss();
+        let initial = total_energy(&state, &params).2;
+        update_forces(&mut state, Integrator::Dopri5, &params);
+        let substeps: Vec<usize> = (0..160).map(|_| dopri5_step(&mut state, 0.05, 1e-9, &params).0).collect();
+
+        // 远离时一个子步就够, 交会时子步数明显增加, 能量误差仍保持在容差附近
+        assert_eq!(substeps[0], 1);
+        assert!(*substeps.iter().max().unwrap() > 8, "substeps {:?}", substeps);
+        let error = ((total_energy(&state, &params).2 - initial) / initial).abs();
+        assert!(error < 1e-6, "energy error {:e}", error);
+    }
+
+    // 从近心点出发积分一个周期, 返回 (全程最大的相对能量误差, 回到近心点时的相对位置误差)
+    fn periastron_errors(integrator: Integrator, bodies: &[Body], steps: usize, params: &ForceParams) -> (f64, f64) {
+        let initial = total_energy(bodies, params).2;
+        let separation = |state: &[Body]| state[1].position - state[0].position;
+        let mut state = bodies.to_vec();
+        update_forces(&mut state, integrator, params);
+        let dt = TAU / steps as Real;
+        let mut worst: f64 = 0.0;
+        for _ in 0..steps {
+            integrate_step(&mut state, integrator, dt, params);
+            worst = worst.max(((total_energy(&state, params).2 - initial) / initial).abs());
+        }
+        let start = separation(bodies);
+        (worst, to_f64(separation(&state).distance(start) / start.length()))
+    }
+
+    #[test]
+    fn hermite_resolves_periastron_far_better_than_leapfrog() {
+        // e = 0.9: 近心点距离 0.1, 那里的轨道时标比周期短约 30 倍
+        let params = test_params(1.0, 0.0);
+        let start = generate_kepler(0.5, 0.5, 1.0, 0.9, 1.0);
+        let (leapfrog_energy, leapfrog_position) = periastron_errors(Integrator::Leapfrog, &start, 10000, &params);
+        let (hermite_energy, hermite_position) = periastron_errors(Integrator::Hermite, &start, 10000, &params);
+        // 同样的 dt 下四阶格式的两项误差都小三个数量级以上 (约 4e3 倍)
+        assert!(hermite_energy < leapfrog_energy / 1000.0, "hermite {:e}, leapfrog {:e}", hermite_energy, leapfrog_energy);
+        assert!(hermite_position < leapfrog_position / 1000.0, "hermite {:e}, leapfrog {:e}", hermite_position, leapfrog_position);
+    }
+}
@@ -0,0 +1,216 @@
+// 静态 k-d 树, 用于最近邻查询 (局部密度估计, 自适应软化). 隐式存放: 下标数组的每个子区间 [lo, hi)
+// 以中点处的粒子为分割点, 左半部分在分割轴上不大于它, 右半部分不小于它; 分割轴按深度轮换 x, y, z.
+// 距离为普通的欧氏距离, 不考虑周期性镜像
+use crate::precision::{to_dvec3, to_f64};
+use crate::Body;
+use glam::DVec3;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+pub struct KdTree {
+    points: Vec<DVec3>,
+    order: Vec<usize>,
+}
+
+// 候选近邻, 按距离平方排序 (堆顶为当前最远的候选)
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate {
+    distance_sq: f64,
+    index: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_sq.total_cmp(&other.distance_sq).then(self.index.cmp(&other.index))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl KdTree {
+    // 以粒子的当前位置建树, 返回的下标即 bodies 中的下标
+    pub fn new(bodies: &[Body]) -> Self {
+        Self::from_points(bodies.iter().map(|b| to_dvec3(b.position)).collect())
+    }
+
+    pub fn from_points(points: Vec<DVec3>) -> Self {
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        build(&points, &mut order, 0);
+        Self { points, order }
+    }
+
+    // 离 pos 最近的 k 个点的下标, 由近到远排列 (距离相同时下标小的在前); 点数少于 k 时返回全部点
+    pub fn k_nearest(&self, pos: DVec3, k: usize) -> Vec<usize> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.search(0, self.order.len(), 0, pos, k, &mut heap);
+        }
+        heap.into_sorted_vec().into_iter().map(|c| c.index).collect()
+    }
+
+    // 与 pos 的距离不大于 radius 的所有点的下标, 追加到 found (无特定顺序)
+    pub fn within(&self, pos: DVec3, radius: f64, found: &mut Vec<usize>) {
+        self.range_search(0, self.order.len(), 0, pos, radius, found);
+    }
+
+    fn range_search(&self, lo: usize, hi: usize, depth: usize, pos: DVec3, radius: f64, found: &mut Vec<usize>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let index = self.order[mid];
+        let point = self.points[index];
+        if point.distance_squared(pos) <= radius * radius {
+            found.push(index);
+        }
+        // 分割面两侧只有与 pos 的距离不超过 radius 的一侧需要搜索
+        let axis = depth % 3;
+        let offset = pos[axis] - point[axis];
+        if offset <= radius {
+            self.range_search(lo, mid, depth + 1, pos, radius, found);
+        }
+        if offset >= -radius {
+            self.range_search(mid + 1, hi, depth + 1, pos, radius, found);
+        }
+    }
+
+    fn search(&self, lo: usize, hi: usize, depth: usize, pos: DVec3, k: usize, heap: &mut BinaryHeap<Candidate>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let index = self.order[mid];
+        let point = self.points[index];
+        let candidate = Candidate { distance_sq: point.distance_squared(pos), index };
+        if heap.len() < k {
+            heap.push(candidate);
+        } else if heap.peek().is_some_and(|farthest| candidate < *farthest) {
+            heap.pop();
+            heap.push(candidate);
+        }
+
+        // 先搜索 pos 所在的一侧; 另一侧只有在分割面比当前第 k 近的候选更近时才可能有更近的点
+        let axis = depth % 3;
+        let offset = pos[axis] - point[axis];
+        let (near, far) = if offset < 0.0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+        self.search(near.0, near.1, depth + 1, pos, k, heap);
+        if heap.len() < k || heap.peek().is_some_and(|farthest| offset * offset <= farthest.distance_sq) {
+            self.search(far.0, far.1, depth + 1, pos, k, heap);
+        }
+    }
+}
+
+fn build(points: &[DVec3], order: &mut [usize], depth: usize) {
+    if order.len() <= 1 {
+        return;
+    }
+    let axis = depth % 3;
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by(mid, |&a, &b| points[a][axis].total_cmp(&points[b][axis]));
+    let (left, right) = order.split_at_mut(mid);
+    build(points, left, depth + 1);
+    build(points, &mut right[1..], depth + 1);
+}
+
+// 每个粒子的局部密度: 不计自身的 k 个最近邻的总质量除以以第 k 近邻距离为半径的球体积.
+// 粒子数不足 k + 1 时用其余全部粒子; 少于两个粒子时为 0, 第 k 近邻与自身重合时为无穷大
+pub fn local_densities(bodies: &[Body], k: usize) -> Vec<f64> {
+    let k = k.min(bodies.len().saturating_sub(1));
+    if k == 0 {
+        return vec![0.0; bodies.len()];
+    }
+    let tree = KdTree::new(bodies);
+    bodies
+        .par_iter()
+        .enumerate()
+        .map(|(i, body)| {
+            let position = to_dvec3(body.position);
+            // 多取一个, 再去掉自身 (与其他粒子重合时自身不一定排在第一)
+            let neighbours: Vec<usize> = tree.k_nearest(position, k + 1).into_iter().filter(|&j| j != i).take(k).collect();
+            let radius = neighbours.last().map_or(0.0, |&j| tree.points[j].distance(position));
+            let mass: f64 = neighbours.iter().map(|&j| to_f64(bodies[j].mass)).sum();
+            mass / (4.0 / 3.0 * std::f64::consts::PI * radius.powi(3))
+        })
+        .collect()
+}
+
+// 朋友之友 (friends-of-friends) 分组: 距离不大于 linking_length 的粒子互为朋友, 朋友关系传递闭包构成一组.
+// 从每个未归组的粒子出发用 k-d 树的范围查询做广度优先搜索, 每个粒子只查询一次, 总耗时约为 O(N log N) 加上近邻对数.
+// 每个粒子恰好属于一组 (孤立粒子自成一组); 组按成员数由多到少排列, 组内下标由小到大. 不考虑周期性镜像
+pub fn find_groups(bodies: &[Body], linking_length: f64) -> Vec<Vec<usize>> {
+    let tree = KdTree::new(bodies);
+    let mut assigned = vec![false; bodies.len()];
+    let (mut groups, mut found) = (Vec::new(), Vec::new());
+    for seed in 0..bodies.len() {
+        if assigned[seed] {
+            continue;
+        }
+        assigned[seed] = true;
+        let mut group = vec![seed];
+        let mut next = 0;
+        while next < group.len() {
+            found.clear();
+            tree.within(tree.points[group[next]], linking_length, &mut found);
+            for &j in &found {
+                if !assigned[j] {
+                    assigned[j] = true;
+                    group.push(j);
+                }
+            }
+            next += 1;
+        }
+        group.sort_unstable();
+        groups.push(group);
+    }
+    groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::generate_cold_sphere;
+    use crate::Vec3;
+
+    // 5 x 5 x 5 的整数格点, 下标为 x + 5 y + 25 z
+    fn grid() -> Vec<DVec3> {
+        (0..125).map(|i| DVec3::new((i % 5) as f64, (i / 5 % 5) as f64, (i / 25) as f64)).collect()
+    }
+
+    #[test]
+    fn k_nearest_on_a_grid_matches_brute_force() {
+        let points = grid();
+        let tree = KdTree::from_points(points.clone());
+
+        // 中心格点的 7 个近邻是它自己和 6 个面相邻的格点, 按下标排列
+        assert_eq!(tree.k_nearest(DVec3::splat(2.0), 7), [62, 37, 57, 61, 63, 67, 87]);
+
+        for query in [DVec3::new(0.3, 1.6, 2.2), DVec3::new(4.9, -1.0, 0.5), DVec3::new(2.5, 2.5, 2.5), DVec3::splat(10.0)] {
+            let mut expected: Vec<usize> = (0..points.len()).collect();
+            expected.sort_by(|&a, &b| points[a].distance_squared(query).total_cmp(&points[b].distance_squared(query)).then(a.cmp(&b)));
+            for k in [1, 8, 30] {
+                assert_eq!(tree.k_nearest(query, k), expected[..k], "query {:?}, k = {}", query, k);
+            }
+        }
+    }
+
+    #[test]
+    fn friends_of_friends_separates_two_clumps() {
+        // 两个半径 0.5 的均匀球, 各 100 个粒子, 相距 10; 团内的平均间距约 0.2, 远小于连接长度
+        let mut bodies = generate_cold_sphere(100, 1.0, 0.5, 1);
+        bodies.extend(generate_cold_sphere(100, 1.0, 0.5, 2).into_iter().map(|b| Body { position: b.position + Vec3::new(10.0, 0.0, 0.0), ..b }));
+
+        let groups = find_groups(&bodies, 0.5);
+        assert_eq!(groups.len(), 2, "group sizes {:?}", groups.iter().map(Vec::len).collect::<Vec<_>>());
+        let mut sorted = groups.clone();
+        sorted.sort();
+        assert_eq!(sorted, [(0..100).collect::<Vec<_>>(), (100..200).collect()]);
+    }
+}
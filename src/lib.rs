@@ -0,0 +1,41 @@
+// N-body 引力模拟库: 粒子数据结构, 引力计算, 积分器与诊断量
+pub mod barnes_hut;
+pub mod block;
+pub mod boundary;
+pub mod budget;
+pub mod body;
+pub mod checkpoint;
+pub mod collision;
+pub mod commands;
+pub mod config;
+pub mod cosmology;
+pub mod diagnostics;
+pub mod driver;
+pub mod error;
+pub mod ewald;
+pub mod force;
+pub mod generators;
+pub mod input;
+pub mod integrators;
+pub mod kdtree;
+pub mod output;
+pub mod plot;
+pub mod pm_solver;
+pub mod potential;
+pub mod precision;
+pub mod profile;
+pub mod simulation;
+pub mod units;
+
+pub use body::{Bodies, Body};
+pub use boundary::BoundaryCondition;
+pub use collision::CollisionMode;
+pub use error::SimError;
+pub use force::{update_acc_and_jerk, update_accelerations, ForceMethod, ForceParams, SofteningKernel};
+pub use integrators::{integrate_step, Integrator};
+pub use potential::{DynamicalFriction, ExternalPotential};
+pub use precision::{Real, Vec3};
+pub use simulation::{Simulation, StepReport};
+
+// SI 单位制下的万有引力常数 (单位: m^3 kg^-1 s^-2); 运行时实际使用的值见 units::Units
+pub const G: Real = 6.67430e-11;
@@ -0,0 +1,535 @@
+// 粒子快照等数据输出
+use crate::collision::Merger;
+use crate::config::{Config, GridFormat, SnapshotFormat};
+use crate::input::{is_gzip, TIPSY_DARK_BYTES, TIPSY_HEADER_BYTES};
+use crate::precision::to_f64;
+use crate::plot::{self, axis_indices, Bounds, PlotOptions, Renderer};
+use crate::units::Units;
+use crate::{diagnostics, Body, Real, SimError};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use glam::DVec3;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// 打开输出文件, 必要时先创建所在目录
+fn create_output_file(path: &Path) -> std::io::Result<BufWriter<File>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+// 可选 gzip 压缩的输出文件. gzip 的尾部在 finish 时写出, 写完后必须调用 finish 而不只是 flush
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl OutputFile {
+    pub fn new(file: File, gzip: bool) -> Self {
+        let writer = BufWriter::new(file);
+        if gzip {
+            OutputFile::Gzip(GzEncoder::new(writer, Compression::default()))
+        } else {
+            OutputFile::Plain(writer)
+        }
+    }
+
+    // 路径以 .gz 结尾时压缩写出 (如 particles.json.gz)
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(Self::new(File::create(path)?, is_gzip(path)))
+    }
+
+    pub fn finish(self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(mut writer) => writer.flush(),
+            OutputFile::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputFile::Plain(writer) => writer.write(buf),
+            OutputFile::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Plain(writer) => writer.flush(),
+            OutputFile::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+// 每个粒子一行的 CSV 快照
+pub fn write_snapshot_csv(bodies: &[Body], path: &Path, time: f64) -> std::io::Result<()> {
+    let mut writer = OutputFile::create(path)?;
+    writeln!(writer, "id,mass,x,y,z,vx,vy,vz,time")?;
+    for body in bodies {
+        let (p, v) = (body.position, body.velocity);
+        writeln!(writer, "{},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e}", body.id, body.mass, p.x, p.y, p.z, v.x, v.y, v.z, time)?;
+    }
+    writer.finish()
+}
+
+// 旧式 (legacy) ASCII VTK 快照, 可直接用 ParaView 打开: 每个粒子是 POLYDATA 中的一个顶点,
+// 点数据为编号, 质量 (标量) 与速度 (矢量), 模拟时间写在 TIME 场数据中
+pub fn write_snapshot_vtk(bodies: &[Body], path: &Path, time: f64) -> std::io::Result<()> {
+    let mut writer = create_output_file(path)?;
+    let n = bodies.len();
+    writeln!(writer, "# vtk DataFile Version 3.0")?;
+    writeln!(writer, "N-body snapshot at t = {:e} s", time)?;
+    writeln!(writer, "ASCII")?;
+    writeln!(writer, "DATASET POLYDATA")?;
+    writeln!(writer, "FIELD FieldData 1")?;
+    writeln!(writer, "TIME 1 1 double")?;
+    writeln!(writer, "{:e}", time)?;
+
+    writeln!(writer, "POINTS {} double", n)?;
+    for body in bodies {
+        let p = body.position;
+        writeln!(writer, "{:e} {:e} {:e}", p.x, p.y, p.z)?;
+    }
+    // 每个顶点单元只含一个点: "1 下标"
+    writeln!(writer, "VERTICES {} {}", n, 2 * n)?;
+    for index in 0..n {
+        writeln!(writer, "1 {}", index)?;
+    }
+
+    writeln!(writer, "POINT_DATA {}", n)?;
+    writeln!(writer, "SCALARS id int 1")?;
+    writeln!(writer, "LOOKUP_TABLE default")?;
+    for body in bodies {
+        writeln!(writer, "{}", body.id)?;
+    }
+    writeln!(writer, "SCALARS mass double 1")?;
+    writeln!(writer, "LOOKUP_TABLE default")?;
+    for body in bodies {
+        writeln!(writer, "{:e}", body.mass)?;
+    }
+    writeln!(writer, "VECTORS velocity double")?;
+    for body in bodies {
+        let v = body.velocity;
+        writeln!(writer, "{:e} {:e} {:e}", v.x, v.y, v.z)?;
+    }
+    writer.flush()
+}
+
+// NumPy .npy (1.0 版) 快照: 形状为 (N, 7) 的 float64 数组, 每行为 mass, x, y, z, vx, vy, vz, 可直接 np.load.
+// 格式为魔数与版本号, 2 字节小端的头长度, 描述 dtype 与形状的 Python 字典 (用空格补齐到 64 字节对齐并以换行结尾),
+// 之后是按行存放的小端数据. 单精度构建同样写出 float64
+pub fn write_snapshot_npy(bodies: &[Body], path: &Path) -> std::io::Result<()> {
+    let mut writer = create_output_file(path)?;
+    let mut header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, 7), }}", bodies.len());
+    // 魔数 6 字节 + 版本 2 字节 + 头长度 2 字节
+    let padding = 63 - (10 + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for body in bodies {
+        let (p, v) = (body.position, body.velocity);
+        for value in [body.mass, p.x, p.y, p.z, v.x, v.y, v.z] {
+            writer.write_all(&to_f64(value).to_le_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+// 柱密度网格 (plot::column_density_grid) 在两轴上的范围与每格的大小
+fn grid_geometry(grid: &[Vec<f64>], axis1: char, axis2: char, bounds: &Bounds) -> Result<[(f64, f64); 2], SimError> {
+    let (i, j) = axis_indices(axis1, axis2)?;
+    let ((min_x, max_x), (min_y, max_y)) = (bounds.axis(i), bounds.axis(j));
+    let (nx, ny) = (grid.first().map_or(1, Vec::len).max(1), grid.len().max(1));
+    Ok([(min_x, (max_x - min_x) / nx as f64), (min_y, (max_y - min_y) / ny as f64)])
+}
+
+// 柱密度网格的 CSV: 以 # 开头的注释行说明两轴, 范围与单位, 之后每行对应一个 iy (沿 axis2 由小到大),
+// 每列对应一个 ix (沿 axis1); 可以直接用 numpy.loadtxt(path, delimiter=",") 读取
+pub fn write_column_density_csv(
+    grid: &[Vec<f64>],
+    (axis1, axis2): (char, char),
+    bounds: &Bounds,
+    units: Units,
+    path: &Path,
+    time: f64,
+) -> Result<(), SimError> {
+    let [(min_x, cell_x), (min_y, cell_y)] = grid_geometry(grid, axis1, axis2, bounds)?;
+    let mut writer = create_output_file(path)?;
+    writeln!(writer, "# column density ({} / {}^2) at time {:e}", units.mass(), units.length(), time)?;
+    writeln!(writer, "# columns: {} from {:e} in steps of {:e} {}", axis1, min_x, cell_x, units.length())?;
+    writeln!(writer, "# rows: {} from {:e} in steps of {:e} {}", axis2, min_y, cell_y, units.length())?;
+    for row in grid {
+        let values: Vec<String> = row.iter().map(|value| format!("{:e}", value)).collect();
+        writeln!(writer, "{}", values.join(","))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// FITS 文件由 2880 字节的块组成, 头部每张卡片 80 个 ASCII 字符
+const FITS_BLOCK: usize = 2880;
+const FITS_CARD: usize = 80;
+
+// 数值或逻辑值的卡片: 关键字占前 8 列, 值右对齐到第 30 列; 浮点数需要带小数点, 调用者用 {:.12E} 格式化
+fn fits_card(keyword: &str, value: &str) -> String {
+    format!("{:<8}= {:>20}", keyword, value)
+}
+
+// 字符串值的卡片: 值用单引号括起, 至少 8 个字符
+fn fits_string_card(keyword: &str, value: &str) -> String {
+    format!("{:<8}= '{:<8}'", keyword, value)
+}
+
+// 柱密度网格的 FITS 图像 (只有主 HDU): BITPIX = -64 的大端双精度数据, NAXIS1 沿 axis1, NAXIS2 沿 axis2,
+// 第一行在最下方. CRPIX/CRVAL/CDELT 给出格中心的线性坐标, 可以用 astropy.io.fits, ds9 等打开
+pub fn write_column_density_fits(
+    grid: &[Vec<f64>],
+    (axis1, axis2): (char, char),
+    bounds: &Bounds,
+    units: Units,
+    path: &Path,
+    time: f64,
+) -> Result<(), SimError> {
+    let [(min_x, cell_x), (min_y, cell_y)] = grid_geometry(grid, axis1, axis2, bounds)?;
+    let (nx, ny) = (grid.first().map_or(0, Vec::len), grid.len());
+    let mut cards = vec![
+        fits_card("SIMPLE", "T"),
+        fits_card("BITPIX", "-64"),
+        fits_card("NAXIS", "2"),
+        fits_card("NAXIS1", &nx.to_string()),
+        fits_card("NAXIS2", &ny.to_string()),
+        fits_string_card("BUNIT", &format!("{}/{}**2", units.mass(), units.length())),
+    ];
+    for (index, (axis, min, cell)) in [(axis1, min_x, cell_x), (axis2, min_y, cell_y)].into_iter().enumerate() {
+        let n = index + 1;
+        cards.push(fits_string_card(&format!("CTYPE{}", n), &axis.to_ascii_uppercase().to_string()));
+        cards.push(fits_string_card(&format!("CUNIT{}", n), units.length()));
+        cards.push(fits_card(&format!("CRPIX{}", n), "1.0"));
+        cards.push(fits_card(&format!("CRVAL{}", n), &format!("{:.12E}", min + 0.5 * cell)));
+        cards.push(fits_card(&format!("CDELT{}", n), &format!("{:.12E}", cell)));
+    }
+    cards.push(fits_card("TIME", &format!("{:.12E}", time)));
+    cards.push("END".to_string());
+
+    let mut header: Vec<u8> = cards.iter().flat_map(|card| format!("{:<width$}", card, width = FITS_CARD).into_bytes()).collect();
+    header.resize(header.len().div_ceil(FITS_BLOCK) * FITS_BLOCK, b' ');
+    let mut data: Vec<u8> = grid.iter().flatten().flat_map(|value| value.to_be_bytes()).collect();
+    data.resize(data.len().div_ceil(FITS_BLOCK) * FITS_BLOCK, 0);
+
+    let mut writer = create_output_file(path)?;
+    writer.write_all(&header)?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// 标准 (大端) TIPSY 文件, pynbody 等工具可直接读取: 所有粒子都写成暗物质记录, 数值为单精度.
+// eps 为粒子自身的软化长度 (未给出时为 0), phi 写 0; 种类, 半径与编号不保存. 路径以 .gz 结尾时压缩写出
+pub fn write_tipsy(bodies: &[Body], path: &Path, time: f64) -> std::io::Result<()> {
+    let n = i32::try_from(bodies.len()).map_err(|_| std::io::Error::other("too many bodies for a TIPSY file"))?;
+    let mut writer = OutputFile::create(path)?;
+    let mut header = Vec::with_capacity(TIPSY_HEADER_BYTES);
+    header.extend_from_slice(&time.to_be_bytes());
+    for value in [n, 3, 0, n, 0, 0] {
+        header.extend_from_slice(&value.to_be_bytes());
+    }
+    writer.write_all(&header)?;
+
+    let mut record = Vec::with_capacity(TIPSY_DARK_BYTES);
+    for body in bodies {
+        let (p, v) = (body.position, body.velocity);
+        record.clear();
+        for value in [body.mass, p.x, p.y, p.z, v.x, v.y, v.z, body.softening.unwrap_or(0.0), 0.0] {
+            record.extend_from_slice(&(to_f64(value) as f32).to_be_bytes());
+        }
+        writer.write_all(&record)?;
+    }
+    writer.finish()
+}
+
+// 轨迹文件中一行的格式, 由扩展名决定: .jsonl/.ndjson 为逐行 JSON, 其余为 CSV
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrajectoryFormat {
+    Csv,
+    JsonLines,
+}
+
+impl TrajectoryFormat {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl") | Some("ndjson") => TrajectoryFormat::JsonLines,
+            _ => TrajectoryFormat::Csv,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TrajectoryRow {
+    step: usize,
+    time: f64,
+    body_id: usize,
+    x: Real,
+    y: Real,
+    z: Real,
+    vx: Real,
+    vy: Real,
+    vz: Real,
+}
+
+// 整个运行过程的长格式轨迹文件, 在主循环前打开一次, 按步追加.
+// 每次调用 write_step 写完一整步的所有行, 且需要 &mut self, 因此不同步的行不会交错;
+// 若以后在多个线程中写入, 应把它放进 Mutex 中.
+pub struct TrajectoryWriter {
+    writer: BufWriter<File>,
+    format: TrajectoryFormat,
+}
+
+impl TrajectoryWriter {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let format = TrajectoryFormat::from_path(path);
+        let mut writer = create_output_file(path)?;
+        if format == TrajectoryFormat::Csv {
+            writeln!(writer, "step,time,body_id,x,y,z,vx,vy,vz")?;
+        }
+        Ok(Self { writer, format })
+    }
+
+    // 续算时追加到已有文件, 不再写表头
+    pub fn append(path: &Path) -> std::io::Result<Self> {
+        let format = TrajectoryFormat::from_path(path);
+        let writer = BufWriter::new(OpenOptions::new().append(true).open(path)?);
+        Ok(Self { writer, format })
+    }
+
+    pub fn write_step(&mut self, bodies: &[Body], step: usize, time: f64) -> std::io::Result<()> {
+        // 粒子数可能因删除逃逸粒子或碰撞合并而逐步减少, 每步只写当前存在的粒子
+        for body in bodies {
+            let (body_id, p, v) = (body.id, body.position, body.velocity);
+            match self.format {
+                TrajectoryFormat::Csv => writeln!(
+                    self.writer,
+                    "{},{:e},{},{:e},{:e},{:e},{:e},{:e},{:e}",
+                    step, time, body_id, p.x, p.y, p.z, v.x, v.y, v.z
+                )?,
+                TrajectoryFormat::JsonLines => {
+                    let row = TrajectoryRow { step, time, body_id, x: p.x, y: p.y, z: p.z, vx: v.x, vy: v.y, vz: v.z };
+                    serde_json::to_writer(&mut self.writer, &row)?;
+                    writeln!(self.writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// 向诊断 CSV 写一行: 能量 (动能, 势能, 总能量) 与动量 (线动量, 角动量)
+pub fn write_diagnostics_row(
+    writer: &mut impl Write,
+    step: usize,
+    time: f64,
+    (kinetic, potential, total): (f64, f64, f64),
+    (momentum, angular_momentum): (DVec3, DVec3),
+) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "{},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e}",
+        step,
+        time,
+        kinetic,
+        potential,
+        total,
+        momentum.x,
+        momentum.y,
+        momentum.z,
+        angular_momentum.x,
+        angular_momentum.y,
+        angular_momentum.z
+    )
+}
+
+// 拉格朗日半径 CSV 的表头, 每个质量分数一列
+pub fn write_lagrangian_header(writer: &mut impl Write, fractions: &[f64]) -> std::io::Result<()> {
+    write!(writer, "step,time")?;
+    for fraction in fractions {
+        write!(writer, ",r_{}", fraction)?;
+    }
+    writeln!(writer)
+}
+
+pub fn write_lagrangian_row(writer: &mut impl Write, step: usize, time: f64, radii: &[f64]) -> std::io::Result<()> {
+    write!(writer, "{},{:e}", step, time)?;
+    for radius in radii {
+        write!(writer, ",{:e}", radius)?;
+    }
+    writeln!(writer)
+}
+
+// 引力波应变 CSV 的一行: 步数, 时间, h+, hx
+pub fn write_strain_row(writer: &mut impl Write, step: usize, time: f64, (h_plus, h_cross): (f64, f64)) -> std::io::Result<()> {
+    writeln!(writer, "{},{:e},{:e},{:e}", step, time, h_plus, h_cross)
+}
+
+// 合并事件日志的一行 (JSON lines)
+pub fn write_merger_event(writer: &mut impl Write, step: usize, time: f64, merger: &Merger) -> std::io::Result<()> {
+    #[derive(Serialize)]
+    struct Event<'a> {
+        step: usize,
+        time: f64,
+        #[serde(flatten)]
+        merger: &'a Merger,
+    }
+    writeln!(writer, "{}", serde_json::to_string(&Event { step, time, merger })?)
+}
+
+pub fn write_dispersion_row(
+    writer: &mut impl Write,
+    step: usize,
+    time: f64,
+    temperature: f64,
+    (sigma, sigma_axes): (f64, DVec3),
+) -> std::io::Result<()> {
+    writeln!(writer, "{},{:e},{:e},{:e},{:e},{:e},{:e}", step, time, temperature, sigma, sigma_axes.x, sigma_axes.y, sigma_axes.z)
+}
+
+// 按扩展名写出粒子: .tipsy (可再加 .gz) 为 TIPSY, 其余为与输入格式相同的 JSON
+pub fn write_bodies(bodies: &[Body], path: &Path, time: f64) -> Result<(), SimError> {
+    let format_path = if is_gzip(path) { Path::new(path.file_stem().unwrap_or_default()) } else { path };
+    match format_path.extension().and_then(|e| e.to_str()) {
+        Some("tipsy") => Ok(write_tipsy(bodies, path, time)?),
+        _ => write_bodies_json(bodies, path),
+    }
+}
+
+// 把粒子写成与输入格式相同的 JSON 文件
+pub fn write_bodies_json(bodies: &[Body], path: &Path) -> Result<(), SimError> {
+    let mut writer = OutputFile::create(path)?;
+    serde_json::to_writer_pretty(&mut writer, bodies).map_err(std::io::Error::from)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// 按格式写出一个快照, stem 为不含扩展名的路径; gzip 只对 CSV 快照有效 (启动时已检查)
+pub fn write_snapshot(bodies: &[Body], format: SnapshotFormat, gzip: bool, stem: &Path, time: f64) -> std::io::Result<()> {
+    match format {
+        SnapshotFormat::Csv => {
+            let extension = if gzip { "csv.gz" } else { "csv" };
+            write_snapshot_csv(bodies, &stem.with_extension(extension), time)
+        }
+        SnapshotFormat::Vtk => write_snapshot_vtk(bodies, &stem.with_extension("vtk"), time),
+        SnapshotFormat::Npy => write_snapshot_npy(bodies, &stem.with_extension("npy")),
+        SnapshotFormat::Tipsy => {
+            let extension = if gzip { "tipsy.gz" } else { "tipsy" };
+            write_tipsy(bodies, &stem.with_extension(extension), time)
+        }
+    }
+}
+
+// 写出一帧: 快照与各种图像, 文件名与标题使用 label; turn 为此前的帧数, 决定 3D 视图的旋转角
+#[allow(clippy::too_many_arguments)]
+pub fn write_frame(
+    bodies: &[Body],
+    label: usize,
+    turn: usize,
+    time: f64,
+    config: &Config,
+    plot_options: &PlotOptions,
+    animation: Option<&plot::GifAnimation>,
+    trails: Option<&plot::Trails>,
+) -> Result<(), SimError> {
+    let output_dir = &config.output_dir;
+    if let Some(format) = config.snapshot_format {
+        write_snapshot(bodies, format, config.gzip_snapshots, &output_dir.join(format!("snapshot_{:04}", label)), time)?;
+    }
+
+    // 以下的图使用旋转后的副本, 模拟状态不变
+    let view = config.face_on.then(|| diagnostics::face_on_view(bodies));
+    let plotted = view.as_deref().unwrap_or(bodies);
+    // 平面模拟中另外两个投影只是一条线
+    let projections: &[(char, char)] = if config.planar { &[('x', 'y')] } else { &[('x', 'y'), ('x', 'z'), ('y', 'z')] };
+    if config.png_frames && config.combined_projections {
+        plot::plot_all_projections(plotted, trails, &output_dir.join(format!("proj_{:04}.png", label)), label, plot_options)?;
+    } else if config.png_frames {
+        // 三个投影各自写入独立的文件, 只共享只读的粒子与绘图选项, 可以并行绘制; 任一失败时返回其错误
+        projections.par_iter().try_for_each(|&(axis1, axis2)| {
+            let path = output_dir.join(format!("{}{}_proj_{:04}.png", axis1, axis2, label));
+            match plot_options.renderer {
+                Renderer::Scatter => plot::plot_density_projection(plotted, trails, axis1, axis2, &path, label, plot_options),
+                Renderer::Heatmap => plot::plot_density_heatmap(plotted, axis1, axis2, &path, label, plot_options),
+            }
+        })?;
+    }
+    if let Some(format) = config.column_density {
+        // 没有固定范围时取当前帧粒子的包围盒
+        let bounds = plot_options.bounds.unwrap_or_else(|| Bounds::from_bodies(plotted, 0.0));
+        let n = plot_options.resolution;
+        for &(axis1, axis2) in projections {
+            let grid = plot::column_density_grid(plotted, axis1, axis2, n, n, &bounds)?;
+            let stem = output_dir.join(format!("{}{}_column_{:04}", axis1, axis2, label));
+            match format {
+                GridFormat::Csv => {
+                    write_column_density_csv(&grid, (axis1, axis2), &bounds, config.units, &stem.with_extension("csv"), time)?
+                }
+                GridFormat::Fits => {
+                    write_column_density_fits(&grid, (axis1, axis2), &bounds, config.units, &stem.with_extension("fits"), time)?
+                }
+            }
+        }
+    }
+    if config.density_profile {
+        let (center, _) = diagnostics::center_of_mass(bodies);
+        let r_max = config
+            .profile_r_max
+            .unwrap_or_else(|| bodies.iter().map(|b| to_f64(b.position.distance(center))).fold(0.0, f64::max));
+        let profile = diagnostics::radial_density_profile(bodies, config.profile_bins, r_max);
+        plot::plot_radial_profile(&profile, &output_dir.join(format!("density_profile_{:04}.png", label)), label, config.units)?;
+    }
+    if config.phase_space {
+        plot::plot_phase_space(bodies, &output_dir.join(format!("phase_space_{:04}.png", label)), label, config.units)?;
+    }
+    if config.plot_3d {
+        let yaw = config.view_yaw + config.view_rotation * turn as f64;
+        let path = output_dir.join(format!("view3d_{:04}.png", label));
+        plot::plot_3d(plotted, &path, label, yaw.to_radians(), config.view_pitch.to_radians(), plot_options)?;
+    }
+    if let Some(animation) = animation {
+        animation.add_frame(plotted, trails, label)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::generate_plummer;
+    use crate::input::load_bodies;
+
+    #[test]
+    fn gzipped_json_round_trips() {
+        let bodies = generate_plummer(100, 1.0, 1.0, 3, 1.0);
+        let path = std::env::temp_dir().join(format!("nbody_gzip_test_{}.json.gz", std::process::id()));
+        write_bodies(&bodies, &path, 0.0).unwrap();
+        let raw = std::fs::read(&path).unwrap();
+        let loaded = load_bodies(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // gzip 文件以魔数 1f 8b 开头
+        assert_eq!(raw[..2], [0x1f, 0x8b]);
+        assert_eq!(loaded, bodies);
+    }
+}
@@ -0,0 +1,921 @@
+// 绘图
+use crate::diagnostics::{center_of_mass, species_present};
+use crate::precision::to_f64;
+use crate::units::Units;
+use crate::{Body, Real, SimError, Vec3};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+// 图像尺寸 (像素)
+const PLOT_SIZE: (u32, u32) = (1024, 768);
+
+// 三视图合并图像的尺寸 (像素)
+const COMBINED_PLOT_SIZE: (u32, u32) = (1920, 720);
+
+// 三视图的投影平面
+const PROJECTIONS: [(char, char); 3] = [('x', 'y'), ('x', 'z'), ('y', 'z')];
+
+// 色标所占的宽度 (像素)
+const COLOR_BAR_WIDTH: u32 = 110;
+
+// 粒子的着色方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// All bodies white
+    #[default]
+    White,
+    /// Speed |v|, normalized to the current frame
+    Speed,
+    /// log10 of the mass, normalized to the current frame
+    Mass,
+    /// One fixed color per species, with a legend
+    Species,
+}
+
+impl ColorMode {
+    // 用于着色的连续量, White 与 Species 模式下没有
+    fn value(self, body: &Body) -> Option<f64> {
+        match self {
+            ColorMode::White | ColorMode::Species => None,
+            ColorMode::Speed => Some(to_f64(body.velocity.length())),
+            ColorMode::Mass => Some(to_f64(body.mass).log10()),
+        }
+    }
+
+    fn label(self, units: Units) -> String {
+        match self {
+            ColorMode::White | ColorMode::Species => String::new(),
+            ColorMode::Speed => format!("|v| ({})", units.speed()),
+            ColorMode::Mass => format!("log10 m ({})", units.mass()),
+        }
+    }
+}
+
+// 种类的颜色, 同一种类在每一帧中都相同
+fn species_color(species: u8) -> RGBAColor {
+    Palette99::pick(species as usize).to_rgba()
+}
+
+// 散点图中粒子的大小
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeMode {
+    /// Every body drawn with the same radius
+    #[default]
+    Uniform,
+    /// Radius grows as the cube root of the mass, relative to the lightest body in the frame
+    Mass,
+}
+
+// 散点的半径 (像素); 按质量缩放时最轻的粒子取 POINT_SIZE, 最大不超过 MAX_POINT_SIZE
+const POINT_SIZE: f64 = 2.0;
+const MAX_POINT_SIZE: f64 = 12.0;
+
+// 投影图的绘制方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    /// One dot per body
+    #[default]
+    Scatter,
+    /// Mass binned on a 2D grid with a log-scaled colormap
+    Heatmap,
+}
+
+// 三维包围盒, 用于在所有帧中固定坐标轴范围
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    // 粒子的包围盒, 每侧向外扩展 padding 倍的边长; 退化的方向 (如平面系统的 z) 按最长边的一半扩展
+    pub fn from_bodies(bodies: &[Body], padding: Real) -> Self {
+        let (mut min, mut max) = (Vec3::splat(Real::MAX), Vec3::splat(Real::MIN));
+        for body in bodies {
+            min = min.min(body.position);
+            max = max.max(body.position);
+        }
+        if bodies.is_empty() {
+            return Self { min: Vec3::splat(-1.0), max: Vec3::splat(1.0) };
+        }
+        let size = max - min;
+        let fallback = Vec3::splat(if size.max_element() > 0.0 { size.max_element() / 2.0 } else { 1.0 });
+        let pad = Vec3::select(size.cmpgt(Vec3::ZERO), size * padding.max(0.0), fallback);
+        Self { min: min - pad, max: max + pad }
+    }
+
+    pub(crate) fn axis(&self, index: usize) -> (f64, f64) {
+        (to_f64(self.min[index]), to_f64(self.max[index]))
+    }
+}
+
+// 绘图选项
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    pub color: ColorMode,
+    pub renderer: Renderer,
+    pub size: SizeMode,
+    // 热图每个方向上的网格数
+    pub resolution: usize,
+    // 散点图最多绘制的粒子数; 超过时按编号确定性地抽取子样本
+    pub max_points: Option<usize>,
+    // 固定的坐标范围; None 时每帧根据当前粒子自动确定
+    pub bounds: Option<Bounds>,
+    // 坐标轴与色标的单位, 写在标题中
+    pub units: Units,
+    // 子样本的随机种子 (--seed), 不同种子选中不同的一批粒子
+    pub seed: u64,
+}
+
+impl Default for PlotOptions {
+    fn default() -> Self {
+        Self {
+            color: ColorMode::default(),
+            renderer: Renderer::default(),
+            size: SizeMode::default(),
+            resolution: 256,
+            max_points: None,
+            bounds: None,
+            units: Units::default(),
+            seed: 42,
+        }
+    }
+}
+
+// 粒子数超过这个值时不记录轨迹尾迹: 缓冲区占 N * K 个位置, 每帧还要画 N * K 条线段
+pub const MAX_TRAIL_BODIES: usize = 1000;
+
+// 散点投影图中的轨迹尾迹: 每个粒子最近 length 个位置的环形缓冲区, 按编号存放.
+// 位置为模拟坐标, 与 --face-on 的旋转视图不对应
+#[derive(Debug, Clone)]
+pub struct Trails {
+    length: usize,
+    positions: HashMap<usize, VecDeque<Vec3>>,
+}
+
+impl Trails {
+    pub fn new(length: usize) -> Self {
+        Self { length, positions: HashMap::new() }
+    }
+
+    // 记录当前位置, 超出 length 时丢弃最旧的; 已被合并或移除的粒子的尾迹一并丢弃
+    pub fn record(&mut self, bodies: &[Body]) {
+        if self.positions.len() > bodies.len() {
+            let present: std::collections::HashSet<usize> = bodies.iter().map(|b| b.id).collect();
+            self.positions.retain(|id, _| present.contains(id));
+        }
+        for body in bodies {
+            let trail = self.positions.entry(body.id).or_insert_with(|| VecDeque::with_capacity(self.length + 1));
+            trail.push_back(body.position);
+            if trail.len() > self.length {
+                trail.pop_front();
+            }
+        }
+    }
+}
+
+// 投影轴 'x', 'y', 'z' 对应的坐标分量下标
+pub(crate) fn axis_indices(axis1: char, axis2: char) -> Result<(usize, usize), SimError> {
+    let index = |axis| match axis {
+        'x' => Ok(0),
+        'y' => Ok(1),
+        'z' => Ok(2),
+        _ => Err(SimError::InvalidAxis(axis)),
+    };
+    Ok((index(axis1)?, index(axis2)?))
+}
+
+// 取出 body 在投影平面上的坐标
+fn project(body: &Body, axes: (usize, usize)) -> (f64, f64) {
+    project_position(body.position, axes)
+}
+
+fn project_position(position: Vec3, (i, j): (usize, usize)) -> (f64, f64) {
+    (to_f64(position[i]), to_f64(position[j]))
+}
+
+// 投影坐标的范围 (min_x, max_x, min_y, max_y), 给定固定范围时直接使用
+fn projected_bounds(bodies: &[Body], axes: (usize, usize), fixed: Option<&Bounds>) -> (f64, f64, f64, f64) {
+    if let Some(bounds) = fixed {
+        let ((min_x, max_x), (min_y, max_y)) = (bounds.axis(axes.0), bounds.axis(axes.1));
+        return (min_x, max_x, min_y, max_y);
+    }
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for body in bodies {
+        let (p1, p2) = project(body, axes);
+        min_x = min_x.min(p1);
+        max_x = max_x.max(p1);
+        min_y = min_y.min(p2);
+        max_y = max_y.max(p2);
+    }
+    (min_x, max_x, min_y, max_y)
+}
+
+// splitmix64 的状态增量 (黄金比例)
+const SPLITMIX_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// 粒子数超过 max_points 时, 取编号哈希值最小的 max_points 个粒子: 与随机抽样等价,
+// 但只取决于编号与种子, 因此每帧 (即使删除了部分粒子) 选中的都是同一批粒子, 动画各帧可以直接比较.
+// 哈希值是以 seed 为初始状态的 splitmix64 序列的第 id + 1 个输出, 不消耗任何随机数流
+fn subsample(bodies: &[Body], max_points: Option<usize>, seed: u64) -> Vec<&Body> {
+    let mut selected: Vec<&Body> = bodies.iter().collect();
+    let Some(max_points) = max_points.filter(|&m| m < bodies.len()) else {
+        return selected;
+    };
+    let hash = |id: usize| {
+        let mut z = seed.wrapping_add((id as u64).wrapping_add(1).wrapping_mul(SPLITMIX_GAMMA));
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    if max_points > 0 {
+        selected.select_nth_unstable_by_key(max_points - 1, |b| hash(b.id));
+    }
+    selected.truncate(max_points);
+    selected
+}
+
+// 投影点是否落在范围内, 范围外的粒子被裁掉
+fn inside((p1, p2): (f64, f64), (min_x, max_x, min_y, max_y): (f64, f64, f64, f64)) -> bool {
+    (min_x..=max_x).contains(&p1) && (min_y..=max_y).contains(&p2)
+}
+
+// 当前帧中着色量的范围, 退化时向两侧扩展以免色标区间为零
+fn value_range(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    if min > max {
+        (0.0, 1.0)
+    } else if min == max {
+        (min - 0.5, max + 0.5)
+    } else {
+        (min, max)
+    }
+}
+
+// 在 area 上绘制竖直色标
+fn draw_color_bar<DB>(area: &DrawingArea<DB, Shift>, label: &str, (min, max): (f64, f64)) -> Result<(), SimError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let mut chart = ChartBuilder::on(area)
+        .caption(label, ("sans-serif", 16).into_font().color(&WHITE))
+        .margin(10)
+        .margin_top(60)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0.0..1.0, min..max)?;
+
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .disable_x_axis()
+        .y_labels(6)
+        .axis_style(WHITE)
+        .label_style(("sans-serif", 12).into_font().color(&WHITE))
+        .draw()?;
+
+    let steps = 100;
+    chart.draw_series((0..steps).map(|k| {
+        let lo = min + (max - min) * k as f64 / steps as f64;
+        let hi = min + (max - min) * (k + 1) as f64 / steps as f64;
+        Rectangle::new([(0.0, lo), (1.0, hi)], ViridisRGB::get_color_normalized(lo, min, max).filled())
+    }))?;
+
+    Ok(())
+}
+
+// 绘制密度投影图
+pub fn plot_density_projection(
+    bodies: &[Body],
+    trails: Option<&Trails>,
+    axis1: char,
+    axis2: char,
+    file_name: &Path,
+    time_step: usize,
+    options: &PlotOptions,
+) -> Result<(), SimError> {
+    let root = BitMapBackend::new(file_name, PLOT_SIZE).into_drawing_area();
+    draw_density_projection(&root, bodies, trails, axis1, axis2, time_step, options)?;
+    root.present()?;
+    Ok(())
+}
+
+// 在任意绘图区域上绘制密度投影, 供 PNG 与 GIF 输出共用
+pub fn draw_density_projection<DB>(
+    root: &DrawingArea<DB, Shift>,
+    bodies: &[Body],
+    trails: Option<&Trails>,
+    axis1: char,
+    axis2: char,
+    time_step: usize,
+    options: &PlotOptions,
+) -> Result<(), SimError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let axes = axis_indices(axis1, axis2)?;
+    let caption = format!("Density Projection ({}-{}) at t={} [{}]", axis1, axis2, time_step, options.units.label());
+    draw_scatter_panel(root, bodies, trails, axes, &caption, options)
+}
+
+// 标题字号随区域宽度缩放, 单图时为 50
+fn caption_size<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>) -> u32 {
+    (area.dim_in_pixel().0 / 20).clamp(16, 50)
+}
+
+fn draw_scatter_panel<DB>(
+    root: &DrawingArea<DB, Shift>,
+    bodies: &[Body],
+    trails: Option<&Trails>,
+    axes: (usize, usize),
+    caption: &str,
+    options: &PlotOptions,
+) -> Result<(), SimError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&BLACK)?;
+
+    // 按连续量着色时在右侧留出色标
+    let color_range = match options.color {
+        ColorMode::White | ColorMode::Species => None,
+        mode => Some(value_range(bodies.iter().filter_map(|b| mode.value(b)))),
+    };
+    let (plot_area, bar_area) = root.split_horizontally(if color_range.is_some() {
+        root.dim_in_pixel().0 - COLOR_BAR_WIDTH
+    } else {
+        root.dim_in_pixel().0
+    });
+    if let Some(range) = color_range {
+        draw_color_bar(&bar_area, &options.color.label(options.units), range)?;
+    }
+
+    // 固定边界或自动确定边界
+    let bounds = projected_bounds(bodies, axes, options.bounds.as_ref());
+    let (min_x, max_x, min_y, max_y) = bounds;
+
+    let mut chart = ChartBuilder::on(&plot_area)
+        .caption(caption, ("sans-serif", caption_size(root)).into_font())
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(70)
+        .build_cartesian_2d(min_x..max_x, min_y..max_y)?;
+
+    chart.configure_mesh().draw()?;
+
+    // 按质量缩放时先画大的, 以免遮住其上的轻粒子; 体积 ∝ 质量, 即半径 ∝ m^(1/3)
+    let mut visible = subsample(bodies, options.max_points, options.seed);
+    visible.retain(|body| inside(project(body, axes), bounds));
+    let lightest = visible.iter().map(|b| to_f64(b.mass)).fold(f64::INFINITY, f64::min);
+    if options.size == SizeMode::Mass {
+        visible.sort_by(|a, b| b.mass.total_cmp(&a.mass));
+    }
+    let color_of = |body: &Body| match (options.color.value(body), color_range) {
+        (Some(v), Some((min, max))) => ViridisRGB::get_color_normalized(v, min, max).to_rgba(),
+        _ if options.color == ColorMode::Species => species_color(body.species),
+        _ => WHITE.to_rgba(),
+    };
+
+    // 尾迹画在粒子之下, 与粒子同色, 不透明度由最旧的线段到最新的线段线性增加;
+    // 端点落在范围外的线段 (包括周期性边界上的折返) 不画
+    if let Some(trails) = trails {
+        for body in &visible {
+            let Some(trail) = trails.positions.get(&body.id) else { continue };
+            let color = color_of(body);
+            let segments = trail.len().saturating_sub(1);
+            let points: Vec<(f64, f64)> = trail.iter().map(|&p| project_position(p, axes)).collect();
+            chart.draw_series(points.windows(2).enumerate().filter(|(_, w)| inside(w[0], bounds) && inside(w[1], bounds)).map(
+                |(k, w)| PathElement::new(vec![w[0], w[1]], color.mix((k + 1) as f64 / segments as f64)),
+            ))?;
+        }
+    }
+
+    chart.draw_series(visible.into_iter().map(|body| {
+        let (p1, p2) = project(body, axes);
+        let color = color_of(body);
+        let radius = match options.size {
+            SizeMode::Uniform => POINT_SIZE,
+            SizeMode::Mass => (POINT_SIZE * (to_f64(body.mass) / lightest).cbrt()).min(MAX_POINT_SIZE),
+        };
+        Circle::new((p1, p2), radius.round() as i32, color.filled())
+    }))?;
+
+    // 每个种类一个空序列, 只用于生成图例
+    if options.color == ColorMode::Species {
+        for species in species_present(bodies) {
+            let color = species_color(species);
+            chart
+                .draw_series(std::iter::empty::<Circle<(f64, f64), i32>>())?
+                .label(format!("species {}", species))
+                .legend(move |(x, y)| Circle::new((x, y), 4, color.filled()));
+        }
+        chart
+            .configure_series_labels()
+            .background_style(BLACK.mix(0.8))
+            .border_style(WHITE)
+            .label_font(("sans-serif", 16).into_font().color(&WHITE))
+            .draw()?;
+    }
+
+    Ok(())
+}
+
+// 绘制投影面密度热图
+pub fn plot_density_heatmap(
+    bodies: &[Body],
+    axis1: char,
+    axis2: char,
+    file_name: &Path,
+    time_step: usize,
+    options: &PlotOptions,
+) -> Result<(), SimError> {
+    let root = BitMapBackend::new(file_name, PLOT_SIZE).into_drawing_area();
+    draw_density_heatmap(&root, bodies, axis1, axis2, time_step, options)?;
+    root.present()?;
+    Ok(())
+}
+
+// 把质量分箱到 resolution x resolution 的网格上, 按 log10 着色, 空网格使用色图的最低色
+pub fn draw_density_heatmap<DB>(
+    root: &DrawingArea<DB, Shift>,
+    bodies: &[Body],
+    axis1: char,
+    axis2: char,
+    time_step: usize,
+    options: &PlotOptions,
+) -> Result<(), SimError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let axes = axis_indices(axis1, axis2)?;
+    let caption = format!("Density Heatmap ({}-{}) at t={} [{}]", axis1, axis2, time_step, options.units.label());
+    draw_heatmap_panel(root, bodies, axes, &caption, options)
+}
+
+// 把质量分箱到 extent 内 nx x ny 的网格上, 按行存放 (下标 iy * nx + ix).
+// 单次遍历, 落在上边界的粒子归入最后一格, 范围外的粒子不计入
+fn bin_mass(bodies: &[Body], axes: (usize, usize), (nx, ny): (usize, usize), extent: (f64, f64, f64, f64)) -> Vec<f64> {
+    let (min_x, max_x, min_y, max_y) = extent;
+    let (width, height) = ((max_x - min_x).max(f64::MIN_POSITIVE), (max_y - min_y).max(f64::MIN_POSITIVE));
+    let mut grid = vec![0.0; nx * ny];
+    for body in bodies {
+        let (p1, p2) = project(body, axes);
+        if !inside((p1, p2), extent) {
+            continue;
+        }
+        let ix = (((p1 - min_x) / width * nx as f64) as usize).min(nx - 1);
+        let iy = (((p2 - min_y) / height * ny as f64) as usize).min(ny - 1);
+        grid[iy * nx + ix] += to_f64(body.mass);
+    }
+    grid
+}
+
+// 投影平面上的柱密度 (面密度) 网格: bounds 在 axis1, axis2 上的范围分成 nx x ny 格, 每格为其中的粒子质量除以格面积.
+// grid[iy][ix], iy 沿 axis2, ix 沿 axis1 由小到大; 范围外的粒子不计入, 因此 sum(grid) * 格面积等于范围内的总质量.
+// 给定同一个 bounds (如 --fixed-bounds) 时各帧的网格一一对应
+pub fn column_density_grid(
+    bodies: &[Body],
+    axis1: char,
+    axis2: char,
+    nx: usize,
+    ny: usize,
+    bounds: &Bounds,
+) -> Result<Vec<Vec<f64>>, SimError> {
+    let axes = axis_indices(axis1, axis2)?;
+    let (nx, ny) = (nx.max(1), ny.max(1));
+    let ((min_x, max_x), (min_y, max_y)) = (bounds.axis(axes.0), bounds.axis(axes.1));
+    let cell_area = (max_x - min_x) / nx as f64 * (max_y - min_y) / ny as f64;
+    let grid = bin_mass(bodies, axes, (nx, ny), (min_x, max_x, min_y, max_y));
+    Ok(grid.chunks(nx).map(|row| row.iter().map(|&mass| mass / cell_area).collect()).collect())
+}
+
+fn draw_heatmap_panel<DB>(
+    root: &DrawingArea<DB, Shift>,
+    bodies: &[Body],
+    axes: (usize, usize),
+    caption: &str,
+    options: &PlotOptions,
+) -> Result<(), SimError>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    let resolution = options.resolution.max(1);
+    let (min_x, max_x, min_y, max_y) = projected_bounds(bodies, axes, options.bounds.as_ref());
+    let (width, height) = ((max_x - min_x).max(f64::MIN_POSITIVE), (max_y - min_y).max(f64::MIN_POSITIVE));
+
+    let grid = bin_mass(bodies, axes, (resolution, resolution), (min_x, max_x, min_y, max_y));
+    let scale = resolution as f64;
+
+    let range = value_range(grid.iter().filter(|&&m| m > 0.0).map(|m| m.log10()));
+
+    root.fill(&BLACK)?;
+    let (plot_area, bar_area) = root.split_horizontally(root.dim_in_pixel().0 - COLOR_BAR_WIDTH);
+    draw_color_bar(&bar_area, &format!("log10 m ({})", options.units.mass()), range)?;
+
+    let mut chart = ChartBuilder::on(&plot_area)
+        .caption(caption, ("sans-serif", caption_size(root)).into_font())
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(70)
+        .build_cartesian_2d(min_x..min_x + width, min_y..min_y + height)?;
+
+    chart.configure_mesh().disable_mesh().draw()?;
+
+    let (cell_x, cell_y) = (width / scale, height / scale);
+    chart.draw_series(grid.iter().enumerate().map(|(index, &mass)| {
+        let (ix, iy) = ((index % resolution) as f64, (index / resolution) as f64);
+        let value = if mass > 0.0 { mass.log10() } else { range.0 };
+        Rectangle::new(
+            [(min_x + ix * cell_x, min_y + iy * cell_y), (min_x + (ix + 1.0) * cell_x, min_y + (iy + 1.0) * cell_y)],
+            ViridisRGB::get_color_normalized(value, range.0, range.1).filled(),
+        )
+    }))?;
+
+    Ok(())
+}
+
+// 把 x-y, x-z, y-z 三个投影并排画在同一张图中, 共用一个标题
+pub fn plot_all_projections(
+    bodies: &[Body],
+    trails: Option<&Trails>,
+    file_name: &Path,
+    time_step: usize,
+    options: &PlotOptions,
+) -> Result<(), SimError> {
+    let root = BitMapBackend::new(file_name, COMBINED_PLOT_SIZE).into_drawing_area();
+    root.fill(&BLACK)?;
+    let title = match options.renderer {
+        Renderer::Scatter => format!("Density Projections at t={} [{}]", time_step, options.units.label()),
+        Renderer::Heatmap => format!("Density Heatmaps at t={} [{}]", time_step, options.units.label()),
+    };
+    let root = root.titled(&title, ("sans-serif", 40).into_font().color(&WHITE))?;
+
+    for (panel, (axis1, axis2)) in root.split_evenly((1, 3)).iter().zip(PROJECTIONS) {
+        let axes = axis_indices(axis1, axis2)?;
+        let caption = format!("{}-{}", axis1, axis2);
+        match options.renderer {
+            Renderer::Scatter => draw_scatter_panel(panel, bodies, trails, axes, &caption, options)?,
+            Renderer::Heatmap => draw_heatmap_panel(panel, bodies, axes, &caption, options)?,
+        }
+    }
+    root.present()?;
+    Ok(())
+}
+
+// 从 (yaw, pitch) 方向 (弧度, 与 plotters 的三维投影相同) 观察的透视散点图. 深度提示: 离观察者越近的
+// 粒子画得越大越亮, 并画在远处粒子之上. 坐标范围取 options.bounds, 未给出时按当前粒子自动确定
+pub fn plot_3d(
+    bodies: &[Body],
+    file_name: &Path,
+    time_step: usize,
+    yaw: f64,
+    pitch: f64,
+    options: &PlotOptions,
+) -> Result<(), SimError> {
+    let root = BitMapBackend::new(file_name, PLOT_SIZE).into_drawing_area();
+    root.fill(&BLACK)?;
+
+    let color_range = match options.color {
+        ColorMode::White | ColorMode::Species => None,
+        mode => Some(value_range(bodies.iter().filter_map(|b| mode.value(b)))),
+    };
+    let (plot_area, bar_area) = root.split_horizontally(if color_range.is_some() {
+        PLOT_SIZE.0 - COLOR_BAR_WIDTH
+    } else {
+        PLOT_SIZE.0
+    });
+    if let Some(range) = color_range {
+        draw_color_bar(&bar_area, &options.color.label(options.units), range)?;
+    }
+
+    let bounds = options.bounds.unwrap_or_else(|| Bounds::from_bodies(bodies, 0.05));
+    let ((min_x, max_x), (min_y, max_y), (min_z, max_z)) = (bounds.axis(0), bounds.axis(1), bounds.axis(2));
+    let caption = format!("3D View at t={} [{}]", time_step, options.units.label());
+    let mut chart = ChartBuilder::on(&plot_area)
+        .caption(caption, ("sans-serif", 40).into_font().color(&WHITE))
+        .margin(20)
+        .build_cartesian_3d(min_x..max_x, min_y..max_y, min_z..max_z)?;
+    chart.with_projection(|mut projection| {
+        projection.yaw = yaw;
+        projection.pitch = pitch;
+        projection.scale = 0.8;
+        projection.into_matrix()
+    });
+    chart
+        .configure_axes()
+        .light_grid_style(WHITE.mix(0.1))
+        .bold_grid_style(WHITE.mix(0.25))
+        .axis_panel_style(WHITE.mix(0.05))
+        .label_style(("sans-serif", 12).into_font().color(&WHITE))
+        .x_formatter(&|x| format!("{:.1e}", x))
+        .y_formatter(&|y| format!("{:.1e}", y))
+        .z_formatter(&|z| format!("{:.1e}", z))
+        .draw()?;
+
+    // 投影深度以像素计, 越大离观察者越远; 从远到近绘制
+    let inside_3d = |p: &Vec3| {
+        let (x, y, z) = (to_f64(p.x), to_f64(p.y), to_f64(p.z));
+        (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y) && (min_z..=max_z).contains(&z)
+    };
+    let mut visible: Vec<(&Body, i32)> = subsample(bodies, options.max_points, options.seed)
+        .into_iter()
+        .filter(|body| inside_3d(&body.position))
+        .map(|body| {
+            let p = body.position;
+            (body, chart.as_coord_spec().projected_depth(&to_f64(p.x), &to_f64(p.y), &to_f64(p.z)))
+        })
+        .collect();
+    visible.sort_by_key(|&(_, depth)| std::cmp::Reverse(depth));
+    let (nearest, farthest) = match (visible.last(), visible.first()) {
+        (Some(&(_, near)), Some(&(_, far))) => (near as f64, far as f64),
+        _ => (0.0, 0.0),
+    };
+
+    chart.draw_series(visible.into_iter().map(|(body, depth)| {
+        // closeness 为 0 (最远) 到 1 (最近)
+        let closeness = if farthest > nearest { (farthest - depth as f64) / (farthest - nearest) } else { 1.0 };
+        let color = match (options.color.value(body), color_range) {
+            (Some(v), Some((min, max))) => ViridisRGB::get_color_normalized(v, min, max).to_rgba(),
+            _ if options.color == ColorMode::Species => species_color(body.species),
+            _ => WHITE.to_rgba(),
+        };
+        let radius = (POINT_SIZE * (0.5 + closeness)).round().max(1.0) as i32;
+        let p = body.position;
+        Circle::new((to_f64(p.x), to_f64(p.y), to_f64(p.z)), radius, color.mix(0.3 + 0.7 * closeness).filled())
+    }))?;
+
+    root.present()?;
+    Ok(())
+}
+
+// 在双对数坐标上绘制径向密度剖面, 跳过密度为零的空球壳
+pub fn plot_radial_profile(profile: &[(f64, f64)], file_name: &Path, time_step: usize, units: Units) -> Result<(), SimError> {
+    let points: Vec<(f64, f64)> = profile.iter().copied().filter(|&(r, rho)| r > 0.0 && rho > 0.0).collect();
+    let root = BitMapBackend::new(file_name, PLOT_SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let (r_min, r_max) = value_range(points.iter().map(|p| p.0));
+    let (rho_min, rho_max) = value_range(points.iter().map(|p| p.1));
+    // 对数坐标需要正的范围, 两侧各留出一点余量
+    let (r_min, r_max) = (r_min.max(f64::MIN_POSITIVE) / 1.5, r_max.max(f64::MIN_POSITIVE) * 1.5);
+    let (rho_min, rho_max) = (rho_min.max(f64::MIN_POSITIVE) / 3.0, rho_max.max(f64::MIN_POSITIVE) * 3.0);
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(format!("Radial Density Profile at t={}", time_step), ("sans-serif", 40).into_font())
+        .margin(20)
+        .x_label_area_size(50)
+        .y_label_area_size(90)
+        .build_cartesian_2d((r_min..r_max).log_scale(), (rho_min..rho_max).log_scale())?;
+
+    chart
+        .configure_mesh()
+        .x_desc(format!("r ({})", units.length()))
+        .y_desc(format!("density ({}/{}^3)", units.mass(), units.length()))
+        .x_label_formatter(&|r| format!("{:.0e}", r))
+        .y_label_formatter(&|rho| format!("{:.0e}", rho))
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(points.iter().copied(), BLUE.stroke_width(2)))?;
+    chart.draw_series(points.iter().map(|&p| Circle::new(p, 4, BLUE.filled())))?;
+
+    root.present()?;
+    Ok(())
+}
+
+// 相空间图每个方向上的网格数, 用于估计点的局部密度
+const PHASE_SPACE_BINS: usize = 100;
+
+// 径向相空间图: 相对质心的半径 r 与径向速度 v_r = (v - V) · r_hat 的散点图.
+// 每个点按其所在网格 (PHASE_SPACE_BINS x PHASE_SPACE_BINS) 中点数的 log10 着色, 以区分致密的核心与稀疏的壳层
+pub fn plot_phase_space(bodies: &[Body], file_name: &Path, time_step: usize, units: Units) -> Result<(), SimError> {
+    let (center, mean_velocity) = center_of_mass(bodies);
+    let points: Vec<(f64, f64)> = bodies
+        .iter()
+        .map(|b| {
+            let offset = b.position - center;
+            let radial_velocity = (b.velocity - mean_velocity).dot(offset.normalize_or_zero());
+            (to_f64(offset.length()), to_f64(radial_velocity))
+        })
+        .collect();
+
+    let (r_min, r_max) = value_range(points.iter().map(|p| p.0));
+    let (v_min, v_max) = value_range(points.iter().map(|p| p.1));
+    let (r_min, v_min) = (r_min.min(0.0), v_min.min(-v_max.abs()));
+    let v_max = v_max.max(-v_min);
+
+    // 局部密度: 每个点所在网格中的点数
+    let cell = |(r, v): (f64, f64)| {
+        let i = ((r - r_min) / (r_max - r_min) * PHASE_SPACE_BINS as f64) as usize;
+        let j = ((v - v_min) / (v_max - v_min) * PHASE_SPACE_BINS as f64) as usize;
+        i.min(PHASE_SPACE_BINS - 1) * PHASE_SPACE_BINS + j.min(PHASE_SPACE_BINS - 1)
+    };
+    let mut counts = vec![0u32; PHASE_SPACE_BINS * PHASE_SPACE_BINS];
+    for &p in &points {
+        counts[cell(p)] += 1;
+    }
+    let density: Vec<f64> = points.iter().map(|&p| (counts[cell(p)] as f64).log10()).collect();
+    let density_range = value_range(density.iter().copied());
+
+    let root = BitMapBackend::new(file_name, PLOT_SIZE).into_drawing_area();
+    root.fill(&BLACK)?;
+    let (plot_area, bar_area) = root.split_horizontally(PLOT_SIZE.0 - COLOR_BAR_WIDTH);
+    draw_color_bar(&bar_area, "log10 N per cell", density_range)?;
+
+    let mut chart = ChartBuilder::on(&plot_area)
+        .caption(format!("Phase Space (r, v_r) at t={}", time_step), ("sans-serif", 40).into_font().color(&WHITE))
+        .margin(20)
+        .x_label_area_size(50)
+        .y_label_area_size(90)
+        .build_cartesian_2d(r_min..r_max, v_min..v_max)?;
+
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc(format!("r ({})", units.length()))
+        .y_desc(format!("v_r ({})", units.speed()))
+        .x_label_formatter(&|r| format!("{:.1e}", r))
+        .y_label_formatter(&|v| format!("{:.1e}", v))
+        .axis_style(WHITE)
+        .label_style(("sans-serif", 14).into_font().color(&WHITE))
+        .draw()?;
+
+    // 先画稀疏的点, 致密区域的颜色画在上面
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by(|&a, &b| density[a].total_cmp(&density[b]));
+    chart.draw_series(order.into_iter().map(|k| {
+        let color = ViridisRGB::get_color_normalized(density[k], density_range.0, density_range.1);
+        Circle::new(points[k], 2, color.filled())
+    }))?;
+
+    root.present()?;
+    Ok(())
+}
+
+// 相对能量误差 (E - E0) / |E0| 随时间变化的折线图, samples 为 (时间, 相对误差)
+pub fn plot_energy_error(samples: &[(f64, f64)], file_name: &Path, units: Units) -> Result<(), SimError> {
+    let root = BitMapBackend::new(file_name, PLOT_SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let (t_min, t_max) = value_range(samples.iter().map(|s| s.0));
+    let (e_min, e_max) = value_range(samples.iter().map(|s| s.1));
+    let margin = 0.05 * (e_max - e_min);
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Relative Energy Error", ("sans-serif", 40).into_font())
+        .margin(20)
+        .x_label_area_size(50)
+        .y_label_area_size(90)
+        .build_cartesian_2d(t_min..t_max, (e_min - margin)..(e_max + margin))?;
+
+    chart
+        .configure_mesh()
+        .x_desc(format!("time ({})", units.time()))
+        .y_desc("(E - E0) / |E0|")
+        .x_label_formatter(&|t| format!("{:.2e}", t))
+        .y_label_formatter(&|e| format!("{:.1e}", e))
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(samples.iter().copied(), BLUE.stroke_width(2)))?;
+
+    root.present()?;
+    Ok(())
+}
+
+// 多条能量误差曲线 (名称, [(时间, 相对误差)]) 的对比图. 各积分器的误差相差多个数量级, 纵轴取 |dE / E0| 的对数,
+// 误差恰为零的采样点 (如起点) 画不出来, 跳过
+pub fn plot_integrator_comparison(curves: &[(String, Vec<(f64, f64)>)], file_name: &Path, units: Units) -> Result<(), SimError> {
+    let root = BitMapBackend::new(file_name, PLOT_SIZE).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let points = curves.iter().flat_map(|(_, samples)| samples.iter()).filter(|s| s.1 != 0.0);
+    let (t_min, t_max) = value_range(curves.iter().flat_map(|(_, samples)| samples.iter().map(|s| s.0)));
+    let (e_min, e_max) = points.fold((f64::MAX, f64::MIN), |(lo, hi), s| (lo.min(s.1.abs()), hi.max(s.1.abs())));
+    let (e_min, e_max) = if e_min > e_max { (1e-16, 1.0) } else { (e_min / 2.0, e_max * 2.0) };
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Integrator Comparison: Relative Energy Error", ("sans-serif", 36).into_font())
+        .margin(20)
+        .x_label_area_size(50)
+        .y_label_area_size(90)
+        .build_cartesian_2d(t_min..t_max, (e_min..e_max).log_scale())?;
+
+    chart
+        .configure_mesh()
+        .x_desc(format!("time ({})", units.time()))
+        .y_desc("|E - E0| / |E0|")
+        .x_label_formatter(&|t| format!("{:.2e}", t))
+        .y_label_formatter(&|e| format!("{:.0e}", e))
+        .draw()?;
+
+    for (index, (name, samples)) in curves.iter().enumerate() {
+        let color = Palette99::pick(index).to_rgba();
+        let line = samples.iter().filter(|s| s.1 != 0.0).map(|&(t, e)| (t, e.abs()));
+        chart
+            .draw_series(LineSeries::new(line, color.stroke_width(2)))?
+            .label(name.as_str())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2)));
+    }
+    chart.configure_series_labels().background_style(WHITE.mix(0.8)).border_style(BLACK).draw()?;
+
+    root.present()?;
+    Ok(())
+}
+
+// 把投影图逐帧写入循环播放的 GIF.
+// 每一帧在 add_frame 时即编码并写入文件, 内存中只保留一帧的缓冲区 (约 1024*768*3 字节),
+// 但 GIF 文件大小随帧数线性增长, 且每帧的颜色量化较慢, 帧数很多时会明显拖慢输出.
+pub struct GifAnimation {
+    root: DrawingArea<BitMapBackend<'static>, Shift>,
+    axes: (char, char),
+    options: PlotOptions,
+}
+
+impl GifAnimation {
+    // frame_delay 为相邻帧之间的间隔 (毫秒)
+    pub fn create(path: &Path, axes: (char, char), frame_delay: u32, options: PlotOptions) -> Result<Self, SimError> {
+        axis_indices(axes.0, axes.1)?;
+        let root = BitMapBackend::gif(path, PLOT_SIZE, frame_delay)
+            .map_err(|e| SimError::Plot(e.to_string()))?
+            .into_drawing_area();
+        Ok(Self { root, axes, options })
+    }
+
+    pub fn add_frame(&self, bodies: &[Body], trails: Option<&Trails>, time_step: usize) -> Result<(), SimError> {
+        let (axis1, axis2) = self.axes;
+        match self.options.renderer {
+            Renderer::Scatter => draw_density_projection(&self.root, bodies, trails, axis1, axis2, time_step, &self.options)?,
+            Renderer::Heatmap => draw_density_heatmap(&self.root, bodies, axis1, axis2, time_step, &self.options)?,
+        }
+        self.root.present()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_species_render_with_distinct_colors() {
+        let mut bodies: Vec<Body> = (0..20).map(|i| Body::new(1.0, Vec3::new(i as Real, (i % 5) as Real, 0.0), Vec3::ZERO)).collect();
+        for body in &mut bodies[10..] {
+            body.species = 1;
+        }
+        assert_ne!(species_color(0), species_color(1));
+
+        let path = std::env::temp_dir().join(format!("nbody_species_{}.png", std::process::id()));
+        let options = PlotOptions { color: ColorMode::Species, ..PlotOptions::default() };
+        plot_density_projection(&bodies, None, 'x', 'y', &path, 0, &options).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn subsample_depends_only_on_seed_and_ids() {
+        let bodies: Vec<Body> = (0..1000).map(|i| Body { id: i, ..Body::new(1.0, Vec3::ZERO, Vec3::ZERO) }).collect();
+        let ids = |seed: u64| {
+            let mut ids: Vec<usize> = subsample(&bodies, Some(100), seed).iter().map(|b| b.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(ids(42), ids(42));
+        assert_ne!(ids(42), ids(43));
+
+        // 选中哪些粒子与它们在数组中的顺序无关
+        let reversed: Vec<Body> = bodies.iter().rev().cloned().collect();
+        let mut again: Vec<usize> = subsample(&reversed, Some(100), 42).iter().map(|b| b.id).collect();
+        again.sort_unstable();
+        assert_eq!(again, ids(42));
+    }
+
+    #[test]
+    fn column_density_grid_holds_the_mass_inside_its_bounds() {
+        let bodies: Vec<Body> = (0..50)
+            .map(|i| {
+                let x = i as Real * 0.1 - 1.0;
+                Body::new(1.0 + (i % 3) as Real, Vec3::new(x, 0.7 * x, 0.0), Vec3::ZERO)
+            })
+            .collect();
+        let bounds = Bounds { min: Vec3::splat(-1.05), max: Vec3::splat(1.55) };
+        let (nx, ny) = (8, 5);
+        let grid = column_density_grid(&bodies, 'x', 'y', nx, ny, &bounds).unwrap();
+        assert_eq!((grid.len(), grid[0].len()), (ny, nx));
+
+        let cell_area = 2.6 / nx as f64 * 2.6 / ny as f64;
+        let binned: f64 = grid.iter().flatten().sum::<f64>() * cell_area;
+        let inside = |v: Real| (-1.05..=1.55).contains(&v);
+        let expected: f64 = bodies.iter().filter(|b| inside(b.position.x) && inside(b.position.y)).map(|b| to_f64(b.mass)).sum();
+        assert!(expected < bodies.iter().map(|b| to_f64(b.mass)).sum());
+        assert!((binned - expected).abs() < 1e-9 * expected, "{} vs {}", binned, expected);
+    }
+}
@@ -0,0 +1,201 @@
+// 粒子-网格 (PM) 引力求解器, 只用于周期性盒子: 云中单元 (CIC) 分配质量, FFT 求解泊松方程,
+// 对网格上的势做差分得到力场, 再用同样的 CIC 权重插值回粒子, 总开销约为 O(N + M log M), M = n^3.
+//
+// 分辨率: 网格间距 H = L / n. CIC 分配与插值相当于把每个粒子抹成边长 H 的立方体, 间距小于约 2-3 H 时
+// 引力被明显削弱 (零间距时为零), 大于约 3 H 时与牛顿引力 (含所有周期像) 相差在百分之几以内.
+// 因此 PM 的有效软化长度约为一个网格间距, --softening 与软化核不起作用; 需要小尺度精度时应加密网格
+// 或改用直接求和 / 树方法. 力还带有 O(H) 量级的各向异性, 与粒子相对网格的位置有关.
+// k = 0 模式被置零, 即减去平均密度 (周期性盒子中泊松方程只对密度涨落有解)
+use crate::precision::{from_dvec3, to_dvec3, to_f64};
+use crate::{Body, Real};
+use glam::DVec3;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn from_angle(angle: f64) -> Self {
+        let (im, re) = angle.sin_cos();
+        Self { re, im }
+    }
+
+    fn mul(self, other: Self) -> Self {
+        Self { re: self.re * other.re - self.im * other.im, im: self.re * other.im + self.im * other.re }
+    }
+
+    fn scale(self, factor: f64) -> Self {
+        Self { re: self.re * factor, im: self.im * factor }
+    }
+}
+
+// 原地的基 2 迭代 FFT, 长度必须是 2 的幂; 正变换取 e^{-i k x}, 逆变换不归一化
+fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    // 位反转重排
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        // 旋转因子逐个直接计算, 避免连乘累积舍入误差
+        let twiddles: Vec<Complex> = (0..half).map(|k| Complex::from_angle(sign * 2.0 * PI * k as f64 / len as f64)).collect();
+        for chunk in data.chunks_mut(len) {
+            for k in 0..half {
+                let u = chunk[k];
+                let v = chunk[k + half].mul(twiddles[k]);
+                chunk[k] = Complex { re: u.re + v.re, im: u.im + v.im };
+                chunk[k + half] = Complex { re: u.re - v.re, im: u.im - v.im };
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// 沿某一轴对 n^3 网格的每条线做 FFT; 网格下标为 x + y n + z n^2
+fn fft_axis(grid: &mut [Complex], n: usize, axis: usize, inverse: bool) {
+    let index = |line: usize, t: usize| {
+        let (a, b) = (line % n, line / n);
+        match axis {
+            0 => t + a * n + b * n * n,
+            1 => a + t * n + b * n * n,
+            _ => a + b * n + t * n * n,
+        }
+    };
+    let lines: Vec<Vec<Complex>> = (0..n * n)
+        .into_par_iter()
+        .map(|line| {
+            let mut values: Vec<Complex> = (0..n).map(|t| grid[index(line, t)]).collect();
+            fft(&mut values, inverse);
+            values
+        })
+        .collect();
+    for (line, values) in lines.into_iter().enumerate() {
+        for (t, value) in values.into_iter().enumerate() {
+            grid[index(line, t)] = value;
+        }
+    }
+}
+
+fn fft_3d(grid: &mut [Complex], n: usize, inverse: bool) {
+    for axis in 0..3 {
+        fft_axis(grid, n, axis, inverse);
+    }
+}
+
+// 位置 (以网格间距为单位) 处的 CIC 权重: 八个相邻格点的下标与权重
+fn cic_weights(cell: DVec3, n: usize) -> [(usize, f64); 8] {
+    let base = cell.floor();
+    let fraction = cell - base;
+    let wrap = |x: f64, offset: usize| ((x as i64).rem_euclid(n as i64) as usize + offset) % n;
+    let mut weights = [(0, 0.0); 8];
+    for (corner, weight) in weights.iter_mut().enumerate() {
+        let (dx, dy, dz) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+        let w = |d: usize, f: f64| if d == 1 { f } else { 1.0 - f };
+        let index = wrap(base.x, dx) + wrap(base.y, dy) * n + wrap(base.z, dz) * n * n;
+        *weight = (index, w(dx, fraction.x) * w(dy, fraction.y) * w(dz, fraction.z));
+    }
+    weights
+}
+
+// 用边长为 box_size 的周期性盒子上 grid^3 的网格计算所有粒子的加速度, grid 必须是 2 的幂
+pub fn pm_accelerations(bodies: &mut [Body], g: Real, box_size: Real, grid: usize) {
+    let n = grid;
+    let size = to_f64(box_size);
+    let spacing = size / n as f64;
+    let cell_volume = spacing * spacing * spacing;
+
+    // CIC 分配质量, 得到密度场
+    let mut density = vec![Complex::default(); n * n * n];
+    for body in bodies.iter() {
+        let cell = to_dvec3(body.position) / spacing;
+        for (index, weight) in cic_weights(cell, n) {
+            density[index].re += to_f64(body.mass) * weight / cell_volume;
+        }
+    }
+    fft_3d(&mut density, n, false);
+
+    // 泊松方程 k^2 phi_k = -4 pi G rho_k; 去掉 k = 0 模式 (平均密度)
+    let wavenumber = |m: usize| {
+        let m = if m < n / 2 { m as f64 } else { m as f64 - n as f64 };
+        2.0 * PI * m / size
+    };
+    let four_pi_g = 4.0 * PI * to_f64(g);
+    density.par_iter_mut().enumerate().for_each(|(index, value)| {
+        let k = DVec3::new(wavenumber(index % n), wavenumber(index / n % n), wavenumber(index / (n * n)));
+        let k_sq = k.length_squared();
+        *value = if k_sq == 0.0 { Complex::default() } else { value.scale(-four_pi_g / k_sq) };
+    });
+    fft_3d(&mut density, n, true);
+    let normalization = 1.0 / (n * n * n) as f64;
+    let potential: Vec<f64> = density.into_iter().map(|value| value.re * normalization).collect();
+
+    // a = -grad phi, 用实空间的四点中心差分; 它同时压低了截断在 Nyquist 频率处的振荡
+    let stride = [1, n, n * n];
+    let field: Vec<DVec3> = (0..n * n * n)
+        .into_par_iter()
+        .map(|index| {
+            let coordinates = [index % n, index / n % n, index / (n * n)];
+            let mut gradient = [0.0; 3];
+            for axis in 0..3 {
+                let neighbour = |offset: isize| {
+                    let shifted = (coordinates[axis] as isize + offset).rem_euclid(n as isize) as usize;
+                    potential[index - coordinates[axis] * stride[axis] + shifted * stride[axis]]
+                };
+                gradient[axis] = (2.0 / 3.0 * (neighbour(1) - neighbour(-1)) - (neighbour(2) - neighbour(-2)) / 12.0) / spacing;
+            }
+            -DVec3::from_array(gradient)
+        })
+        .collect();
+
+    // 插值与分配使用同样的 CIC 权重, 粒子对之间的力反对称, 总动量守恒
+    bodies.par_iter_mut().for_each(|body| {
+        let cell = to_dvec3(body.position) / spacing;
+        let mut acceleration = DVec3::ZERO;
+        for (index, weight) in cic_weights(cell, n) {
+            acceleration += field[index] * weight;
+        }
+        body.acceleration = from_dvec3(acceleration);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ewald::ewald_table;
+    use crate::Vec3;
+
+    #[test]
+    fn single_overdensity_matches_the_ewald_periodic_force() {
+        // 单位盒子中心的点质量与几个远离网格尺度 (H = 1/64) 的无质量示踪粒子; 沿坐标轴的方向各向异性误差最大
+        let center = Vec3::splat(0.5);
+        let offsets = [Vec3::new(0.25, 0.0, 0.0), Vec3::new(0.15, -0.2, 0.1), Vec3::new(0.0, 0.3, 0.3)];
+        let mut bodies = vec![Body::new(1.0, center, Vec3::ZERO)];
+        bodies.extend(offsets.iter().map(|&offset| Body::new(0.0, center + offset, Vec3::ZERO)));
+        pm_accelerations(&mut bodies, 1.0, 1.0, 64);
+
+        for (body, &offset) in bodies[1..].iter().zip(&offsets) {
+            // 周期解: 最小镜像的牛顿引力加上 Ewald 修正, 位移为源减去受力粒子
+            let direction = -offset;
+            let expected = direction / direction.length().powi(3) + ewald_table().correction(direction, 1.0);
+            let error = body.acceleration.distance(expected) / expected.length();
+            assert!(error < 0.03, "offset {:?}: {:?} vs {:?}", offset, body.acceleration, expected);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// 外部静态势场, 用于在固定的宿主势中追踪示踪粒子
+use crate::ewald::erfc;
+use crate::precision::consts::PI;
+use crate::precision::{from_dvec3, to_dvec3, to_f64};
+use crate::{Real, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExternalPotential {
+    // 位于 position 的点质量, 不做软化
+    PointMass { mass: Real, position: Vec3 },
+    // 以原点为中心的 NFW 晕, rho(r) = rho0 / ((r/rs) (1 + r/rs)^2)
+    NFW { rho0: Real, scale_radius: Real },
+}
+
+impl ExternalPotential {
+    // 位置 pos 处的外部引力加速度, g 为万有引力常数
+    pub fn external_acceleration(&self, pos: Vec3, g: Real) -> Vec3 {
+        match *self {
+            ExternalPotential::PointMass { mass, position } => {
+                let direction = position - pos;
+                let distance = direction.length();
+                if distance == 0.0 {
+                    return Vec3::ZERO;
+                }
+                direction * (g * mass / (distance * distance * distance))
+            }
+            ExternalPotential::NFW { rho0, scale_radius } => {
+                let r = pos.length();
+                if r == 0.0 {
+                    return Vec3::ZERO;
+                }
+                // a = -G M(<r) / r^2 * r_hat, M(<r) = 4 pi rho0 rs^3 [ln(1+x) - x/(1+x)]
+                let x = r / scale_radius;
+                let enclosed = 4.0 * PI * rho0 * scale_radius.powi(3) * ((1.0 + x).ln() - x / (1.0 + x));
+                -pos * (g * enclosed / (r * r * r))
+            }
+        }
+    }
+
+    // 位置 pos 处单位质量的势能, 用于能量诊断
+    pub fn potential(&self, pos: Vec3, g: Real) -> Real {
+        match *self {
+            ExternalPotential::PointMass { mass, position } => {
+                let distance = position.distance(pos);
+                if distance == 0.0 {
+                    return 0.0;
+                }
+                -g * mass / distance
+            }
+            ExternalPotential::NFW { rho0, scale_radius } => {
+                let r = pos.length();
+                let prefactor = -4.0 * PI * g * rho0 * scale_radius.powi(2);
+                if r == 0.0 {
+                    return prefactor;
+                }
+                // Phi = -4 pi G rho0 rs^3 ln(1 + r/rs) / r, 在 r -> 0 时趋于 -4 pi G rho0 rs^2
+                prefactor * (1.0 + r / scale_radius).ln() * scale_radius / r
+            }
+        }
+    }
+}
+
+// Chandrasekhar 动力学摩擦: 质量为 M, 速度为 v 的粒子穿过静止的均匀背景 (密度 rho, 各向同性 Maxwell 分布,
+// 一维速度弥散 sigma) 时受到的阻力
+//   a = -4 pi G^2 M rho ln(Lambda) / |v|^3 * [erf(X) - 2 X / sqrt(pi) exp(-X^2)] v,  X = |v| / (sqrt(2) sigma).
+// 假设: 背景无限大, 均匀且不受粒子影响, 粒子本身不改变背景 (不计自引力尾迹的反作用), ln(Lambda) 为常数;
+// 背景只产生摩擦, 不产生引力 (宿主的引力由 --external 等给出). 只作用于质量不小于 min_mass 的粒子.
+// 阻力依赖速度, Leapfrog 中用半步 kick 后的速度计算, 积分不再是辛的; 能量随之耗散
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DynamicalFriction {
+    pub background_density: Real,
+    pub velocity_dispersion: Real,
+    pub coulomb_log: Real,
+    pub min_mass: Real,
+}
+
+impl DynamicalFriction {
+    // 质量为 mass, 速度为 velocity 的粒子的摩擦加速度, g 为万有引力常数
+    pub fn acceleration(&self, mass: Real, velocity: Vec3, g: Real) -> Vec3 {
+        if mass < self.min_mass {
+            return Vec3::ZERO;
+        }
+        let v = to_dvec3(velocity);
+        let speed = v.length();
+        let sigma = to_f64(self.velocity_dispersion);
+        let x = speed / (std::f64::consts::SQRT_2 * sigma);
+        let two_over_sqrt_pi = std::f64::consts::FRAC_2_SQRT_PI;
+        // bracket / X^3; X 较小时 erf(X) 与第二项几乎相消, 改用级数 2/sqrt(pi) (2/3 - 2/5 X^2 + X^4/7 - X^6/27)
+        let x_sq = x * x;
+        let bracket_over_x3 = if x < 0.3 {
+            two_over_sqrt_pi * (2.0 / 3.0 + x_sq * (-2.0 / 5.0 + x_sq * (1.0 / 7.0 - x_sq / 27.0)))
+        } else {
+            (1.0 - erfc(x) - two_over_sqrt_pi * x * (-x_sq).exp()) / (x_sq * x)
+        };
+        // 1 / |v|^3 * bracket = bracket_over_x3 / (sqrt(2) sigma)^3, 在 v -> 0 时保持有限
+        let g = to_f64(g);
+        let prefactor = 4.0 * std::f64::consts::PI * g * g * to_f64(mass) * to_f64(self.background_density) * to_f64(self.coulomb_log);
+        from_dvec3(-v * (prefactor * bracket_over_x3 / (std::f64::consts::SQRT_2 * sigma).powi(3)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::test_params;
+    use crate::integrators::{integrate_step, update_forces, Integrator};
+    use crate::{Body, ForceParams};
+
+    #[test]
+    fn tracer_keeps_a_constant_radius_around_a_point_mass() {
+        let external = ExternalPotential::PointMass { mass: 1.0, position: Vec3::ZERO };
+        let params = ForceParams { external: Some(external), ..test_params(1.0, 0.0) };
+        // 圆速度 v = sqrt(G M / r) = 1; 约 16 圈
+        let mut bodies = vec![Body::new(1e-10, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))];
+        update_forces(&mut bodies, Integrator::Leapfrog, &params);
+        for step in 0..10_000 {
+            integrate_step(&mut bodies, Integrator::Leapfrog, 0.01, &params);
+            let radius = bodies[0].position.length();
+            assert!((radius - 1.0).abs() < 1e-4, "step {}: r = {}", step, radius);
+        }
+    }
+
+    #[test]
+    fn massive_body_spirals_inward_under_dynamical_friction() {
+        let external = ExternalPotential::PointMass { mass: 1.0, position: Vec3::ZERO };
+        let friction = DynamicalFriction { background_density: 0.02, velocity_dispersion: 0.5, coulomb_log: 3.0, min_mass: 1e-3 };
+        let params = ForceParams { external: Some(external), friction: Some(friction), ..test_params(1.0, 0.0) };
+        let mut bodies = vec![Body::new(0.01, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))];
+        update_forces(&mut bodies, Integrator::Leapfrog, &params);
+
+        // 摩擦力与速度反向, 比角动量 |r x v| 单调减小; 摩擦较弱时轨道近似保持圆形, 半径随之缩小
+        let mut angular_momenta = vec![1.0];
+        for _ in 0..20 {
+            for _ in 0..100 {
+                integrate_step(&mut bodies, Integrator::Leapfrog, 0.01, &params);
+            }
+            angular_momenta.push(bodies[0].position.cross(bodies[0].velocity).length());
+        }
+        assert!(angular_momenta.windows(2).all(|pair| pair[1] < pair[0]), "L {:?}", angular_momenta);
+        let radius = bodies[0].position.length();
+        assert!(radius < 0.9, "r = {}", radius);
+    }
+}
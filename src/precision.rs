@@ -0,0 +1,85 @@
+// 粒子状态与引力计算使用的浮点精度. 默认为 f64; 启用 f32 特性后改用单精度与 glam::Vec3,
+// 粒子数据的内存与带宽减半. 注意单精度的相对精度只有约 1e-7, 引力求和与积分的舍入误差累积很快,
+// 能量通常只能守恒到 1e-4 量级; SI 单位下的大数 (如 1e30 kg 的质量) 也更容易溢出.
+// 模拟时间, 配置参数, 能量与其它诊断量的累加始终使用 f64.
+use glam::DVec3;
+
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+#[cfg(not(feature = "f32"))]
+pub use glam::DVec3 as Vec3;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+
+#[cfg(feature = "f32")]
+pub type Real = f32;
+#[cfg(feature = "f32")]
+pub use glam::Vec3;
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+
+// 转换为 f64, 用于诊断量的累加与绘图; 默认精度下是恒等转换
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f64(x: Real) -> f64 {
+    x as f64
+}
+
+pub fn to_dvec3(v: Vec3) -> DVec3 {
+    DVec3::new(to_f64(v.x), to_f64(v.y), to_f64(v.z))
+}
+
+pub fn from_dvec3(v: DVec3) -> Vec3 {
+    Vec3::new(v.x as Real, v.y as Real, v.z as Real)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::test_params;
+    use crate::generators::generate_kepler;
+    use crate::integrators::{integrate_step, update_forces, Integrator};
+
+    // 两体 Leapfrog (KDK) 的 f64 参考实现, 与当前精度下的积分器对照
+    fn reference_leapfrog(positions: &mut [DVec3; 2], velocities: &mut [DVec3; 2], masses: [f64; 2], dt: f64, steps: usize) {
+        let accelerations = |x: &[DVec3; 2]| {
+            let r = x[1] - x[0];
+            let factor = r / r.length().powi(3);
+            [factor * masses[1], -factor * masses[0]]
+        };
+        let mut a = accelerations(positions);
+        for _ in 0..steps {
+            for i in 0..2 {
+                velocities[i] += a[i] * (dt / 2.0);
+                positions[i] += velocities[i] * dt;
+            }
+            a = accelerations(positions);
+            for i in 0..2 {
+                velocities[i] += a[i] * (dt / 2.0);
+            }
+        }
+    }
+
+    #[test]
+    fn short_integration_agrees_with_f64_reference_to_working_precision() {
+        let params = test_params(1.0, 0.0);
+        let mut bodies = generate_kepler(0.7, 0.3, 1.0, 0.5, 1.0);
+        let mut positions = [to_dvec3(bodies[0].position), to_dvec3(bodies[1].position)];
+        let mut velocities = [to_dvec3(bodies[0].velocity), to_dvec3(bodies[1].velocity)];
+        let masses = [to_f64(bodies[0].mass), to_f64(bodies[1].mass)];
+
+        let steps = 1000;
+        let dt = consts::TAU / steps as Real;
+        update_forces(&mut bodies, Integrator::Leapfrog, &params);
+        for _ in 0..steps {
+            integrate_step(&mut bodies, Integrator::Leapfrog, dt, &params);
+        }
+        reference_leapfrog(&mut positions, &mut velocities, masses, to_f64(dt), steps);
+
+        // 舍入误差随步数累积, 容差按当前精度的机器 epsilon 放大
+        let tolerance = 1e4 * to_f64(Real::EPSILON);
+        for (body, (x, v)) in bodies.iter().zip(positions.iter().zip(&velocities)) {
+            assert!(to_dvec3(body.position).distance(*x) < tolerance, "{:?} vs {:?}", body.position, x);
+            assert!(to_dvec3(body.velocity).distance(*v) < tolerance, "{:?} vs {:?}", body.velocity, v);
+        }
+    }
+}
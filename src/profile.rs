@@ -0,0 +1,75 @@
+// --profile: 统计主循环的墙钟时间花在哪里. 受力计算分散在各积分器内部, 用全局累加器记录;
+// 其余阶段由主循环在阶段之间调用 Profiler::lap 划分. 未启用时 time_forces 只多一次原子读取
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static FORCE_NANOS: AtomicU64 = AtomicU64::new(0);
+
+// 执行一次受力计算, 启用时累计其耗时
+pub fn time_forces<T>(f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    FORCE_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    result
+}
+
+pub fn force_time() -> Duration {
+    Duration::from_nanos(FORCE_NANOS.load(Ordering::Relaxed))
+}
+
+// 主循环中的阶段; Step 包含受力计算, 报告时再把两者分开
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Step,
+    Diagnostics,
+    Output,
+}
+
+pub struct Profiler {
+    enabled: bool,
+    started: Instant,
+    last: Instant,
+    // 按 Phase 的顺序
+    totals: [Duration; 3],
+}
+
+impl Profiler {
+    // 启用时同时打开受力计时
+    pub fn new(enabled: bool) -> Self {
+        if enabled {
+            ENABLED.store(true, Ordering::Relaxed);
+        }
+        let now = Instant::now();
+        Self { enabled, started: now, last: now, totals: [Duration::ZERO; 3] }
+    }
+
+    // 把上一次调用以来的时间计入 phase
+    pub fn lap(&mut self, phase: Phase) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        self.totals[phase as usize] += now - self.last;
+        self.last = now;
+    }
+
+    // (阶段名, 耗时) 各行, 最后一行为总时间; 未计入任何阶段的时间 (进度条等) 归为 other
+    pub fn report(&self) -> Vec<(&'static str, Duration)> {
+        let total = self.started.elapsed();
+        let [step, diagnostics, output] = self.totals;
+        let forces = force_time().min(step);
+        let other = total.saturating_sub(step + diagnostics + output);
+        vec![
+            ("forces", forces),
+            ("integration", step - forces),
+            ("diagnostics", diagnostics),
+            ("output", output),
+            ("other", other),
+            ("total", total),
+        ]
+    }
+}
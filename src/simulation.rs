@@ -0,0 +1,398 @@
+// 模拟的驱动: 持有粒子, 配置与当前的时间和步数, 每次 step 推进一个积分步.
+// 输出文件 (图像, 诊断 CSV, 检查点) 不在这里写, 由调用者在两步之间自行处理
+use crate::collision::Merger;
+use crate::config::{Config, Softening};
+use crate::cosmology::{comoving_leapfrog_step, Cosmology};
+use crate::integrators::{adaptive_dt, dopri5_step, subcycled_step, update_forces};
+use crate::precision::to_f64;
+use crate::{block, collision, diagnostics, integrate_step, Body, ForceMethod, ForceParams, Integrator, Real};
+use crate::{BoundaryCondition, SimError, SofteningKernel, Vec3};
+use log::{info, warn};
+
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub bodies: Vec<Body>,
+    pub config: Config,
+    // 当前的模拟时间与已完成的步数; 续算时可直接改写为检查点中的值
+    pub time: f64,
+    pub step_count: usize,
+    // 累计计算受力的粒子次数, 全局步长下每一步为 N 次
+    pub force_evaluations: usize,
+    // 最近一步中的合并事件, 每步开始时清空
+    pub mergers: Vec<Merger>,
+    force_params: ForceParams,
+    cosmology: Option<Cosmology>,
+}
+
+// 一步中发生的事件, 供调用者记录日志; 粒子数改变后能量不再与之前可比
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StepReport {
+    // 这一步实际推进的时间
+    pub dt: f64,
+    // 近距离交会时的子步数, 未细分时为 1
+    pub substeps: usize,
+    pub collisions: usize,
+    // 碰撞合并与逃逸各自删除的粒子数
+    pub merged: usize,
+    pub escaped: usize,
+}
+
+impl StepReport {
+    pub fn bodies_removed(&self) -> bool {
+        self.merged + self.escaped > 0
+    }
+}
+
+impl Simulation {
+    // 检查配置的组合, 把初始位置放回盒内并计算初始受力. 时间与步数从零开始
+    pub fn new(mut bodies: Vec<Body>, config: Config) -> Result<Self, SimError> {
+        let invalid = |message: &str| Err(SimError::Config(message.to_string()));
+        // 平面模拟: 把初始条件投影到 x-y 平面
+        if config.planar {
+            let lifted = bodies.iter().filter(|b| b.position.z != 0.0 || b.velocity.z != 0.0).count();
+            if lifted > 0 {
+                warn!("--planar: set z and vz to zero for {} bodies.", lifted);
+            }
+            for body in bodies.iter_mut() {
+                body.position.z = 0.0;
+                body.velocity.z = 0.0;
+            }
+        }
+        // 固定粒子不动, 其速度 (包括动能与动量中的贡献) 没有意义
+        let moving = bodies.iter().filter(|b| b.fixed && b.velocity != Vec3::ZERO).count();
+        if moving > 0 {
+            warn!("Set the velocity of {} fixed bodies to zero.", moving);
+        }
+        for body in bodies.iter_mut().filter(|b| b.fixed) {
+            body.velocity = Vec3::ZERO;
+        }
+        let softening = match config.softening_factor {
+            Softening::Length(length) => length,
+            Softening::Auto => {
+                if config.softening_fraction <= 0.0 || !config.softening_fraction.is_finite() {
+                    return invalid(&format!("softening_fraction must be positive, got {:e}", config.softening_fraction));
+                }
+                let length = diagnostics::auto_softening(&bodies, config.softening_fraction);
+                info!("Automatic softening length: {:.6e} {}", length, config.units.length());
+                length
+            }
+        };
+        let force_params = ForceParams {
+            g: config.units.gravitational_constant(),
+            softening: softening as Real,
+            kernel: config.softening_kernel,
+            method: config.force_method(),
+            boundary: config.boundary_condition()?,
+            external: config.external_potential()?,
+            friction: config.dynamical_friction()?,
+            planar: config.planar,
+            force_exponent: config.force_exponent as Real,
+        };
+
+        // 非牛顿力律只实现了 Plummer 软化的直接求和; Hermite 的 jerk 与块时间步都按 1 / r^2 推导
+        if config.force_exponent != 2.0 {
+            if config.force_exponent <= 0.0 || !config.force_exponent.is_finite() {
+                return invalid(&format!("force_exponent must be positive, got {:e}", config.force_exponent));
+            }
+            if force_params.method != ForceMethod::Direct || force_params.kernel != SofteningKernel::Plummer {
+                return invalid("--force-exponent other than 2 requires --force direct and the Plummer softening kernel");
+            }
+            if config.integrator == Integrator::Hermite {
+                return invalid("--force-exponent other than 2 does not support the Hermite integrator");
+            }
+            warn!("Force law 1/r^{}: not Newtonian gravity.", config.force_exponent);
+        }
+
+        // Hermite 的 jerk 只对孤立系统的直接求和实现
+        if config.integrator == Integrator::Hermite {
+            let extra_forces = force_params.external.is_some() || force_params.friction.is_some();
+            if force_params.boundary != BoundaryCondition::Open || extra_forces {
+                return invalid("The Hermite integrator does not support box boundaries, external potentials or dynamical friction");
+            }
+            if force_params.method != ForceMethod::Direct {
+                warn!("The Hermite integrator always uses direct summation; --force is ignored.");
+            }
+            if force_params.kernel != SofteningKernel::Plummer {
+                return invalid("The Hermite integrator only supports the Plummer softening kernel");
+            }
+        }
+
+        // PM 的格林函数建立在周期性盒子上, 逐轴 FFT 要求网格边长为 2 的幂
+        if let ForceMethod::ParticleMesh { grid } = force_params.method {
+            if force_params.boundary.periodic_size().is_none() {
+                return invalid("--force pm requires a periodic box (--box-size)");
+            }
+            if grid < 2 || !grid.is_power_of_two() {
+                return invalid(&format!("pm_grid must be a power of two of at least 2, got {}", grid));
+            }
+        }
+
+        if force_params.method == ForceMethod::Ewald && force_params.boundary.periodic_size().is_none() {
+            return invalid("--force ewald requires a periodic box (--box-size)");
+        }
+
+        // 块时间步建立在 Hermite 格式之上, 每个基本步内部自行选择子步长
+        if config.block_timesteps {
+            if config.integrator != Integrator::Hermite {
+                return invalid("--block-timesteps requires --integrator hermite");
+            }
+            if config.adaptive_dt {
+                return invalid("--block-timesteps cannot be combined with --adaptive-dt");
+            }
+            if config.block_max_level > 62 {
+                return invalid(&format!("block_max_level must be at most 62, got {}", config.block_max_level));
+            }
+        }
+
+        // Dormand-Prince 由误差估计自行细分每一步
+        if config.integrator == Integrator::Dopri5 {
+            if config.dopri_tolerance <= 0.0 || !config.dopri_tolerance.is_finite() {
+                return invalid(&format!("dopri_tolerance must be positive, got {:e}", config.dopri_tolerance));
+            }
+            if config.encounter_eta.is_some() {
+                return invalid("--encounter-eta cannot be combined with --integrator dopri5, which controls its own substeps");
+            }
+        }
+
+        // 块时间步本身已按粒子细分步长, 不再叠加交会子步
+        if let Some(eta) = config.encounter_eta {
+            if config.block_timesteps {
+                return invalid("--encounter-eta cannot be combined with --block-timesteps");
+            }
+            if eta <= 0.0 {
+                return invalid(&format!("encounter_eta must be positive, got {:e}", eta));
+            }
+            if config.encounter_max_level > 62 {
+                return invalid(&format!("encounter_max_level must be at most 62, got {}", config.encounter_max_level));
+            }
+        }
+
+        // 共动坐标的运动方程假设平均密度已从引力中减去, 即周期性盒子; 积分使用专门的 Leapfrog 步
+        let cosmology = config.cosmology()?;
+        if cosmology.is_some() {
+            if !matches!(force_params.boundary, BoundaryCondition::Periodic { .. }) {
+                return invalid("--initial-scale-factor requires a periodic box (--box-size)");
+            }
+            if config.integrator != Integrator::Leapfrog || config.block_timesteps || config.encounter_eta.is_some() {
+                return invalid("--initial-scale-factor only supports the leapfrog integrator with a global step");
+            }
+            if force_params.external.is_some() || force_params.friction.is_some() {
+                return invalid("--initial-scale-factor cannot be combined with an external potential or dynamical friction");
+            }
+            if !matches!(force_params.method, ForceMethod::ParticleMesh { .. } | ForceMethod::Ewald) {
+                warn!("Minimum-image forces do not subtract the mean density; prefer --force pm or ewald in comoving runs.");
+            }
+        }
+
+        let simulation =
+            Self { bodies: Vec::new(), config, time: 0.0, step_count: 0, force_evaluations: 0, mergers: Vec::new(), force_params, cosmology };
+        let (dt_min, dt_max) = simulation.dt_limits();
+        if simulation.config.adaptive_dt && !(0.0 < dt_min && dt_min <= dt_max) {
+            return invalid(&format!("Invalid adaptive step limits: dt_min = {:e}, dt_max = {:e}", dt_min, dt_max));
+        }
+
+        // 碰撞检测使用普通距离, 不考虑周期性镜像
+        if simulation.config.collisions.is_some() {
+            if force_params.boundary.periodic_size().is_some() {
+                return invalid("--collisions does not support periodic boxes");
+            }
+            if bodies.iter().any(|b| b.fixed) {
+                return invalid("--collisions does not support fixed bodies");
+            }
+            if bodies.iter().all(|b| b.radius.is_none()) {
+                warn!("No body has a radius; --collisions has no effect.");
+            }
+        }
+
+        // 盒子边界: 初始位置同样放回盒内; 检查点中不保存加速度, 续算时同样需要先计算受力
+        force_params.boundary.apply(&mut bodies);
+        update_forces(&mut bodies, simulation.config.integrator, &force_params);
+        Ok(Self { bodies, ..simulation })
+    }
+
+    pub fn force_params(&self) -> &ForceParams {
+        &self.force_params
+    }
+
+    // 当前的尺度因子, 非宇宙学模拟时为 None
+    pub fn scale_factor(&self) -> Option<f64> {
+        self.cosmology.map(|cosmology| cosmology.scale_factor(self.time))
+    }
+
+    // 自适应步长时的结束时间 (未给出时为 time_steps * dt)
+    pub fn t_end(&self) -> f64 {
+        self.config.t_end.unwrap_or(self.config.time_steps as f64 * self.config.dt)
+    }
+
+    // 自适应步长的上下限, 默认为 dt / 1000 与 dt
+    fn dt_limits(&self) -> (f64, f64) {
+        let dt = self.config.dt;
+        (self.config.dt_min.unwrap_or(dt / 1000.0), self.config.dt_max.unwrap_or(dt))
+    }
+
+    // 自适应步长时以模拟时间, 否则以步数决定是否结束
+    pub fn is_finished(&self) -> bool {
+        if self.config.adaptive_dt {
+            self.time >= self.t_end()
+        } else {
+            self.step_count >= self.config.time_steps
+        }
+    }
+
+    // 推进一个积分步, 随后按配置检查有限性, 处理碰撞与逃逸粒子; 粒子数改变后重新计算受力
+    pub fn step(&mut self) -> Result<StepReport, SimError> {
+        let config = &self.config;
+        let params = &self.force_params;
+        let dt = if config.adaptive_dt {
+            let (dt_min, dt_max) = self.dt_limits();
+            let step_dt = to_f64(adaptive_dt(&self.bodies, config.eta as Real, params.softening)).clamp(dt_min, dt_max);
+            step_dt.min(self.t_end() - self.time)
+        } else {
+            config.dt
+        };
+
+        let mut report = StepReport { dt, substeps: 1, ..StepReport::default() };
+        if let Some(cosmology) = &self.cosmology {
+            comoving_leapfrog_step(&mut self.bodies, self.time, dt, cosmology, params);
+            self.force_evaluations += self.bodies.len();
+        } else if config.block_timesteps {
+            let (eta, max_level) = (config.block_eta as Real, config.block_max_level);
+            self.force_evaluations += block::block_step(&mut self.bodies, dt as Real, eta, max_level, params);
+        } else if config.integrator == Integrator::Dopri5 {
+            let (substeps, evaluations) = dopri5_step(&mut self.bodies, dt as Real, config.dopri_tolerance as Real, params);
+            report.substeps = substeps;
+            self.force_evaluations += self.bodies.len() * evaluations;
+        } else if let Some(eta) = config.encounter_eta {
+            let (integrator, max_level) = (config.integrator, config.encounter_max_level);
+            report.substeps = subcycled_step(&mut self.bodies, integrator, dt as Real, eta as Real, max_level, params);
+            self.force_evaluations += self.bodies.len() * report.substeps;
+        } else {
+            integrate_step(&mut self.bodies, config.integrator, dt as Real, params);
+            self.force_evaluations += self.bodies.len();
+        }
+
+        let previous = self.step_count;
+        self.step_count += 1;
+        self.time += dt;
+
+        if config.check_finite > 0 && previous.is_multiple_of(config.check_finite) {
+            diagnostics::check_finite(&self.bodies, previous)?;
+        }
+
+        // 碰撞改变了速度 (合并时还有粒子数), 需要重新计算受力
+        if let Some(mode) = config.collisions {
+            let before = self.bodies.len();
+            self.mergers.clear();
+            report.collisions = collision::handle_collisions(&mut self.bodies, mode, &mut self.mergers);
+            report.merged = before - self.bodies.len();
+            if report.collisions > 0 {
+                update_forces(&mut self.bodies, config.integrator, params);
+            }
+        }
+
+        // 删除逃逸粒子后粒子数改变, 需要重新计算加速度
+        if let Some(radius) = config.escape_radius {
+            if config.escape_interval > 0 && self.step_count.is_multiple_of(config.escape_interval) {
+                report.escaped = diagnostics::remove_escapers(&mut self.bodies, radius as Real);
+                if report.escaped > 0 {
+                    update_forces(&mut self.bodies, config.integrator, params);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    // 最多推进 n_steps 步, 到达配置的结束时间或步数时提前停止
+    pub fn run(&mut self, n_steps: usize) -> Result<(), SimError> {
+        for _ in 0..n_steps {
+            if self.is_finished() {
+                break;
+            }
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExternalKind;
+    use crate::diagnostics::total_energy;
+    use crate::generators::{generate_kepler, generate_plummer};
+    use crate::units::Units;
+
+    // N-body 单位下的配置, 软化长度与步长按单位质量, 单位半径的星团选择
+    fn nbody_config() -> Config {
+        Config { units: Units::Nbody, softening_factor: Softening::Length(0.01), dt: 1e-3, ..Config::default() }
+    }
+
+    #[test]
+    fn unbound_body_far_away_is_removed() {
+        let mut bodies = generate_plummer(100, 1.0, 1.0, 11, 1.0);
+        bodies.push(Body::new(0.01, Vec3::new(1000.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0)));
+        Body::assign_ids(&mut bodies);
+        let config = Config { escape_radius: Some(100.0), escape_interval: 1, ..nbody_config() };
+        let mut sim = Simulation::new(bodies, config).unwrap();
+
+        let report = sim.step().unwrap();
+        assert_eq!(report.escaped, 1);
+        assert!(report.bodies_removed());
+        assert_eq!(sim.bodies.len(), 100);
+        assert!(sim.bodies.iter().all(|b| b.id != 100));
+    }
+
+    #[test]
+    fn planar_orbit_stays_exactly_in_plane() {
+        // 初始略微离开平面, 外加一个在平面外的点质量, 两者的 z 分量都应被去掉
+        let mut bodies = generate_kepler(0.5, 0.5, 1.0, 0.3, 1.0);
+        bodies[0].position.z = 0.1;
+        bodies[1].velocity.z = -0.05;
+        let config = Config {
+            planar: true,
+            external: Some(ExternalKind::Point),
+            external_mass: 0.1,
+            external_position: vec![0.0, 0.0, 0.5],
+            ..nbody_config()
+        };
+        let mut sim = Simulation::new(bodies, config).unwrap();
+        sim.run(2000).unwrap();
+        assert!(sim.bodies.iter().all(|b| b.position.z == 0.0 && b.velocity.z == 0.0), "{:?}", sim.bodies);
+        assert!(sim.bodies.iter().any(|b| b.position.x != 0.0 || b.position.y != 0.0));
+    }
+
+    #[test]
+    fn frozen_body_stays_put_but_still_attracts() {
+        let mut bodies = vec![
+            Body { fixed: true, ..Body::new(1.0, Vec3::ZERO, Vec3::ZERO) },
+            Body::new(1e-3, Vec3::new(1.0, 0.0, 0.0), Vec3::ZERO),
+        ];
+        Body::assign_ids(&mut bodies);
+        let mut sim = Simulation::new(bodies, nbody_config()).unwrap();
+        sim.run(500).unwrap();
+
+        assert_eq!(sim.bodies[0].position, Vec3::ZERO);
+        assert_eq!(sim.bodies[0].velocity, Vec3::ZERO);
+        // 自由下落 0.5 个时间单位: x ~ 1 - t^2 / 2, 速度指向冻结粒子
+        let tracer = &sim.bodies[1];
+        assert!(tracer.position.x < 0.9 && tracer.velocity.x < -0.4, "{:?}", tracer);
+    }
+
+    #[test]
+    fn non_integer_force_exponent_conserves_energy_with_its_potential() {
+        // 1 / r^2.5 力律下的小星团; 能量诊断使用对应的势 -G m / (1.5 r^1.5)
+        let bodies = generate_plummer(32, 1.0, 1.0, 5, 1.0);
+        let config = Config { force_exponent: 2.5, softening_factor: Softening::Length(0.05), ..nbody_config() };
+        let mut sim = Simulation::new(bodies, config).unwrap();
+        let (_, _, initial) = total_energy(&sim.bodies, sim.force_params());
+        sim.run(2000).unwrap();
+        let (_, _, last) = total_energy(&sim.bodies, sim.force_params());
+
+        let drift = ((last - initial) / initial).abs();
+        assert!(drift < 1e-4, "relative energy drift {:e}", drift);
+        // 势能用的是 n = 2.5 的势而不是牛顿势: 换成牛顿势时同一状态的能量明显不同
+        let newtonian = ForceParams { force_exponent: 2.0, ..*sim.force_params() };
+        assert!((total_energy(&sim.bodies, &newtonian).2 - last).abs() > 1e-2 * last.abs());
+    }
+}
@@ -0,0 +1,245 @@
+// 单位制: 决定万有引力常数 G 以及输出中各物理量的单位名称
+use crate::precision::consts::PI;
+use crate::diagnostics::center_of_mass;
+use crate::precision::{from_dvec3, to_dvec3, to_f64};
+use crate::{Body, Real, SimError, G};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// Meters, kilograms and seconds
+    #[default]
+    Si,
+    /// Astronomical units, solar masses and years, where G = 4 pi^2
+    Astro,
+    /// Dimensionless N-body (Henon) units with G = 1; see the nbody-units command
+    Nbody,
+}
+
+impl Units {
+    // 该单位制下的 G; 天文单位制中取 4 pi^2, 即 1 M_sun 质量的主星外 1 AU 圆轨道的周期恰为 1 yr
+    pub fn gravitational_constant(self) -> Real {
+        match self {
+            Units::Si => G,
+            Units::Astro => 4.0 * PI * PI,
+            Units::Nbody => 1.0,
+        }
+    }
+
+    // 光速, 引力波应变的换算需要; 1 AU = 1.495978707e11 m, 1 yr = 365.25 d. N-body 单位没有固定的物理尺度, 没有光速
+    pub fn speed_of_light(self) -> Option<f64> {
+        const SPEED_OF_LIGHT: f64 = 299_792_458.0;
+        match self {
+            Units::Si => Some(SPEED_OF_LIGHT),
+            Units::Astro => Some(SPEED_OF_LIGHT * 365.25 * 86400.0 / 1.495978707e11),
+            Units::Nbody => None,
+        }
+    }
+
+    // 写入日志与图像标题的简短名称
+    pub fn label(self) -> &'static str {
+        match self {
+            Units::Si => "SI (m, kg, s)",
+            Units::Astro => "AU, M_sun, yr",
+            Units::Nbody => "N-body (G = M = R_v = 1)",
+        }
+    }
+
+    pub fn length(self) -> &'static str {
+        match self {
+            Units::Si => "m",
+            Units::Astro => "AU",
+            Units::Nbody => "R_v",
+        }
+    }
+
+    pub fn mass(self) -> &'static str {
+        match self {
+            Units::Si => "kg",
+            Units::Astro => "M_sun",
+            Units::Nbody => "M",
+        }
+    }
+
+    pub fn time(self) -> &'static str {
+        match self {
+            Units::Si => "s",
+            Units::Astro => "yr",
+            Units::Nbody => "T",
+        }
+    }
+
+    pub fn speed(self) -> &'static str {
+        match self {
+            Units::Si => "m/s",
+            Units::Astro => "AU/yr",
+            Units::Nbody => "R_v/T",
+        }
+    }
+
+    pub fn energy(self) -> &'static str {
+        match self {
+            Units::Si => "J",
+            Units::Astro => "M_sun AU^2/yr^2",
+            Units::Nbody => "M R_v^2/T^2",
+        }
+    }
+
+    pub fn momentum(self) -> &'static str {
+        match self {
+            Units::Si => "kg m/s",
+            Units::Astro => "M_sun AU/yr",
+            Units::Nbody => "M R_v/T",
+        }
+    }
+
+    pub fn angular_momentum(self) -> &'static str {
+        match self {
+            Units::Si => "kg m^2/s",
+            Units::Astro => "M_sun AU^2/yr",
+            Units::Nbody => "M R_v^2/T",
+        }
+    }
+}
+
+// N-body 单位与原单位制之间的换算因子: 原单位制中的量 = N-body 单位中的量 * 对应的因子.
+// nbody-units 把它写成 JSON 文件, physical-units 读回后把 N-body 单位的结果换回原单位制
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScaleFactors {
+    pub length: f64,
+    pub mass: f64,
+    pub time: f64,
+}
+
+impl ScaleFactors {
+    pub fn velocity(&self) -> f64 {
+        self.length / self.time
+    }
+
+    pub fn energy(&self) -> f64 {
+        self.mass * self.velocity() * self.velocity()
+    }
+
+    // 从 N-body 单位换回原单位制, 用于输出
+    pub fn to_physical(&self, bodies: &mut [Body]) {
+        scale_bodies(bodies, self.length, self.mass, self.time);
+    }
+
+    // 从原单位制换算到 N-body 单位
+    pub fn to_nbody(&self, bodies: &mut [Body]) {
+        scale_bodies(bodies, self.length.recip(), self.mass.recip(), self.time.recip());
+    }
+}
+
+// 按长度, 质量与时间的因子缩放粒子的全部有量纲的字段 (包括软化长度, 半径与积分器的状态).
+// 乘法在 f64 中进行后再转为 Real: 银河系尺度的因子 (如 1e-41) 在单精度中是零或次正规数
+fn scale_bodies(bodies: &mut [Body], length: f64, mass: f64, time: f64) {
+    let scale = |v, factor: f64| from_dvec3(to_dvec3(v) * factor);
+    let scalar = |x: Real, factor: f64| (to_f64(x) * factor) as Real;
+    bodies.par_iter_mut().for_each(|body| {
+        body.mass = scalar(body.mass, mass);
+        body.position = scale(body.position, length);
+        body.velocity = scale(body.velocity, length / time);
+        body.acceleration = scale(body.acceleration, length / (time * time));
+        body.jerk = scale(body.jerk, length / (time * time * time));
+        body.timestep = scalar(body.timestep, time);
+        body.softening = body.softening.map(|eps| scalar(eps, length));
+        body.radius = body.radius.map(|r| scalar(r, length));
+    });
+}
+
+// 不软化的两两势能之和 W, 以 f64 计算, O(N^2); 位置重合的粒子对跳过
+fn pair_potential_energy(bodies: &[Body], g: f64) -> f64 {
+    (0..bodies.len())
+        .into_par_iter()
+        .map(|i| {
+            let (pos_i, m_i) = (to_dvec3(bodies[i].position), to_f64(bodies[i].mass));
+            bodies[i + 1..]
+                .iter()
+                .map(|b| (to_dvec3(b.position).distance(pos_i), to_f64(b.mass)))
+                .filter(|&(distance, _)| distance > 0.0)
+                .map(|(distance, m_j)| -g * m_i * m_j / distance)
+                .sum::<f64>()
+        })
+        .sum()
+}
+
+// 把 bodies 换算到 N-body (Henon) 单位并返回换算因子: G = 1, 总质量 M = 1, 位力半径 R_v = -G M^2 / (2 W) = 1,
+// 时间单位为 sqrt(R_v^3 / (G M)); g 为 bodies 原单位制中的 G. 换算因子与 W 都在 f64 中计算.
+// 单位变换本身不改变动力学: 位力比 K / |W| 不变, 只有处于位力平衡 (2K = -W) 的系统能量恰为 -1/4.
+// virialize 时先把质心系中的速度统一缩放到 2K = -W, 换算后的能量就是 E = W / 2 = -1/4, 但速度分布的形状不变.
+// 注意单精度构建读入银河系尺度的 SI 数值 (质量 ~1e41 kg) 时就已溢出, 应在 f64 构建中换算后再用单精度运行
+pub fn rescale_to_nbody_units(bodies: &mut [Body], g: Real, virialize: bool) -> Result<ScaleFactors, SimError> {
+    let g = to_f64(g);
+    let mass: f64 = bodies.iter().map(|b| to_f64(b.mass)).sum();
+    let potential = pair_potential_energy(bodies, g);
+    if mass <= 0.0 || potential >= 0.0 {
+        return Err(SimError::Config("N-body units need a positive total mass and at least two separated bodies".to_string()));
+    }
+    if virialize {
+        let mean = to_dvec3(center_of_mass(bodies).1);
+        let kinetic: f64 = bodies.iter().map(|b| 0.5 * to_f64(b.mass) * (to_dvec3(b.velocity) - mean).length_squared()).sum();
+        if kinetic <= 0.0 {
+            return Err(SimError::Config("--virialize needs bodies that move relative to their center of mass".to_string()));
+        }
+        let factor = (-potential / (2.0 * kinetic)).sqrt();
+        for body in bodies.iter_mut() {
+            body.velocity = from_dvec3(mean + (to_dvec3(body.velocity) - mean) * factor);
+        }
+    }
+    let length = -g * mass * mass / (2.0 * potential);
+    let factors = ScaleFactors { length, mass, time: (length.powi(3) / (g * mass)).sqrt() };
+    factors.to_nbody(bodies);
+    Ok(factors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force::test_params;
+    use crate::generators::{generate_kepler, generate_plummer};
+    use crate::integrators::{integrate_step, update_forces, Integrator};
+
+    #[test]
+    fn one_au_orbit_around_the_sun_takes_one_year() {
+        // 质量可忽略的行星在 1 AU 圆轨道上, 积分 1 yr 后应回到起点
+        let g = Units::Astro.gravitational_constant();
+        let params = test_params(g, 0.0);
+        let mut bodies = generate_kepler(1.0, 1e-10, 1.0, 0.0, g);
+        let start = bodies[1].position;
+        update_forces(&mut bodies, Integrator::Yoshida4, &params);
+        for _ in 0..1000 {
+            integrate_step(&mut bodies, Integrator::Yoshida4, 1e-3, &params);
+        }
+        assert!((start.length() - 1.0).abs() < 1e-9, "r = {}", start.length());
+        assert!(bodies[1].position.distance(start) < 1e-6, "{:?} -> {:?}", start, bodies[1].position);
+    }
+
+    #[test]
+    fn virialized_rescale_gives_henon_units() {
+        // SI 数值的 Plummer 球, 速度加倍后远离位力平衡
+        let g = Units::Si.gravitational_constant();
+        let mut bodies = generate_plummer(500, 1e12, 1e5, 4, g);
+        for body in &mut bodies {
+            body.velocity *= 2.0;
+        }
+        let original = bodies.clone();
+        let factors = rescale_to_nbody_units(&mut bodies, g, true).unwrap();
+
+        let mass: f64 = bodies.iter().map(|b| to_f64(b.mass)).sum();
+        let potential = pair_potential_energy(&bodies, 1.0);
+        let kinetic: f64 = bodies.iter().map(|b| 0.5 * to_f64(b.mass) * to_f64(b.velocity.length_squared())).sum();
+        assert!((mass - 1.0).abs() < 1e-12, "M = {}", mass);
+        assert!((-mass * mass / (2.0 * potential) - 1.0).abs() < 1e-12, "W = {}", potential);
+        assert!((kinetic + potential + 0.25).abs() < 1e-12, "E = {}", kinetic + potential);
+
+        // 换回原单位制: 质量与位置复原, 速度是位力平衡后的速度
+        factors.to_physical(&mut bodies);
+        for (body, original) in bodies.iter().zip(&original) {
+            assert!((body.mass / original.mass - 1.0).abs() < 1e-12);
+            assert!(body.position.distance(original.position) < 1e-12 * 1e5);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// 通过库中的驱动完整地运行一次模拟, 与 nbody_simulation 可执行文件所做的相同, 只是不经过命令行
+use nbody_simulation::config::{Config, Softening};
+use nbody_simulation::driver::simulate;
+use nbody_simulation::generators::generate_kepler;
+use nbody_simulation::output::write_bodies;
+use nbody_simulation::units::Units;
+
+#[test]
+fn short_run_writes_the_diagnostics_csv() {
+    let dir = std::env::temp_dir().join(format!("nbody_driver_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("kepler.json");
+    write_bodies(&generate_kepler(0.5, 0.5, 1.0, 0.3, 1.0), &input, 0.0).unwrap();
+
+    let csv = dir.join("diagnostics.csv");
+    let config = Config {
+        units: Units::Nbody,
+        softening_factor: Softening::Length(0.0),
+        dt: 1e-3,
+        time_steps: 20,
+        plot_interval: 10,
+        png_frames: false,
+        progress_bar: false,
+        input_file: input,
+        output_dir: dir.clone(),
+        diagnostics_csv: Some(csv.clone()),
+        ..Config::default()
+    };
+    simulate(config).unwrap();
+
+    // 表头, 初始状态, 以及两个绘图间隔各一行
+    let contents = std::fs::read_to_string(&csv).unwrap();
+    let lines: Vec<&str> = contents.lines().collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(lines.len(), 4, "{}", contents);
+    assert!(lines[0].starts_with("step,time,kinetic"));
+    // 两体问题的能量守恒: 各行的总能量相差很小
+    let totals: Vec<f64> = lines[1..].iter().map(|line| line.split(',').nth(4).unwrap().parse().unwrap()).collect();
+    assert!(totals.iter().all(|e| (e - totals[0]).abs() < 1e-6 * totals[0].abs()), "{:?}", totals);
+}
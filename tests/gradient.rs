@@ -0,0 +1,44 @@
+// 受力与势能的一致性: 各种受力计算给出的加速度应等于总势能的负梯度 -grad U / m.
+// 梯度用 diagnostics::gradient_error 中的中心差分估计, 初始条件是固定种子的 Plummer 球
+use nbody_simulation::diagnostics::gradient_error;
+use nbody_simulation::generators::generate_plummer;
+use nbody_simulation::{Body, BoundaryCondition, ForceMethod, ForceParams, Real, SofteningKernel};
+
+fn plummer() -> Vec<Body> {
+    generate_plummer(200, 1.0, 1.0, 5, 1.0)
+}
+
+fn params(method: ForceMethod, kernel: SofteningKernel, softening: Real) -> ForceParams {
+    ForceParams {
+        g: 1.0,
+        softening,
+        kernel,
+        method,
+        boundary: BoundaryCondition::Open,
+        external: None,
+        friction: None,
+        planar: false,
+        force_exponent: 2.0,
+    }
+}
+
+#[test]
+fn direct_summation_matches_the_potential_gradient() {
+    let error = gradient_error(&plummer(), &params(ForceMethod::Direct, SofteningKernel::Plummer, 0.01), 16);
+    assert!(error < 1e-4, "max relative error {:e}", error);
+}
+
+#[test]
+fn cubic_spline_kernel_matches_the_potential_gradient() {
+    // 软化长度较大, 许多粒子对落在核内
+    let error = gradient_error(&plummer(), &params(ForceMethod::Direct, SofteningKernel::CubicSpline, 0.1), 16);
+    assert!(error < 1e-4, "max relative error {:e}", error);
+}
+
+#[test]
+fn tree_forces_match_the_potential_gradient_to_the_opening_error() {
+    // 势能按精确的两两求和计算, 误差主要来自树的多极近似
+    let method = ForceMethod::BarnesHut { theta: 0.3 };
+    let error = gradient_error(&plummer(), &params(method, SofteningKernel::Plummer, 0.01), 16);
+    assert!(error < 0.02, "max relative error {:e}", error);
+}